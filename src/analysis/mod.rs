@@ -0,0 +1,8 @@
+mod replication;
+mod routing;
+
+pub use replication::{
+    audit_replication, published_keys, KeyReplication, ReplicationAuditor, ReplicationReport,
+    ReplicationSummary,
+};
+pub use routing::{PeerRoutingHealth, PeerStatus, RoutingHealthReport};
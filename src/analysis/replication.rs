@@ -0,0 +1,298 @@
+use crate::{peer::Peer, storage::RecordData, Environment, Key, PeerId};
+use dslab_core::{cast, Event, EventHandler, Simulation, SimulationContext};
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    rc::Rc,
+};
+
+/// Represents the replication of a single key at the moment of the audit.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyReplication {
+    pub key: Key,
+    /// The number of the true k closest peers to the key holding an unexpired record of it.
+    pub closest_holding: usize,
+    /// The number of the true k closest peers holding the record that are online.
+    pub closest_online_holding: usize,
+    /// The number of providers listed in the records held by the k closest peers.
+    pub providers: usize,
+    /// The number of the listed providers that are online and hold the data.
+    pub providers_online: usize,
+    /// Whether the data can be found: an online peer among the k closest holds a record
+    /// listing an online provider that holds the data.
+    pub reachable: bool,
+}
+
+/// Represents the aggregated replication of the audited keys.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplicationSummary {
+    /// The simulation time of the audit.
+    pub time: f64,
+    /// The number of the audited keys.
+    pub keys: usize,
+    /// The mean share of the k closest peers holding the record.
+    pub mean_replication: f64,
+    /// The mean share of the k closest peers holding the record that are online.
+    pub mean_online_replication: f64,
+    /// The mean number of online providers holding the data.
+    pub mean_online_providers: f64,
+    /// The share of the keys that are reachable.
+    pub reachable_ratio: f64,
+}
+
+/// Represents the result of a replication audit.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplicationReport {
+    pub summary: ReplicationSummary,
+    pub keys: Vec<KeyReplication>,
+}
+
+impl ReplicationReport {
+    /// Writes the report to the JSON file.
+    pub fn write_json(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+}
+
+/// Returns the keys of the data ever published by any of the peers, sorted to keep the order
+/// of the audit results deterministic. The keys whose data has been removed or lost are kept,
+/// so that the audits over time follow the same set of keys.
+pub fn published_keys(peers: &[Rc<RefCell<Peer>>]) -> Vec<Key> {
+    let mut keys = peers
+        .iter()
+        .flat_map(|peer| peer.borrow().published_keys().cloned().collect::<Vec<_>>())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    keys.sort_unstable_by_key(|key| key.to_string());
+    keys
+}
+
+/// Audits the replication of the keys using the global knowledge of the network.
+/// The audit does not affect the state of the peers.
+///
+/// # Arguments
+///
+/// * `env` - The environment of the simulation.
+/// * `peers` - All the peers of the simulation.
+/// * `keys` - The keys to audit, or `None` to audit all the published keys.
+/// * `curr_time` - The current simulation time.
+pub fn audit_replication(
+    env: &Environment,
+    peers: &[Rc<RefCell<Peer>>],
+    keys: Option<&[Key]>,
+    curr_time: f64,
+) -> ReplicationReport {
+    let keys = match keys {
+        Some(keys) => keys.to_vec(),
+        None => published_keys(peers),
+    };
+    let peers = peers
+        .iter()
+        .map(|peer| {
+            let peer = peer.borrow();
+            (peer.id(), peer)
+        })
+        .collect::<HashMap<PeerId, _>>();
+
+    let keys = keys
+        .into_iter()
+        .map(|key| {
+            let mut replication = KeyReplication {
+                key: key.clone(),
+                closest_holding: 0,
+                closest_online_holding: 0,
+                providers: 0,
+                providers_online: 0,
+                reachable: false,
+            };
+            let mut providers = HashSet::new();
+            let mut reachable_providers = HashSet::new();
+            for peer_id in env.find_closest_peers(&key, env.k()) {
+                let Some(peer) = peers.get(&peer_id) else {
+                    continue;
+                };
+                let Some(record) = peer.peek_dht_record(&key) else {
+                    continue;
+                };
+                replication.closest_holding += 1;
+                let RecordData::ProviderRecord {
+                    providers: listed, ..
+                } = &record.data;
+                providers.extend(listed.iter().map(|provider| provider.id));
+                if peer.is_online() {
+                    replication.closest_online_holding += 1;
+                    reachable_providers.extend(listed.iter().map(|provider| provider.id));
+                }
+            }
+            let is_serving = |id: &PeerId| {
+                peers
+                    .get(id)
+                    .is_some_and(|peer| peer.is_online() && peer.has_data(&key))
+            };
+            replication.providers = providers.len();
+            replication.providers_online = providers.iter().filter(|id| is_serving(id)).count();
+            replication.reachable = reachable_providers.iter().any(is_serving);
+            replication
+        })
+        .collect::<Vec<_>>();
+
+    let mut summary = ReplicationSummary {
+        time: curr_time,
+        keys: keys.len(),
+        mean_replication: 0.,
+        mean_online_replication: 0.,
+        mean_online_providers: 0.,
+        reachable_ratio: 0.,
+    };
+    if !keys.is_empty() {
+        let n = keys.len() as f64;
+        let k = env.k() as f64;
+        summary.mean_replication = keys
+            .iter()
+            .map(|r| r.closest_holding as f64 / k)
+            .sum::<f64>()
+            / n;
+        summary.mean_online_replication = keys
+            .iter()
+            .map(|r| r.closest_online_holding as f64 / k)
+            .sum::<f64>()
+            / n;
+        summary.mean_online_providers =
+            keys.iter().map(|r| r.providers_online as f64).sum::<f64>() / n;
+        summary.reachable_ratio = keys.iter().filter(|r| r.reachable).count() as f64 / n;
+    }
+    ReplicationReport { summary, keys }
+}
+
+/// Periodically audits the replication of all the published keys.
+pub struct ReplicationAuditor {
+    ctx: SimulationContext,
+    env: Rc<Environment>,
+    peers: Vec<Rc<RefCell<Peer>>>,
+    interval: f64,
+    history: Vec<ReplicationSummary>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ReplicationAuditTimer {}
+
+impl ReplicationAuditor {
+    /// Registers a new `ReplicationAuditor` within the given simulation.
+    ///
+    /// # Arguments
+    ///
+    /// * `sim` - A mutable reference to the simulation.
+    /// * `env` - The environment of the simulation.
+    /// * `peers` - All the peers of the simulation.
+    /// * `interval` - The interval between the audits in seconds.
+    ///
+    /// # Returns
+    ///
+    /// The registered auditor.
+    pub fn register(
+        sim: &mut Simulation,
+        env: Rc<Environment>,
+        peers: Vec<Rc<RefCell<Peer>>>,
+        interval: f64,
+    ) -> Rc<RefCell<Self>> {
+        let name = "replication_auditor";
+        let ctx = sim.create_context(name);
+        ctx.emit_self(ReplicationAuditTimer {}, interval);
+        let auditor = Rc::new(RefCell::new(Self {
+            ctx,
+            env,
+            peers,
+            interval,
+            history: vec![],
+        }));
+        sim.add_handler(name, auditor.clone());
+        auditor
+    }
+
+    /// Returns the summaries of the audits made so far.
+    pub fn history(&self) -> &[ReplicationSummary] {
+        &self.history
+    }
+
+    /// Writes the summaries of the audits to the CSV file.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "time,keys,mean_replication,mean_online_replication,\
+             mean_online_providers,reachable_ratio"
+        )?;
+        for s in self.history.iter() {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                s.time,
+                s.keys,
+                s.mean_replication,
+                s.mean_online_replication,
+                s.mean_online_providers,
+                s.reachable_ratio,
+            )?;
+        }
+        writer.flush()
+    }
+}
+
+impl EventHandler for ReplicationAuditor {
+    fn on(&mut self, event: Event) {
+        cast!(match event.data {
+            ReplicationAuditTimer {} => {
+                let report = audit_replication(&self.env, &self.peers, None, self.ctx.time());
+                self.history.push(report.summary);
+                self.ctx.emit_self(ReplicationAuditTimer {}, self.interval);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{environment::test_config, network::NetworkAgent, peer::test_peers};
+
+    #[test]
+    fn test_audit_replication() {
+        let env = Environment::new(test_config(16));
+        let mut sim = Simulation::new(0);
+        let network = NetworkAgent::from_function(|_, _, _| Some(0.1));
+        let peers = test_peers(&mut sim, test_config(16), network);
+
+        let key = Key::from_sha256(b"data");
+        peers[3].borrow_mut().publish_data("data".to_string());
+        sim.step_until_time(10.);
+        let report = audit_replication(&env, &peers, None, sim.time());
+        assert_eq!(report.summary.keys, 1);
+        assert_eq!(report.keys[0].key, key);
+        assert!(report.keys[0].closest_holding > 0);
+        assert_eq!(report.keys[0].providers, 1);
+        assert_eq!(report.keys[0].providers_online, 1);
+        assert!(report.keys[0].reachable);
+        assert_eq!(report.summary.reachable_ratio, 1.);
+
+        // the removed data is still audited, but can no longer be found
+        peers[3].borrow_mut().remove_data(key.clone());
+        let report = audit_replication(&env, &peers, None, sim.time());
+        assert_eq!(report.summary.keys, 1);
+        assert_eq!(report.keys[0].providers_online, 0);
+        assert!(!report.keys[0].reachable);
+        assert_eq!(report.summary.reachable_ratio, 0.);
+
+        let other = Key::from_sha256(b"other");
+        let report = audit_replication(&env, &peers, Some(&[other]), sim.time());
+        assert_eq!(report.summary.keys, 1);
+        assert_eq!(report.keys[0].closest_holding, 0);
+        assert!(!report.keys[0].reachable);
+    }
+}
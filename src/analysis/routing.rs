@@ -0,0 +1,258 @@
+use crate::{kbucket::KBucketsTable, query::Histogram, Environment, PeerId};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Represents the actual state of a peer known to the simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerStatus {
+    Online,
+    Offline,
+    /// The peer is not a part of the simulation.
+    Nonexistent,
+}
+
+/// Represents the health of the k-buckets table of a single peer.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerRoutingHealth {
+    pub peer_id: PeerId,
+    /// The number of entries in the table.
+    pub entries: usize,
+    /// The fill ratio of every bucket, i.e. its length divided by k.
+    pub bucket_fill: Vec<f64>,
+    /// The number of entries pointing to offline peers.
+    pub offline_entries: usize,
+    /// The number of entries pointing to peers that do not exist.
+    pub nonexistent_entries: usize,
+    /// The number of the true k closest peers to the local key that are in the table.
+    pub k_closest_known: usize,
+    /// The mean time elapsed since the entries were last seen.
+    pub mean_staleness: Option<f64>,
+    /// The maximum time elapsed since the entries were last seen.
+    pub max_staleness: Option<f64>,
+}
+
+impl PeerRoutingHealth {
+    /// Analyzes the k-buckets table of a peer against the ground truth.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The environment of the simulation.
+    /// * `peer_id` - The ID of the peer owning the table.
+    /// * `table` - The k-buckets table of the peer.
+    /// * `status` - The function returning the actual state of a peer.
+    /// * `curr_time` - The current simulation time.
+    pub fn analyze(
+        env: &Environment,
+        peer_id: PeerId,
+        table: &KBucketsTable,
+        status: impl Fn(PeerId) -> PeerStatus,
+        curr_time: f64,
+    ) -> Self {
+        let k = env.k();
+        let true_closest = env.find_closest_peers(&table.local_key(), k + 1);
+        let mut health = Self {
+            peer_id,
+            entries: 0,
+            bucket_fill: (0..table.buckets_count())
+                .map(|i| table.bucket_len(i) as f64 / k as f64)
+                .collect(),
+            offline_entries: 0,
+            nonexistent_entries: 0,
+            k_closest_known: 0,
+            mean_staleness: None,
+            max_staleness: None,
+        };
+        let mut staleness = Histogram::new();
+        for (_, entry_id, last_seen) in table.entries() {
+            health.entries += 1;
+            match status(entry_id) {
+                PeerStatus::Online => {}
+                PeerStatus::Offline => health.offline_entries += 1,
+                PeerStatus::Nonexistent => health.nonexistent_entries += 1,
+            }
+            if entry_id != peer_id && true_closest.contains(&entry_id) {
+                health.k_closest_known += 1;
+            }
+            staleness.add(curr_time - last_seen);
+        }
+        health.mean_staleness = staleness.mean();
+        health.max_staleness = staleness.percentile(100.);
+        health
+    }
+}
+
+/// Represents the health of the k-buckets tables of all the peers.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoutingHealthReport {
+    /// The simulation time of the analysis.
+    pub time: f64,
+    /// The mean number of entries in a table.
+    pub mean_entries: f64,
+    /// The mean fill ratio of the buckets at every index over all the tables.
+    /// Missing buckets are treated as empty.
+    pub mean_bucket_fill: Vec<f64>,
+    /// The share of the entries pointing to offline peers.
+    pub offline_entries_ratio: f64,
+    /// The share of the entries pointing to peers that do not exist.
+    pub nonexistent_entries_ratio: f64,
+    /// The mean share of the true k closest peers known by a peer.
+    pub k_closest_known_ratio: f64,
+    /// The distribution of the time elapsed since the entries were last seen.
+    pub staleness: Histogram,
+    /// The health of every table.
+    pub peers: Vec<PeerRoutingHealth>,
+}
+
+impl RoutingHealthReport {
+    /// Aggregates the health of the tables of the peers.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - The environment of the simulation.
+    /// * `peers` - The peer IDs with their k-buckets tables.
+    /// * `status` - The function returning the actual state of a peer.
+    /// * `curr_time` - The current simulation time.
+    pub fn analyze<'a>(
+        env: &Environment,
+        peers: impl IntoIterator<Item = (PeerId, &'a KBucketsTable)>,
+        status: impl Fn(PeerId) -> PeerStatus,
+        curr_time: f64,
+    ) -> Self {
+        let mut report = Self {
+            time: curr_time,
+            mean_entries: 0.,
+            mean_bucket_fill: vec![],
+            offline_entries_ratio: 0.,
+            nonexistent_entries_ratio: 0.,
+            k_closest_known_ratio: 0.,
+            staleness: Histogram::new(),
+            peers: vec![],
+        };
+        for (peer_id, table) in peers {
+            for (_, _, last_seen) in table.entries() {
+                report.staleness.add(curr_time - last_seen);
+            }
+            report.peers.push(PeerRoutingHealth::analyze(
+                env, peer_id, table, &status, curr_time,
+            ));
+        }
+        if report.peers.is_empty() {
+            return report;
+        }
+
+        let peers_count = report.peers.len() as f64;
+        let entries = report.peers.iter().map(|p| p.entries).sum::<usize>();
+        report.mean_entries = entries as f64 / peers_count;
+        let buckets_count = report
+            .peers
+            .iter()
+            .map(|p| p.bucket_fill.len())
+            .max()
+            .unwrap_or(0);
+        report.mean_bucket_fill = (0..buckets_count)
+            .map(|i| {
+                report
+                    .peers
+                    .iter()
+                    .filter_map(|p| p.bucket_fill.get(i))
+                    .sum::<f64>()
+                    / peers_count
+            })
+            .collect();
+        if entries > 0 {
+            let offline = report
+                .peers
+                .iter()
+                .map(|p| p.offline_entries)
+                .sum::<usize>();
+            let nonexistent = report
+                .peers
+                .iter()
+                .map(|p| p.nonexistent_entries)
+                .sum::<usize>();
+            report.offline_entries_ratio = offline as f64 / entries as f64;
+            report.nonexistent_entries_ratio = nonexistent as f64 / entries as f64;
+        }
+        report.k_closest_known_ratio = report
+            .peers
+            .iter()
+            .map(|p| p.k_closest_known as f64 / env.k() as f64)
+            .sum::<f64>()
+            / peers_count;
+        report
+    }
+
+    /// Writes the report to the JSON file.
+    pub fn write_json(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+
+    /// Writes the health of every table to the CSV file, one peer per line.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "peer_id,entries,buckets,offline_entries,nonexistent_entries,\
+             k_closest_known,mean_staleness,max_staleness"
+        )?;
+        for p in self.peers.iter() {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{}",
+                p.peer_id,
+                p.entries,
+                p.bucket_fill.len(),
+                p.offline_entries,
+                p.nonexistent_entries,
+                p.k_closest_known,
+                p.mean_staleness.map(|s| s.to_string()).unwrap_or_default(),
+                p.max_staleness.map(|s| s.to_string()).unwrap_or_default(),
+            )?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::test_env;
+
+    #[test]
+    fn test_analyze() {
+        let env = test_env(64);
+        let local_id = 0;
+        let mut table = KBucketsTable::new(env.clone(), env.peer_key(local_id));
+        let closest = env.find_closest_peers(env.peer_key(local_id), env.k() + 1);
+        let known = closest.iter().copied().find(|&id| id != local_id).unwrap();
+        table.add_peer(known, 5.);
+        let other = (1..).find(|id| !closest.contains(id)).unwrap();
+        table.add_peer(other, 8.);
+
+        let status = |id| {
+            if id == other {
+                PeerStatus::Offline
+            } else {
+                PeerStatus::Online
+            }
+        };
+        let health = PeerRoutingHealth::analyze(&env, local_id, &table, status, 10.);
+        assert_eq!(health.entries, 2);
+        assert_eq!(health.offline_entries, 1);
+        assert_eq!(health.nonexistent_entries, 0);
+        assert_eq!(health.k_closest_known, 1);
+        assert_eq!(health.mean_staleness, Some(3.5));
+        assert_eq!(health.max_staleness, Some(5.));
+
+        let report = RoutingHealthReport::analyze(&env, [(local_id, &table)], status, 10.);
+        assert_eq!(report.mean_entries, 2.);
+        assert_eq!(report.offline_entries_ratio, 0.5);
+        assert_eq!(report.staleness.count(), 2);
+    }
+}
@@ -1,43 +1,146 @@
 use crate::{
-    network::{NetworkAgent, UserLoadGenerator},
+    analysis::{
+        audit_replication, PeerStatus, ReplicationAuditor, ReplicationReport, ReplicationSummary,
+        RoutingHealthReport,
+    },
+    config::{ConfigError, FieldError},
+    logger,
+    monitor::QueryMonitor,
+    network::{
+        ChurnModel, NetworkAgent, TraceReplayer, UptimeModel, UserLoadGenerator, WorkloadTrace,
+    },
     peer::Peer,
-    Key, PeerId, CONFIG,
+    profile::ProfileStats,
+    query::{busiest_peers, PeerTraffic, QueriesStats},
+    report::{suffixed_path, Report},
+    sampler::{MetricsSample, MetricsSampler},
+    scenario::{Action, NamedSelection, PeerSelection, ScenarioSpec, StatsSnapshot},
+    trace::{TraceFilter, Tracer},
+    Environment, Key, PeerId, SimulationConfig,
 };
 use dslab_core::{Simulation, SimulationContext};
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::Path,
+    rc::Rc,
+};
 
 /// Represents the application that runs the IPFS simulator.
 pub struct App {
     sim: Simulation,
+    env: Rc<Environment>,
     peers: Vec<Rc<RefCell<Peer>>>,
     peer_ids: Vec<PeerId>,
     network: NetworkAgent,
     user_load: Option<Rc<RefCell<UserLoadGenerator>>>,
+    trace_replayer: Option<Rc<RefCell<TraceReplayer>>>,
+    churn_model: Option<Rc<RefCell<ChurnModel>>>,
+    uptime_model: Option<Rc<RefCell<UptimeModel>>>,
+    tracer: Option<Tracer>,
+    metrics_sampler: Option<Rc<RefCell<MetricsSampler>>>,
+    replication_auditor: Option<Rc<RefCell<ReplicationAuditor>>>,
+    query_monitor: Option<Rc<QueryMonitor>>,
+    snapshots: Vec<StatsSnapshot>,
 }
 
 impl App {
-    /// Creates a new `App` instance and adds the peers to the simulation.
-    pub fn new() -> Self {
+    /// Creates a new `App` instance with the given configuration
+    /// and adds the peers to the simulation.
+    ///
+    /// # Returns
+    ///
+    /// The created instance, or an error if the log file cannot be created
+    /// or the workload trace cannot be loaded.
+    pub fn new(config: SimulationConfig) -> Result<Self, ConfigError> {
+        let env = Rc::new(Environment::new(config));
+        let config = env.config();
         let mut app = Self {
-            sim: Simulation::new(CONFIG.seed),
+            sim: Simulation::new(config.seed),
+            env: env.clone(),
             peers: vec![],
             peer_ids: vec![],
             network: NetworkAgent::from_topology_and_delay_distribution(
-                CONFIG.topology.clone(),
-                CONFIG.delay_distribution.clone(),
+                config.topology.clone(),
+                config.delay_distribution.clone(),
             ),
             user_load: None,
+            trace_replayer: None,
+            churn_model: None,
+            uptime_model: None,
+            tracer: None,
+            metrics_sampler: None,
+            replication_auditor: None,
+            query_monitor: None,
+            snapshots: vec![],
         };
-        if let Some(path) = CONFIG.log_file_path.as_ref() {
-            simple_logging::log_to_file(path, CONFIG.log_level_filter).unwrap();
-        } else {
-            simple_logging::log_to_stderr(CONFIG.log_level_filter);
+        // the sink is set for the current thread only, so that the apps run in parallel
+        // by a sweep do not reconfigure the logging of each other
+        logger::log_to(config.log_file_path.as_ref(), config.log_level_filter)
+            .map_err(|err| field_error("log_file_path", err))?;
+        if let Some(path) = config.trace_file_path.as_ref() {
+            let filter = TraceFilter {
+                // the validation ensures that the peers are set with the query IDs
+                queries: config.trace_query_ids.as_ref().map(|ids| {
+                    let peers = config.trace_peers.as_deref().unwrap_or_default();
+                    peers
+                        .iter()
+                        .flat_map(|&peer| ids.iter().map(move |&id| (peer, id.into())))
+                        .collect()
+                }),
+                peers: config
+                    .trace_peers
+                    .as_ref()
+                    .map(|peers| peers.iter().copied().collect()),
+            };
+            app.tracer = Some(
+                Tracer::to_file(path, filter).map_err(|err| field_error("trace_file_path", err))?,
+            );
         }
         app.add_peers();
-        if CONFIG.enable_user_load_generation {
-            app.user_load = Some(UserLoadGenerator::register(&mut app.sim, app.peers.clone()));
+        if let Some(interval) = config.metrics_sampling_interval {
+            app.metrics_sampler = Some(MetricsSampler::register(
+                &mut app.sim,
+                app.peers.clone(),
+                interval,
+            ));
+        }
+        if let Some(interval) = config.replication_audit_interval {
+            app.replication_auditor = Some(ReplicationAuditor::register(
+                &mut app.sim,
+                env.clone(),
+                app.peers.clone(),
+                interval,
+            ));
+        }
+        if config
+            .profiles
+            .iter()
+            .any(|profile| profile.uptime.is_some())
+        {
+            app.uptime_model = Some(UptimeModel::register(&mut app.sim, &env, app.peers.clone()));
         }
-        app
+        if config.churn.is_some() {
+            app.churn_model = Some(ChurnModel::register(&mut app.sim, &env, app.peers.clone()));
+        }
+        if config.enable_user_load_generation {
+            app.user_load = Some(UserLoadGenerator::register(
+                &mut app.sim,
+                &env,
+                app.peers.clone(),
+            ));
+        }
+        if let Some(trace_config) = config.workload_trace.as_ref() {
+            let trace = WorkloadTrace::from_file(trace_config, config.num_peers)
+                .map_err(|err| field_error("workload_trace.path", err))?;
+            app.trace_replayer = Some(TraceReplayer::register(
+                &mut app.sim,
+                app.peers.clone(),
+                trace,
+                trace_config.preload_missing,
+            ));
+        }
+        Ok(app)
     }
 
     /// Changes the network filter of the application.
@@ -52,18 +155,72 @@ impl App {
         self.network = NetworkAgent::from_function(filter);
     }
 
+    /// Returns the environment of the simulation.
+    pub fn env(&self) -> &Rc<Environment> {
+        &self.env
+    }
+
+    /// Returns the configuration of the simulation.
+    pub fn config(&self) -> &SimulationConfig {
+        self.env.config()
+    }
+
+    /// Returns the peers of the simulation indexed by their IDs.
+    pub fn peers(&self) -> &[Rc<RefCell<Peer>>] {
+        &self.peers
+    }
+
+    /// Returns the monitor of the finished queries of all the peers.
+    /// The monitor is created and subscribed to the peers on the first call,
+    /// so the notifications are not sent if it is never used.
+    pub fn query_monitor(&mut self) -> Rc<QueryMonitor> {
+        if let Some(monitor) = self.query_monitor.as_ref() {
+            return monitor.clone();
+        }
+        let monitor = QueryMonitor::register(&mut self.sim);
+        let monitor_id = monitor.id();
+        for peer in self.peers.iter() {
+            peer.borrow_mut().subscribe(monitor_id);
+        }
+        self.query_monitor = Some(monitor.clone());
+        monitor
+    }
+
+    /// Returns the snapshots of the statistics taken so far, see `take_snapshot`.
+    pub fn snapshots(&self) -> &[StatsSnapshot] {
+        &self.snapshots
+    }
+
+    /// Saves the statistics of all the peers collected so far under the given name
+    /// without resetting them.
+    pub fn take_snapshot(&mut self, name: &str) {
+        let mut stats = QueriesStats::new();
+        for peer in self.peers.iter() {
+            stats.merge(peer.borrow().peek_stats());
+        }
+        self.snapshots.push(StatsSnapshot {
+            name: name.to_string(),
+            time: self.sim.time(),
+            stats,
+        });
+    }
+
     /// Adds the peers to the simulation.
-    /// The number of peers is retrieved from the configuration file.
+    /// The number of peers is retrieved from the configuration.
     fn add_peers(&mut self) {
-        let n = CONFIG.num_peers;
+        let n = self.env.config().num_peers;
         let width = (n - 1).to_string().len();
         for i in 0..n {
             let name = format!("peer-{:01$}", i, width);
             let peer = Rc::new(RefCell::new(Peer::new(
                 &mut self.sim,
+                self.env.clone(),
                 &name,
                 self.network.clone(),
             )));
+            if let Some(tracer) = self.tracer.as_ref() {
+                peer.borrow_mut().set_tracer(tracer.clone());
+            }
             self.peer_ids
                 .push(self.sim.add_handler(&name, peer.clone()));
             self.peers.push(peer);
@@ -73,60 +230,422 @@ impl App {
         }
     }
 
+    /// Returns the samples of the network-wide metrics taken so far.
+    /// The samples are empty if the sampling is disabled in the configuration.
+    pub fn metrics_samples(&self) -> Vec<MetricsSample> {
+        self.metrics_sampler
+            .as_ref()
+            .map(|sampler| sampler.borrow().samples().to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Analyzes the k-buckets tables of all the peers against the ground truth.
+    pub fn routing_table_health(&self) -> RoutingHealthReport {
+        let peers = self
+            .peers
+            .iter()
+            .map(|peer| peer.borrow())
+            .collect::<Vec<_>>();
+        let online = peers
+            .iter()
+            .map(|peer| (peer.id(), peer.is_online()))
+            .collect::<HashMap<_, _>>();
+        let status = |peer_id| match online.get(&peer_id) {
+            Some(true) => PeerStatus::Online,
+            Some(false) => PeerStatus::Offline,
+            None => PeerStatus::Nonexistent,
+        };
+        RoutingHealthReport::analyze(
+            &self.env,
+            peers.iter().map(|peer| (peer.id(), peer.kbuckets())),
+            status,
+            self.sim.time(),
+        )
+    }
+
+    /// Audits how the records of the keys are replicated among their true k closest peers
+    /// and whether the data is reachable.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The keys to audit, or `None` to audit all the published keys.
+    pub fn audit_replication(&self, keys: Option<&[Key]>) -> ReplicationReport {
+        audit_replication(&self.env, &self.peers, keys, self.sim.time())
+    }
+
+    /// Returns the summaries of the periodic replication audits made so far.
+    /// The summaries are empty if the audits are disabled in the configuration.
+    pub fn replication_history(&self) -> Vec<ReplicationSummary> {
+        self.replication_auditor
+            .as_ref()
+            .map(|auditor| auditor.borrow().history().to_vec())
+            .unwrap_or_default()
+    }
+
     /// Extracts the statistics from the peers and logs them.
-    pub fn summarize_stats(&self) {
-        let mut stats = crate::query::QueriesStats::new();
+    /// The reports of the run are written if they are enabled in the configuration.
+    ///
+    /// # Returns
+    ///
+    /// The merged statistics of all the peers.
+    pub fn summarize_stats(&self) -> QueriesStats {
+        let profile_stats = self.stats_by_profile();
+        let mut stats = QueriesStats::new();
+        let mut traffic = vec![];
         for peer in self.peers.iter() {
-            stats.merge(&peer.borrow_mut().stats());
+            let mut peer = peer.borrow_mut();
+            let peer_stats = peer.stats();
+            traffic.push(PeerTraffic {
+                peer_id: peer.id(),
+                counter: peer_stats.traffic.total,
+            });
+            stats.merge(&peer_stats);
         }
+        let busiest_peers = busiest_peers(traffic, self.env.config().report_busiest_peers);
         log::error!("{:#?}", stats);
+        log::error!("Busiest peers: {:#?}", busiest_peers);
+        if !profile_stats.is_empty() {
+            log::error!("Stats by profile: {:#?}", profile_stats);
+        }
+        if let (Some(sampler), Some(path)) = (
+            self.metrics_sampler.as_ref(),
+            self.env.config().metrics_file_path.as_ref(),
+        ) {
+            if let Err(err) = sampler.borrow().write_csv(path) {
+                log::error!("Failed to write metrics to {}: {}", path, err);
+            }
+        }
+        if let (Some(auditor), Some(path)) = (
+            self.replication_auditor.as_ref(),
+            self.env.config().replication_audit_path.as_ref(),
+        ) {
+            if let Err(err) = auditor.borrow().write_csv(path) {
+                log::error!("Failed to write replication audits to {}: {}", path, err);
+            }
+        }
+        if let Some(path) = self.env.config().routing_health_path.as_ref() {
+            if let Err(err) = self.routing_table_health().write_json(path) {
+                log::error!("Failed to write routing table health to {}: {}", path, err);
+            }
+        }
+        if let Some(tracer) = self.tracer.as_ref() {
+            if let Err(err) = tracer.flush() {
+                log::error!("Failed to flush trace: {}", err);
+            }
+        }
+        let mut report = Report::new(self.env.config(), self.sim.time(), &stats, &busiest_peers);
+        report.profiles = &profile_stats;
+        if let Some(path) = self.env.config().report_json_path.as_ref() {
+            if let Err(err) = report.write_json(path) {
+                log::error!("Failed to write JSON report to {}: {}", path, err);
+            }
+        }
+        if let Some(path) = self.env.config().report_csv_path.as_ref() {
+            if let Err(err) = report.write_csv(path) {
+                log::error!("Failed to write CSV report to {}: {}", path, err);
+            }
+        }
+        stats
+    }
+
+    /// Merges the statistics collected so far by the peers of every profile.
+    ///
+    /// # Returns
+    ///
+    /// The statistics in the order of the profiles in the configuration,
+    /// or an empty vector if there are no profiles.
+    pub fn stats_by_profile(&self) -> Vec<ProfileStats> {
+        let mut profile_stats = self
+            .env
+            .config()
+            .profiles
+            .iter()
+            .map(|profile| ProfileStats {
+                name: profile.name.clone(),
+                peers: 0,
+                stats: QueriesStats::new(),
+            })
+            .collect::<Vec<_>>();
+        for peer in self.peers.iter() {
+            let peer = peer.borrow();
+            let Some(profile) = self.env.peer_profile(peer.id()) else {
+                continue;
+            };
+            let group = profile_stats
+                .iter_mut()
+                .find(|group| group.name == profile.name)
+                .unwrap();
+            group.peers += 1;
+            group.stats.merge(peer.peek_stats());
+        }
+        profile_stats
     }
 
     /// Runs the simulation.
     /// You're expected to override this function to define the simulation scenario.
-    pub fn run(&mut self) {
-        // self.sim.step_until_time(CONFIG.kbuckets_refresh_interval);
+    ///
+    /// # Returns
+    ///
+    /// The merged statistics of all the peers.
+    pub fn run(&mut self) -> QueriesStats {
+        // self.sim.step_until_time(self.env.config().kbuckets_refresh_interval);
         // for peer in self.peers.iter() {
         //     peer.borrow_mut().clear_storage();
         // }
 
-        self.summarize_stats();
+        self.summarize_stats()
+    }
+
+    /// Runs the scenario described by the specification.
+    /// The times of the steps are counted from the current simulation time.
+    /// If the JSON report is configured, the snapshots taken by the scenario
+    /// are written next to it into files with the names of the snapshots as suffixes.
+    ///
+    /// # Returns
+    ///
+    /// The merged statistics of all the peers, or the problems found
+    /// if the scenario cannot be run with the configuration of the application.
+    pub fn run_scenario(&mut self, scenario: &ScenarioSpec) -> Result<QueriesStats, ConfigError> {
+        scenario.validate(self.env.config())?;
+        let start = self.sim.time();
+
+        // every step is split into operations ordered by time and then by the order of the steps
+        let mut operations = vec![];
+        for (i, step) in scenario.steps.iter().enumerate() {
+            match step.action {
+                Action::Publish {
+                    count, interval, ..
+                }
+                | Action::Retrieve {
+                    count, interval, ..
+                } => {
+                    for j in 0..count {
+                        operations.push((start + step.at + j as f64 * interval, i, j));
+                    }
+                }
+                Action::GoOffline {
+                    duration: Some(duration),
+                    ..
+                } => {
+                    operations.push((start + step.at, i, 0));
+                    operations.push((start + step.at + duration, i, 1));
+                }
+                _ => operations.push((start + step.at, i, 0)),
+            }
+        }
+        operations.sort_by(|a, b| a.0.total_cmp(&b.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+
+        let content_keys = scenario
+            .steps
+            .iter()
+            .map(|step| match step.action {
+                Action::Retrieve {
+                    content: Some(ref content),
+                    ..
+                } => content
+                    .iter()
+                    .map(|data| Key::from_sha256(data.as_bytes()))
+                    .collect(),
+                _ => vec![],
+            })
+            .collect::<Vec<Vec<_>>>();
+        let mut published = vec![];
+        let mut published_set = HashSet::new();
+        let mut offline = HashMap::new();
+        let mut next_block = 0;
+        for (time, i, j) in operations {
+            if time > self.sim.time() {
+                self.sim.step_until_time(time);
+            }
+            match scenario.steps[i].action {
+                Action::Publish {
+                    ref peers,
+                    ref content,
+                    size,
+                    ..
+                } => {
+                    let data = match (content, size) {
+                        (Some(content), _) => content[j % content.len()].clone(),
+                        (None, Some(size)) => self.sim.random_string(size),
+                        (None, None) => {
+                            next_block += 1;
+                            format!("data-{}", next_block - 1)
+                        }
+                    };
+                    if let Some(idx) = self.choose_peer(peers) {
+                        let key = Key::from_sha256(data.as_bytes());
+                        self.peers[idx].borrow_mut().publish_data(data);
+                        if published_set.insert(key.clone()) {
+                            published.push(key);
+                        }
+                    }
+                }
+                Action::Retrieve {
+                    ref peers,
+                    ref content,
+                    popularity,
+                    ..
+                } => {
+                    let keys = if content.is_some() {
+                        &content_keys[i]
+                    } else {
+                        &published
+                    };
+                    if keys.is_empty() {
+                        log::warn!("Step {}: nothing to retrieve", i);
+                        continue;
+                    }
+                    let rank = self
+                        .sim
+                        .sample_from_distribution(&popularity.rank(keys.len(), j));
+                    let key = keys[rank].clone();
+                    if let Some(idx) = self.choose_peer(peers) {
+                        self.peers[idx].borrow_mut().retrieve_data(key);
+                    }
+                }
+                Action::StartUserLoad => match self.user_load.as_ref() {
+                    Some(generator) => generator.borrow_mut().set_active(true),
+                    None => {
+                        self.user_load = Some(UserLoadGenerator::register(
+                            &mut self.sim,
+                            &self.env,
+                            self.peers.clone(),
+                        ));
+                    }
+                },
+                Action::StopUserLoad => {
+                    if let Some(generator) = self.user_load.as_ref() {
+                        generator.borrow_mut().set_active(false);
+                    }
+                }
+                Action::Partition { ref groups } => {
+                    let groups = groups
+                        .iter()
+                        .map(|group| {
+                            group
+                                .iter()
+                                .map(|&idx| self.peer_ids[idx as usize])
+                                .collect()
+                        })
+                        .collect::<Vec<_>>();
+                    self.network.set_partition(&groups);
+                }
+                Action::Heal => self.network.heal_partition(),
+                Action::GoOffline { ref peers, .. } if j == 0 => {
+                    let peers = self.select_peers(peers);
+                    for &idx in peers.iter() {
+                        self.peers[idx].borrow_mut().set_online(false);
+                    }
+                    offline.insert(i, peers);
+                }
+                Action::GoOffline { .. } => {
+                    for idx in offline.remove(&i).unwrap_or_default() {
+                        self.peers[idx].borrow_mut().set_online(true);
+                    }
+                }
+                Action::GoOnline { ref peers } => {
+                    for idx in self.select_peers(peers) {
+                        self.peers[idx].borrow_mut().set_online(true);
+                    }
+                }
+                Action::Snapshot { ref name } => self.take_snapshot(name),
+                Action::ClearStorage { ref peers } => {
+                    for idx in self.select_peers(peers) {
+                        self.peers[idx].borrow_mut().clear_storage();
+                    }
+                }
+            }
+        }
+
+        match scenario.duration {
+            Some(duration) => {
+                self.sim.step_until_time(start + duration);
+            }
+            None => self.sim.step_until_no_events(),
+        }
+        if let Some(path) = self.env.config().report_json_path.as_ref() {
+            for snapshot in self.snapshots.iter() {
+                let path = suffixed_path(Path::new(path), &snapshot.name);
+                let report = Report::new(self.env.config(), snapshot.time, &snapshot.stats, &[]);
+                if let Err(err) = report.write_json(&path) {
+                    log::error!("Failed to write snapshot to {}: {}", path.display(), err);
+                }
+            }
+        }
+        Ok(self.summarize_stats())
+    }
+
+    /// Returns the indices of the selected peers.
+    fn select_peers(&mut self, selection: &PeerSelection) -> Vec<usize> {
+        let n = self.peers.len();
+        let count = match *selection {
+            PeerSelection::Ids(ref ids) => return ids.iter().map(|&id| id as usize).collect(),
+            PeerSelection::Named(NamedSelection::All) => return (0..n).collect(),
+            PeerSelection::Named(NamedSelection::Random) => 1,
+            PeerSelection::Count { count } => count,
+            PeerSelection::Fraction { fraction } => (fraction * n as f64).round() as usize,
+        };
+        // partial Fisher-Yates shuffle
+        let mut indices = (0..n).collect::<Vec<_>>();
+        for i in 0..count.min(n) {
+            let j = self.sim.gen_range(i..n);
+            indices.swap(i, j);
+        }
+        indices.truncate(count);
+        indices
+    }
+
+    /// Returns the index of a random peer of the selection.
+    fn choose_peer(&mut self, selection: &PeerSelection) -> Option<usize> {
+        match *selection {
+            PeerSelection::Named(_) => Some(self.sim.gen_range(0..self.peers.len())),
+            PeerSelection::Ids(ref ids) => Some(ids[self.sim.gen_range(0..ids.len())] as usize),
+            _ => {
+                let peers = self.select_peers(selection);
+                if peers.is_empty() {
+                    return None;
+                }
+                Some(peers[self.sim.gen_range(0..peers.len())])
+            }
+        }
     }
 
     /// Runs the simulation with no queries.
     /// This is useful to measure the overhead of the simulation or
     /// to explore background tasks.
-    pub fn run_scenario_no_queries(&mut self) {
+    pub fn run_scenario_no_queries(&mut self) -> QueriesStats {
         self.sim.step_until_time(3600.);
-        self.summarize_stats();
+        let stats = self.summarize_stats();
 
         for peer in self.peers.iter() {
             peer.borrow_mut().clear_storage();
         }
+        stats
     }
 
     /// Runs the simulation with intensive publishing.
-    pub fn run_scenario_intensive_publishing(&mut self) {
+    pub fn run_scenario_intensive_publishing(&mut self) -> QueriesStats {
         const PUBLISHING_DELAY: f64 = 0.1;
         const SIMULATION_DURATION: f64 = 3600.;
         let mut i = 0;
         while self.sim.time() < SIMULATION_DURATION {
-            let idx = self.sim.gen_range(0..CONFIG.num_peers as usize);
+            let idx = self.sim.gen_range(0..self.env.config().num_peers as usize);
             self.peers[idx]
                 .borrow_mut()
                 .publish_data(format!("data-{}", i));
             i += 1;
             self.sim.step_until_time(self.sim.time() + PUBLISHING_DELAY);
         }
-        self.summarize_stats();
+        let stats = self.summarize_stats();
 
         for peer in self.peers.iter() {
             peer.borrow_mut().clear_storage();
         }
+        stats
     }
 
     /// Runs the simulation with intensive retrieving.
-    pub fn run_scenario_intensive_retrieving(&mut self) {
+    pub fn run_scenario_intensive_retrieving(&mut self) -> QueriesStats {
         const BLOCKS_COUNT: usize = 1_000;
         const SIMULATION_DURATION: f64 = 3600.;
         const PROPAGATION_BLOCKS_TIME_RESERVE: f64 = 10.;
@@ -139,7 +658,7 @@ impl App {
             .map(|block| Key::from_sha256(block.as_bytes()))
             .collect::<Vec<_>>();
         for block in blocks.iter().cloned() {
-            let idx = self.sim.gen_range(0..CONFIG.num_peers as usize);
+            let idx = self.sim.gen_range(0..self.env.config().num_peers as usize);
             let mut peer = self.peers[idx].borrow_mut();
             peer.publish_data(block);
         }
@@ -147,27 +666,31 @@ impl App {
         self.sim.step_until_time(PROPAGATION_BLOCKS_TIME_RESERVE);
 
         while self.sim.time() < SIMULATION_DURATION {
-            let idx = self.sim.gen_range(0..CONFIG.num_peers as usize);
+            let idx = self.sim.gen_range(0..self.env.config().num_peers as usize);
             let key = keys[self.sim.gen_range(0..BLOCKS_COUNT)].clone();
             self.peers[idx].borrow_mut().retrieve_data(key);
             self.sim.step_until_time(self.sim.time() + RETRIEVING_DELAY);
         }
-        self.summarize_stats();
+        let stats = self.summarize_stats();
 
         for peer in self.peers.iter() {
             peer.borrow_mut().clear_storage();
         }
+        stats
     }
 
     /// Allows to measure the propagation delay of the network.
     /// Pay attention to the `retrieve_data_queries_completed` and
     /// `retrieve_data_queries_failed` fields of the statistics.
-    /// 
+    ///
     /// # Arguments
-    /// 
+    ///
     /// * `timedelta` - The time difference between the publishing and the retrieving.
     /// If `timedelta` is positive, the publishing happens first; otherwise, the retrieving.
-    pub fn run_scenario_publishing_retrieving_race(&mut self, timedelta: f64) {
+    ///
+    /// The simulation runs until there are no events left, so it never finishes
+    /// if any of the periodic tasks is enabled, see `SimulationConfig::periodic_tasks`.
+    pub fn run_scenario_publishing_retrieving_race(&mut self, timedelta: f64) -> QueriesStats {
         const KEYS_CNT: usize = 10_000;
 
         let blocks = (0..KEYS_CNT)
@@ -180,43 +703,99 @@ impl App {
 
         if timedelta >= 0. {
             for block in blocks.iter().cloned() {
-                self.peers[self.sim.gen_range(0..CONFIG.num_peers) as usize]
+                self.peers[self.sim.gen_range(0..self.env.config().num_peers) as usize]
                     .borrow_mut()
                     .publish_data(block);
             }
             self.sim.step_until_time(self.sim.time() + timedelta);
             for key in keys.iter().cloned() {
-                self.peers[self.sim.gen_range(0..CONFIG.num_peers) as usize]
+                self.peers[self.sim.gen_range(0..self.env.config().num_peers) as usize]
                     .borrow_mut()
                     .retrieve_data(key);
             }
         } else {
             for key in keys.iter().cloned() {
-                self.peers[self.sim.gen_range(0..CONFIG.num_peers) as usize]
+                self.peers[self.sim.gen_range(0..self.env.config().num_peers) as usize]
                     .borrow_mut()
                     .retrieve_data(key);
             }
             self.sim.step_until_time(self.sim.time() - timedelta);
             for block in blocks.iter().cloned() {
-                self.peers[self.sim.gen_range(0..CONFIG.num_peers) as usize]
+                self.peers[self.sim.gen_range(0..self.env.config().num_peers) as usize]
                     .borrow_mut()
                     .publish_data(block);
             }
         }
 
-        // background tasks, such as republishing, bootstrap and
-        // user load generation must be disabled
+        // the periodic tasks must be disabled, see `SimulationConfig::periodic_tasks`
         self.sim.step_until_no_events();
 
         for peer in self.peers.iter() {
             peer.borrow_mut().clear_storage();
         }
-        self.summarize_stats();
+        self.summarize_stats()
     }
 }
 
 impl Default for App {
+    /// Creates a new `App` instance from the default configuration file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the configuration file cannot be loaded.
     fn default() -> Self {
-        Self::new()
+        Self::new(SimulationConfig::from_default_config_file().expect("Failed to load config"))
+            .expect("Failed to create app")
+    }
+}
+
+/// Describes the failure to use the file named by the given field of the configuration.
+fn field_error(field: &str, err: std::io::Error) -> ConfigError {
+    ConfigError::Invalid(vec![FieldError {
+        field: field.to_string(),
+        reason: err.to_string(),
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_workload_trace() {
+        let overrides = [
+            ("log_level_filter", "'off'"),
+            ("num_peers", "10"),
+            ("workload_trace.path", "'missing.csv'"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let config = SimulationConfig::from_toml_str_with_overrides(
+            include_str!("../config.toml"),
+            &overrides,
+        )
+        .unwrap();
+        let Err(ConfigError::Invalid(errors)) = App::new(config) else {
+            panic!("expected the app creation to fail");
+        };
+        assert_eq!(errors[0].field, "workload_trace.path");
+    }
+
+    #[test]
+    fn test_invalid_trace_file() {
+        let overrides = [
+            ("log_level_filter", "'off'"),
+            ("num_peers", "10"),
+            ("trace_file_path", "'missing/trace.jsonl'"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let config = SimulationConfig::from_toml_str_with_overrides(
+            include_str!("../config.toml"),
+            &overrides,
+        )
+        .unwrap();
+        let Err(ConfigError::Invalid(errors)) = App::new(config) else {
+            panic!("expected the app creation to fail");
+        };
+        assert_eq!(errors[0].field, "trace_file_path");
     }
 }
@@ -0,0 +1,109 @@
+use std::fmt;
+
+/// Represents a problem with the value of a single configuration field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// The path to the field, e.g. `delay_max`.
+    pub field: String,
+    /// The reason why the value is invalid.
+    pub reason: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.reason)
+    }
+}
+
+/// Represents an error that occurred while loading the configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file could not be read.
+    Io(std::io::Error),
+    /// The configuration is not valid TOML or does not match the expected structure.
+    Parse(String),
+    /// The values of some fields are invalid. All the problems found are listed.
+    Invalid(Vec<FieldError>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "failed to read config file: {}", err),
+            ConfigError::Parse(reason) => write!(f, "failed to parse config: {}", reason),
+            ConfigError::Invalid(errors) => {
+                write!(f, "invalid config:")?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(err: std::io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+/// Collects the problems found while validating the configuration.
+#[derive(Debug, Default)]
+pub(super) struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    /// Records a problem with the field.
+    pub fn error(&mut self, field: &str, reason: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            reason: reason.into(),
+        });
+    }
+
+    /// Records a problem with the field if the condition does not hold.
+    pub fn check(&mut self, condition: bool, field: &str, reason: impl Into<String>) {
+        if !condition {
+            self.error(field, reason);
+        }
+    }
+
+    /// Records a problem if the field is missing.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value of the field.
+    /// * `field` - The path to the field.
+    /// * `context` - The condition under which the field is required, or an empty string.
+    pub fn require<T>(&mut self, value: Option<T>, field: &str, context: &str) -> Option<T> {
+        if value.is_none() {
+            if context.is_empty() {
+                self.error(field, "is required");
+            } else {
+                self.error(field, format!("is required {}", context));
+            }
+        }
+        value
+    }
+
+    /// Returns `true` if no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Converts the found problems into an error.
+    pub fn into_error(self) -> ConfigError {
+        ConfigError::Invalid(self.errors)
+    }
+}
@@ -1,4 +1,6 @@
+mod error;
 mod simulation_config;
 mod toml_parser;
 
+pub use error::{ConfigError, FieldError};
 pub use simulation_config::SimulationConfig;
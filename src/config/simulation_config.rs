@@ -1,8 +1,21 @@
-use super::toml_parser::ConfigTOML;
-use crate::network::{DelayDistribution, Topology};
+use super::{
+    error::{ConfigError, Validator},
+    toml_parser::{ConfigTOML, DhtTOML, StorageLimitsTOML},
+};
+use crate::{
+    network::{
+        Arrivals, ChurnConfig, DelayDistribution, FlashCrowd, Popularity, RateFunction, Region,
+        Topology, WorkloadTraceConfig,
+    },
+    profile::{assign_profiles, DhtMode, PeerProfile},
+    query::LookupTermination,
+    storage::{EvictionPolicy, StorageCapacity},
+};
+use serde::Serialize;
+use std::path::Path;
 
 /// Represents the configuration of the IPFS simulator.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct SimulationConfig {
     pub log_level_filter: log::LevelFilter,
     pub log_file_path: Option<String>,
@@ -10,131 +23,988 @@ pub struct SimulationConfig {
     pub user_load_block_size: Option<usize>,
     pub user_load_blocks_pool_size: Option<usize>,
     pub user_load_events_interval: Option<f64>,
+    pub user_load_arrivals: Arrivals,
+    pub user_load_popularity: Popularity,
+    pub user_load_publish_ratio: f64,
+    pub user_load_new_content_ratio: f64,
+    pub user_load_rate: Option<RateFunction>,
+    pub user_load_flash_crowds: Vec<FlashCrowd>,
+    pub workload_trace: Option<WorkloadTraceConfig>,
+    pub regions: Vec<Region>,
+    pub churn: Option<ChurnConfig>,
+    pub profiles: Vec<PeerProfile>,
     pub seed: u64,
     pub k: usize,
     pub alpha: usize,
     pub num_peers: u32,
     pub delay_distribution: DelayDistribution,
     pub topology: Topology,
+    pub lookup_termination: LookupTermination,
     pub record_publication_interval: f64,
     pub record_expiration_interval: f64,
     pub kbuckets_refresh_interval: f64,
     pub query_timeout: f64,
+    pub request_timeout: Option<f64>,
+    pub evict_unresponsive_peers: bool,
     pub caching_max_peers: usize,
     pub enable_bootstrap: bool,
     pub enable_republishing: bool,
+    pub dht_storage_capacity: StorageCapacity,
+    pub dht_storage_eviction_policy: EvictionPolicy,
+    pub file_storage_capacity: StorageCapacity,
+    pub file_storage_eviction_policy: EvictionPolicy,
+    pub cache_retrieved_data: bool,
+    pub provide_retrieved_data: bool,
+    pub retrieve_max_providers: Option<usize>,
+    pub record_expiration_sweep_interval: Option<f64>,
+    pub collect_query_records: bool,
+    pub report_json_path: Option<String>,
+    pub report_csv_path: Option<String>,
+    pub report_busiest_peers: usize,
+    pub trace_file_path: Option<String>,
+    pub metrics_sampling_interval: Option<f64>,
+    pub metrics_file_path: Option<String>,
+    pub routing_health_path: Option<String>,
+    pub replication_audit_interval: Option<f64>,
+    pub replication_audit_path: Option<String>,
+    pub trace_query_ids: Option<Vec<u64>>,
+    pub trace_peers: Option<Vec<u32>>,
+    pub gc_interval: Option<f64>,
+    pub gc_watermark_bytes: Option<usize>,
 }
 
 impl SimulationConfig {
     /// Creates a new `SimulationConfig` instance from the default configuration file.
-    pub fn from_default_config_file() -> Self {
-        let toml = ConfigTOML::from_file("config.toml");
-        Self::from_toml(toml)
-    }
-
-    /// Creates a new `SimulationConfig` instance from the specified TOML configuration file.
-    fn from_toml(toml: ConfigTOML) -> Self {
-        let log_level_filter = match toml.log_level_filter.as_str() {
-            "off" => log::LevelFilter::Off,
-            "error" => log::LevelFilter::Error,
-            "warn" => log::LevelFilter::Warn,
-            "info" => log::LevelFilter::Info,
-            "debug" => log::LevelFilter::Debug,
-            "trace" => log::LevelFilter::Trace,
-            _ => panic!("invalid log_level_filter"),
-        };
-        if toml.enable_user_load_generation {
-            assert!(
-                toml.user_load_block_size.is_some(),
-                "missing user_load_block_size"
+    pub fn from_default_config_file() -> Result<Self, ConfigError> {
+        Self::from_file("config.toml")
+    }
+
+    /// Creates a new `SimulationConfig` instance from the given configuration file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::from_file_with_overrides(path, &[])
+    }
+
+    /// Creates a new `SimulationConfig` instance from the given configuration file
+    /// with some of its fields overridden.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the configuration file.
+    /// * `overrides` - Pairs of field names and values in TOML syntax.
+    pub fn from_file_with_overrides(
+        path: impl AsRef<Path>,
+        overrides: &[(String, String)],
+    ) -> Result<Self, ConfigError> {
+        Self::from_toml(ConfigTOML::from_file(path, overrides)?)
+    }
+
+    /// Creates a new `SimulationConfig` instance from the given TOML string.
+    pub fn from_toml_str(data: &str) -> Result<Self, ConfigError> {
+        Self::from_toml_str_with_overrides(data, &[])
+    }
+
+    /// Creates a new `SimulationConfig` instance from the given TOML string
+    /// with some of its fields overridden.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The contents of the configuration file.
+    /// * `overrides` - Pairs of field names and values in TOML syntax.
+    pub fn from_toml_str_with_overrides(
+        data: &str,
+        overrides: &[(String, String)],
+    ) -> Result<Self, ConfigError> {
+        Self::from_toml(ConfigTOML::from_toml_str(data, overrides)?)
+    }
+
+    /// Places the output files of the simulation into the given directory.
+    /// Relative paths of the configured files are resolved against the directory,
+    /// and the JSON and CSV reports are written there even if they are not configured.
+    pub fn set_output_dir(&mut self, dir: impl AsRef<Path>) {
+        let dir = dir.as_ref();
+        self.report_json_path
+            .get_or_insert_with(|| "report.json".to_string());
+        self.report_csv_path
+            .get_or_insert_with(|| "report.csv".to_string());
+        for path in [
+            &mut self.log_file_path,
+            &mut self.report_json_path,
+            &mut self.report_csv_path,
+            &mut self.trace_file_path,
+            &mut self.metrics_file_path,
+            &mut self.routing_health_path,
+            &mut self.replication_audit_path,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = dir.join(&*path).to_string_lossy().into_owned();
+        }
+    }
+
+    /// Returns the configuration keys of the enabled periodic tasks.
+    /// Every such task reschedules itself forever, so a simulation with any of them
+    /// never runs out of events and must be run for a limited time.
+    pub fn periodic_tasks(&self) -> Vec<&'static str> {
+        let tasks = [
+            ("dht.enable_bootstrap", self.enable_bootstrap),
+            ("dht.enable_republishing", self.enable_republishing),
+            ("user_load.enabled", self.enable_user_load_generation),
+            (
+                "record_expiration_sweep_interval",
+                self.record_expiration_sweep_interval.is_some(),
+            ),
+            ("gc_interval", self.gc_interval.is_some()),
+            (
+                "metrics_sampling_interval",
+                self.metrics_sampling_interval.is_some(),
+            ),
+            (
+                "replication_audit_interval",
+                self.replication_audit_interval.is_some(),
+            ),
+            ("churn", self.churn.is_some()),
+            (
+                "profiles.uptime",
+                self.profiles.iter().any(|profile| profile.uptime.is_some()),
+            ),
+        ];
+        tasks
+            .into_iter()
+            .filter(|&(_, enabled)| enabled)
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// Validates the parsed TOML configuration and converts it into a `SimulationConfig`.
+    /// All the problems are collected instead of stopping at the first one.
+    fn from_toml(toml: ConfigTOML) -> Result<Self, ConfigError> {
+        let mut v = Validator::default();
+
+        let user_load = resolve_user_load(&mut v, &toml);
+        let dht = resolve_dht(&mut v, &toml);
+        let network = toml.network.as_ref();
+
+        v.check(toml.num_peers > 0, "num_peers", "must be positive");
+        let k = v.require(dht.k, "dht.k", "");
+        let alpha = v.require(dht.alpha, "dht.alpha", "");
+        let caching_max_peers = v.require(dht.caching_max_peers, "dht.caching_max_peers", "");
+        if let Some(k) = k {
+            v.check(k > 0, "dht.k", "must be positive");
+            if let Some(alpha) = alpha {
+                v.check(alpha > 0, "dht.alpha", "must be positive");
+                v.check(alpha <= k, "dht.alpha", "must not be greater than dht.k");
+            }
+            if let Some(caching_max_peers) = caching_max_peers {
+                v.check(
+                    caching_max_peers <= k,
+                    "dht.caching_max_peers",
+                    "must not be greater than dht.k",
+                );
+            }
+        }
+        let record_publication_interval = v.require(
+            dht.record_publication_interval,
+            "dht.record_publication_interval",
+            "",
+        );
+        let record_expiration_interval = v.require(
+            dht.record_expiration_interval,
+            "dht.record_expiration_interval",
+            "",
+        );
+        if let (Some(publication), Some(expiration)) =
+            (record_publication_interval, record_expiration_interval)
+        {
+            v.check(
+                publication > 0.,
+                "dht.record_publication_interval",
+                "must be positive",
             );
-            assert!(
-                toml.user_load_blocks_pool_size.is_some(),
-                "missing user_load_blocks_pool_size"
+            v.check(
+                publication < expiration,
+                "dht.record_publication_interval",
+                "must be less than dht.record_expiration_interval",
             );
-            assert!(
-                toml.user_load_events_interval.is_some(),
-                "missing user_load_events_interval"
-            );
-        }
-        let delay_distribution = match toml.delay_distribution.as_str() {
-            "constant" => {
-                let mean = match toml.delay_mean {
-                    Some(mean) => {
-                        assert!(mean >= 0., "delay_mean must be non-negative");
-                        mean
-                    }
-                    None => panic!("missing delay_mean"),
-                };
-                DelayDistribution::Constant(mean)
-            }
-            "uniform" => {
-                let left = match toml.delay_min {
-                    Some(min) => {
-                        assert!(min >= 0., "delay_min must be non-negative");
-                        min
-                    }
-                    None => panic!("missing delay_min"),
-                };
-                let right = match toml.delay_max {
-                    Some(max) => {
-                        assert!(max > left, "delay_max must be greater than delay_min");
-                        max
-                    }
-                    None => panic!("missing delay_max"),
-                };
-                DelayDistribution::Uniform { left, right }
-            }
-            "positive_normal" => {
-                let mean = match toml.delay_mean {
-                    Some(mean) => {
-                        assert!(mean >= 0., "delay_mean must be non-negative");
-                        mean
-                    }
-                    None => panic!("missing delay_mean"),
-                };
-                let std_dev = match toml.delay_std_dev {
-                    Some(std_dev) => {
-                        assert!(std_dev >= 0., "delay_std_dev must be non-negative");
-                        std_dev
-                    }
-                    None => panic!("missing delay_std_dev"),
-                };
-                DelayDistribution::PositiveNormal { mean, std_dev }
-            }
-            _ => panic!("invalid delay distribution"),
+        }
+        let kbuckets_refresh_interval = v.require(
+            dht.kbuckets_refresh_interval,
+            "dht.kbuckets_refresh_interval",
+            "",
+        );
+        if let Some(interval) = kbuckets_refresh_interval {
+            v.check(
+                interval > 0.,
+                "dht.kbuckets_refresh_interval",
+                "must be positive",
+            );
+        }
+        let query_timeout = v.require(dht.query_timeout, "dht.query_timeout", "");
+        if let Some(timeout) = query_timeout {
+            v.check(timeout > 0., "dht.query_timeout", "must be positive");
+        }
+        if let Some(timeout) = dht.request_timeout {
+            v.check(timeout > 0., "dht.request_timeout", "must be positive");
+        }
+
+        let delay_distribution = match (
+            network.and_then(|network| network.delay.clone()),
+            toml.delay_distribution.as_deref(),
+        ) {
+            (Some(_), Some(_)) => {
+                v.error("delay_distribution", "conflicts with network.delay");
+                None
+            }
+            (Some(delay), None) => {
+                validate_delay(&mut v, &delay);
+                Some(delay)
+            }
+            (None, Some(kind)) => parse_flat_delay(&mut v, &toml, kind),
+            (None, None) => {
+                v.error("network.delay", "is required");
+                None
+            }
         };
 
-        let topology = match toml.topology.as_str() {
-            "full" => Topology::Full,
-            "ring" => Topology::Ring {
+        let topology = match (
+            network.and_then(|network| network.topology.clone()),
+            toml.topology.as_deref(),
+        ) {
+            (Some(_), Some(_)) => {
+                v.error("topology", "conflicts with network.topology");
+                None
+            }
+            (Some(topology), None) => {
+                validate_topology(&mut v, &topology, toml.num_peers);
+                Some(topology)
+            }
+            (None, Some("full")) => Some(Topology::Full),
+            (None, Some("ring")) => Some(Topology::Ring {
                 first_id: 0,
-                last_id: toml.num_peers - 1,
+                last_id: toml.num_peers.saturating_sub(1),
+            }),
+            (None, Some("star")) => Some(Topology::Star { center_id: 0 }),
+            (None, Some(other)) => {
+                v.error("topology", format!("invalid value '{}'", other));
+                None
+            }
+            (None, None) => {
+                v.error("network.topology", "is required");
+                None
+            }
+        };
+
+        let storage = toml.storage.as_ref();
+        let (dht_storage_capacity, dht_storage_eviction_policy) = resolve_storage(
+            &mut v,
+            "dht",
+            storage.and_then(|storage| storage.dht.as_ref()),
+            StorageLimitsTOML {
+                max_records: toml.dht_storage_max_records,
+                max_bytes: toml.dht_storage_max_bytes,
+                eviction_policy: toml.dht_storage_eviction_policy.unwrap_or_default(),
             },
-            "star" => Topology::Star { center_id: 0 },
-            _ => panic!("invalid topology"),
+            toml.dht_storage_eviction_policy.is_some(),
+        );
+        let (file_storage_capacity, file_storage_eviction_policy) = resolve_storage(
+            &mut v,
+            "file",
+            storage.and_then(|storage| storage.file.as_ref()),
+            StorageLimitsTOML {
+                max_records: toml.file_storage_max_records,
+                max_bytes: toml.file_storage_max_bytes,
+                eviction_policy: toml.file_storage_eviction_policy.unwrap_or_default(),
+            },
+            toml.file_storage_eviction_policy.is_some(),
+        );
+
+        if let Some(interval) = toml.record_expiration_sweep_interval {
+            v.check(
+                interval > 0.,
+                "record_expiration_sweep_interval",
+                "must be positive",
+            );
+        }
+        v.check(
+            toml.trace_query_ids.is_none() || toml.trace_peers.is_some(),
+            "trace_query_ids",
+            "requires trace_peers, since every peer assigns the query IDs independently",
+        );
+        v.check(
+            !toml.provide_retrieved_data || toml.cache_retrieved_data,
+            "provide_retrieved_data",
+            "requires cache_retrieved_data",
+        );
+        if let Some(peers) = toml.trace_peers.as_ref() {
+            v.check(
+                peers.iter().all(|&id| id < toml.num_peers),
+                "trace_peers",
+                "must be less than num_peers",
+            );
+        }
+        if let Some(interval) = toml.metrics_sampling_interval {
+            v.check(
+                interval > 0.,
+                "metrics_sampling_interval",
+                "must be positive",
+            );
+        }
+        if let Some(interval) = toml.replication_audit_interval {
+            v.check(
+                interval > 0.,
+                "replication_audit_interval",
+                "must be positive",
+            );
+        }
+        if let Some(count) = toml.retrieve_max_providers {
+            v.check(count > 0, "retrieve_max_providers", "must be positive");
+        }
+        if let Some(interval) = toml.gc_interval {
+            v.check(interval > 0., "gc_interval", "must be positive");
+        }
+        if let Some(trace) = toml.workload_trace.as_ref() {
+            v.check(
+                trace.time_scale > 0.,
+                "workload_trace.time_scale",
+                "must be positive",
+            );
+        }
+        validate_regions(&mut v, toml.regions.as_deref().unwrap_or_default());
+        if let Some(churn) = toml.churn.as_ref() {
+            v.check(churn.interval > 0., "churn.interval", "must be positive");
+            v.check(
+                churn.turnover > 0. && churn.turnover <= 1.,
+                "churn.turnover",
+                "must be greater than 0 and at most 1",
+            );
+            validate_rate(&mut v, &churn.online, "churn.online");
+        }
+        let profiles = toml.profiles.as_deref().unwrap_or_default();
+        validate_profiles(&mut v, profiles, toml.num_peers);
+        v.check(
+            toml.churn.is_none() || profiles.iter().all(|profile| profile.uptime.is_none()),
+            "churn",
+            "conflicts with the uptime of the profiles",
+        );
+
+        let (
+            Some(k),
+            Some(alpha),
+            Some(caching_max_peers),
+            Some(record_publication_interval),
+            Some(record_expiration_interval),
+            Some(kbuckets_refresh_interval),
+            Some(query_timeout),
+            Some(delay_distribution),
+            Some(topology),
+        ) = (
+            k,
+            alpha,
+            caching_max_peers,
+            record_publication_interval,
+            record_expiration_interval,
+            kbuckets_refresh_interval,
+            query_timeout,
+            delay_distribution,
+            topology,
+        )
+        else {
+            return Err(v.into_error());
         };
+        if !v.is_ok() {
+            return Err(v.into_error());
+        }
 
-        Self {
-            log_level_filter,
+        Ok(Self {
+            log_level_filter: toml.log_level_filter,
             log_file_path: toml.log_file_path,
-            enable_user_load_generation: toml.enable_user_load_generation,
-            user_load_block_size: toml.user_load_block_size,
-            user_load_blocks_pool_size: toml.user_load_blocks_pool_size,
-            user_load_events_interval: toml.user_load_events_interval,
+            enable_user_load_generation: user_load.enabled,
+            user_load_block_size: user_load.block_size,
+            user_load_blocks_pool_size: user_load.blocks_pool_size,
+            user_load_events_interval: user_load.events_interval,
+            user_load_arrivals: user_load.arrivals,
+            user_load_popularity: user_load.popularity,
+            user_load_publish_ratio: user_load.publish_ratio,
+            user_load_new_content_ratio: user_load.new_content_ratio,
+            user_load_rate: user_load.rate,
+            user_load_flash_crowds: user_load.flash_crowds,
+            workload_trace: toml.workload_trace,
+            regions: toml.regions.unwrap_or_default(),
+            churn: toml.churn,
+            profiles: toml.profiles.unwrap_or_default(),
             seed: toml.seed,
-            k: toml.k,
-            alpha: toml.alpha,
+            k,
+            alpha,
             num_peers: toml.num_peers,
             delay_distribution,
             topology,
-            record_publication_interval: toml.record_publication_interval,
-            record_expiration_interval: toml.record_expiration_interval,
-            kbuckets_refresh_interval: toml.kbuckets_refresh_interval,
-            query_timeout: toml.query_timeout,
-            caching_max_peers: toml.caching_max_peers,
-            enable_bootstrap: toml.enable_bootstrap,
-            enable_republishing: toml.enable_republishing,
+            lookup_termination: dht.lookup_termination.unwrap_or_default(),
+            record_publication_interval,
+            record_expiration_interval,
+            kbuckets_refresh_interval,
+            query_timeout,
+            request_timeout: dht.request_timeout,
+            evict_unresponsive_peers: toml.evict_unresponsive_peers,
+            caching_max_peers,
+            enable_bootstrap: dht.enable_bootstrap.unwrap_or(false),
+            enable_republishing: dht.enable_republishing.unwrap_or(false),
+            dht_storage_capacity,
+            dht_storage_eviction_policy,
+            file_storage_capacity,
+            file_storage_eviction_policy,
+            cache_retrieved_data: toml.cache_retrieved_data,
+            provide_retrieved_data: toml.provide_retrieved_data,
+            retrieve_max_providers: toml.retrieve_max_providers,
+            record_expiration_sweep_interval: toml.record_expiration_sweep_interval,
+            collect_query_records: toml.collect_query_records,
+            report_json_path: toml.report_json_path,
+            report_csv_path: toml.report_csv_path,
+            report_busiest_peers: toml.report_busiest_peers,
+            trace_file_path: toml.trace_file_path,
+            metrics_sampling_interval: toml.metrics_sampling_interval,
+            metrics_file_path: toml.metrics_file_path,
+            routing_health_path: toml.routing_health_path,
+            replication_audit_interval: toml.replication_audit_interval,
+            replication_audit_path: toml.replication_audit_path,
+            trace_query_ids: toml.trace_query_ids,
+            trace_peers: toml.trace_peers,
+            gc_interval: toml.gc_interval,
+            gc_watermark_bytes: toml.gc_watermark_bytes,
+        })
+    }
+}
+
+/// The parameters of the user load from either the `[user_load]` table or the flat fields.
+struct UserLoad {
+    enabled: bool,
+    block_size: Option<usize>,
+    blocks_pool_size: Option<usize>,
+    events_interval: Option<f64>,
+    arrivals: Arrivals,
+    popularity: Popularity,
+    publish_ratio: f64,
+    new_content_ratio: f64,
+    rate: Option<RateFunction>,
+    flash_crowds: Vec<FlashCrowd>,
+}
+
+/// Resolves the parameters of the user load.
+fn resolve_user_load(v: &mut Validator, toml: &ConfigTOML) -> UserLoad {
+    let user_load = if let Some(table) = toml.user_load.as_ref() {
+        for (field, is_set) in [
+            (
+                "enable_user_load_generation",
+                toml.enable_user_load_generation.is_some(),
+            ),
+            ("user_load_block_size", toml.user_load_block_size.is_some()),
+            (
+                "user_load_blocks_pool_size",
+                toml.user_load_blocks_pool_size.is_some(),
+            ),
+            (
+                "user_load_events_interval",
+                toml.user_load_events_interval.is_some(),
+            ),
+        ] {
+            if is_set {
+                v.error(field, "conflicts with the [user_load] table");
+            }
+        }
+        UserLoad {
+            enabled: table.enabled,
+            block_size: Some(table.block_size),
+            blocks_pool_size: Some(table.blocks_pool_size),
+            events_interval: Some(table.events_interval),
+            arrivals: table.arrivals,
+            popularity: table.popularity,
+            publish_ratio: table.publish_ratio,
+            new_content_ratio: table.new_content_ratio,
+            rate: table.rate.clone(),
+            flash_crowds: table.flash_crowds.clone(),
+        }
+    } else {
+        let enabled = toml.enable_user_load_generation.unwrap_or(false);
+        if enabled {
+            let context = "when enable_user_load_generation is set";
+            v.require(toml.user_load_block_size, "user_load_block_size", context);
+            v.require(
+                toml.user_load_blocks_pool_size,
+                "user_load_blocks_pool_size",
+                context,
+            );
+            v.require(
+                toml.user_load_events_interval,
+                "user_load_events_interval",
+                context,
+            );
+        }
+        UserLoad {
+            enabled,
+            block_size: toml.user_load_block_size,
+            blocks_pool_size: toml.user_load_blocks_pool_size,
+            events_interval: toml.user_load_events_interval,
+            arrivals: Arrivals::Fixed,
+            popularity: Popularity::Uniform,
+            publish_ratio: 0.5,
+            new_content_ratio: 0.,
+            rate: None,
+            flash_crowds: vec![],
+        }
+    };
+    if let (true, Some(interval)) = (user_load.enabled, user_load.events_interval) {
+        v.check(
+            interval > 0.,
+            "user_load.events_interval",
+            "must be positive",
+        );
+    }
+    if let Arrivals::Bursty {
+        on_duration,
+        off_duration,
+    } = user_load.arrivals
+    {
+        v.check(
+            on_duration > 0.,
+            "user_load.arrivals.on_duration",
+            "must be positive",
+        );
+        v.check(
+            off_duration > 0.,
+            "user_load.arrivals.off_duration",
+            "must be positive",
+        );
+    }
+    if let Popularity::Zipf { exponent } = user_load.popularity {
+        v.check(
+            exponent > 0.,
+            "user_load.popularity.exponent",
+            "must be positive",
+        );
+    }
+    v.check(
+        (0. ..=1.).contains(&user_load.publish_ratio),
+        "user_load.publish_ratio",
+        "must be between 0 and 1",
+    );
+    v.check(
+        (0. ..=1.).contains(&user_load.new_content_ratio),
+        "user_load.new_content_ratio",
+        "must be between 0 and 1",
+    );
+    if let (true, Some(0)) = (user_load.enabled, user_load.blocks_pool_size) {
+        v.check(
+            user_load.new_content_ratio > 0.,
+            "user_load.blocks_pool_size",
+            "must be positive unless user_load.new_content_ratio is",
+        );
+    }
+    if let Some(rate) = user_load.rate.as_ref() {
+        validate_rate(v, rate, "user_load.rate");
+        v.check(
+            rate.max_value() > 0.,
+            "user_load.rate",
+            "must be positive at some time",
+        );
+    }
+    for (i, flash_crowd) in user_load.flash_crowds.iter().enumerate() {
+        let field = |name: &str| format!("user_load.flash_crowds.{}.{}", i, name);
+        v.check(
+            flash_crowd.duration > 0.,
+            &field("duration"),
+            "must be positive",
+        );
+        v.check(
+            flash_crowd.events_interval > 0.,
+            &field("events_interval"),
+            "must be positive",
+        );
+        v.check(
+            user_load
+                .blocks_pool_size
+                .is_some_and(|size| flash_crowd.block < size),
+            &field("block"),
+            "must be less than user_load.blocks_pool_size",
+        );
+    }
+    user_load
+}
+
+/// Checks the parameters of the rate function at the given path.
+fn validate_rate(v: &mut Validator, rate: &RateFunction, path: &str) {
+    for (field, reason) in rate.validate() {
+        v.error(&format!("{}.{}", path, field), reason);
+    }
+}
+
+/// Checks that the fractions of the regions are valid and sum to 1.
+fn validate_regions(v: &mut Validator, regions: &[Region]) {
+    for (i, region) in regions.iter().enumerate() {
+        v.check(
+            (0. ..=1.).contains(&region.fraction),
+            &format!("regions.{}.fraction", i),
+            "must be between 0 and 1",
+        );
+    }
+    if !regions.is_empty() {
+        let total = regions.iter().map(|region| region.fraction).sum::<f64>();
+        v.check(
+            (total - 1.).abs() < 1e-6,
+            "regions",
+            "the fractions must sum to 1",
+        );
+    }
+}
+
+/// Checks the parameters of the profiles and that their shares sum to 1.
+/// The profiles actually assigned to the peers must make at least one of them a DHT server
+/// and give a positive load share to at least one of them.
+fn validate_profiles(v: &mut Validator, profiles: &[PeerProfile], num_peers: u32) {
+    for (i, profile) in profiles.iter().enumerate() {
+        let field = |name: &str| format!("profiles.{}.{}", i, name);
+        v.check(
+            !profile.name.is_empty(),
+            &field("name"),
+            "must not be empty",
+        );
+        v.check(
+            profiles[..i].iter().all(|other| other.name != profile.name),
+            &field("name"),
+            "must be unique",
+        );
+        v.check(
+            (0. ..=1.).contains(&profile.share),
+            &field("share"),
+            "must be between 0 and 1",
+        );
+        v.check(
+            profile.load_share >= 0.,
+            &field("load_share"),
+            "must be non-negative",
+        );
+        if let Some(bandwidth) = profile.bandwidth {
+            v.check(bandwidth > 0., &field("bandwidth"), "must be positive");
+        }
+        v.check(
+            profile.processing_delay >= 0.,
+            &field("processing_delay"),
+            "must be non-negative",
+        );
+        if let Some(uptime) = profile.uptime {
+            v.check(
+                uptime.session > 0.,
+                &field("uptime.session"),
+                "must be positive",
+            );
+            v.check(
+                uptime.downtime > 0.,
+                &field("uptime.downtime"),
+                "must be positive",
+            );
+        }
+    }
+    if !profiles.is_empty() {
+        let total = profiles.iter().map(|profile| profile.share).sum::<f64>();
+        v.check(
+            (total - 1.).abs() < 1e-6,
+            "profiles",
+            "the shares must sum to 1",
+        );
+        let assigned = assign_profiles(profiles, num_peers as usize)
+            .into_iter()
+            .map(|i| &profiles[i])
+            .collect::<Vec<_>>();
+        v.check(
+            assigned
+                .iter()
+                .any(|profile| profile.dht_mode == DhtMode::Server),
+            "profiles",
+            "the DHT mode must be 'server' for some peers",
+        );
+        v.check(
+            assigned.iter().any(|profile| profile.load_share > 0.),
+            "profiles",
+            "the load shares must be positive for some peers",
+        );
+    }
+}
+
+/// Merges the `[dht]` table with the fields specified at the top level.
+/// A field specified in both places is reported as a conflict.
+fn resolve_dht(v: &mut Validator, toml: &ConfigTOML) -> DhtTOML {
+    fn pick<T: Copy>(
+        v: &mut Validator,
+        nested: Option<T>,
+        flat: Option<T>,
+        field: &str,
+    ) -> Option<T> {
+        if nested.is_some() && flat.is_some() {
+            v.error(field, format!("conflicts with dht.{}", field));
+        }
+        nested.or(flat)
+    }
+
+    let dht = toml.dht.as_ref();
+    DhtTOML {
+        k: pick(v, dht.and_then(|dht| dht.k), toml.k, "k"),
+        alpha: pick(v, dht.and_then(|dht| dht.alpha), toml.alpha, "alpha"),
+        record_publication_interval: pick(
+            v,
+            dht.and_then(|dht| dht.record_publication_interval),
+            toml.record_publication_interval,
+            "record_publication_interval",
+        ),
+        record_expiration_interval: pick(
+            v,
+            dht.and_then(|dht| dht.record_expiration_interval),
+            toml.record_expiration_interval,
+            "record_expiration_interval",
+        ),
+        kbuckets_refresh_interval: pick(
+            v,
+            dht.and_then(|dht| dht.kbuckets_refresh_interval),
+            toml.kbuckets_refresh_interval,
+            "kbuckets_refresh_interval",
+        ),
+        query_timeout: pick(
+            v,
+            dht.and_then(|dht| dht.query_timeout),
+            toml.query_timeout,
+            "query_timeout",
+        ),
+        request_timeout: pick(
+            v,
+            dht.and_then(|dht| dht.request_timeout),
+            toml.request_timeout,
+            "request_timeout",
+        ),
+        caching_max_peers: pick(
+            v,
+            dht.and_then(|dht| dht.caching_max_peers),
+            toml.caching_max_peers,
+            "caching_max_peers",
+        ),
+        enable_bootstrap: pick(
+            v,
+            dht.and_then(|dht| dht.enable_bootstrap),
+            toml.enable_bootstrap,
+            "enable_bootstrap",
+        ),
+        enable_republishing: pick(
+            v,
+            dht.and_then(|dht| dht.enable_republishing),
+            toml.enable_republishing,
+            "enable_republishing",
+        ),
+        lookup_termination: resolve_termination(
+            v,
+            dht.and_then(|dht| dht.lookup_termination),
+            toml,
+        ),
+    }
+}
+
+/// Resolves the lookup termination rule from either the `dht.lookup_termination` table
+/// or the flat `lookup_termination` string with the `lookup_*` fields.
+fn resolve_termination(
+    v: &mut Validator,
+    nested: Option<LookupTermination>,
+    toml: &ConfigTOML,
+) -> Option<LookupTermination> {
+    let Some(termination) = nested else {
+        return toml
+            .lookup_termination
+            .as_deref()
+            .and_then(|kind| parse_flat_termination(v, toml, kind));
+    };
+    for (field, is_set) in [
+        ("lookup_termination", toml.lookup_termination.is_some()),
+        ("lookup_beta", toml.lookup_beta.is_some()),
+        ("lookup_max_hops", toml.lookup_max_hops.is_some()),
+        ("lookup_max_requests", toml.lookup_max_requests.is_some()),
+    ] {
+        if is_set {
+            v.error(field, "conflicts with dht.lookup_termination");
+        }
+    }
+    if let LookupTermination::Beta { beta } = termination {
+        v.check(beta > 0, "dht.lookup_termination.beta", "must be positive");
+    }
+    Some(termination)
+}
+
+/// Builds the lookup termination rule from the flat `lookup_*` fields.
+fn parse_flat_termination(
+    v: &mut Validator,
+    toml: &ConfigTOML,
+    kind: &str,
+) -> Option<LookupTermination> {
+    match kind {
+        "k_closest_responded" => Some(LookupTermination::KClosestResponded),
+        "no_closer_peers" => Some(LookupTermination::NoCloserPeers),
+        "beta" => v
+            .require(
+                toml.lookup_beta,
+                "lookup_beta",
+                "when lookup_termination is 'beta'",
+            )
+            .map(|beta| {
+                v.check(beta > 0, "lookup_beta", "must be positive");
+                LookupTermination::Beta { beta }
+            }),
+        "max_hops" => v
+            .require(
+                toml.lookup_max_hops,
+                "lookup_max_hops",
+                "when lookup_termination is 'max_hops'",
+            )
+            .map(|max_hops| LookupTermination::MaxHops { max_hops }),
+        "max_requests" => v
+            .require(
+                toml.lookup_max_requests,
+                "lookup_max_requests",
+                "when lookup_termination is 'max_requests'",
+            )
+            .map(|max_requests| LookupTermination::MaxRequests { max_requests }),
+        other => {
+            v.error("lookup_termination", format!("invalid value '{}'", other));
+            None
+        }
+    }
+}
+
+/// Resolves the capacity and the eviction policy of a storage from either
+/// its `[storage.*]` table or the flat `*_storage_*` fields.
+///
+/// # Arguments
+///
+/// * `v` - The validator to report the conflicts to.
+/// * `name` - The name of the storage, either `dht` or `file`.
+/// * `table` - The `[storage.*]` table of the storage, if it is specified.
+/// * `flat` - The limits and the eviction policy from the flat fields.
+/// * `flat_policy_set` - Whether the eviction policy is specified by a flat field.
+fn resolve_storage(
+    v: &mut Validator,
+    name: &str,
+    table: Option<&StorageLimitsTOML>,
+    flat: StorageLimitsTOML,
+    flat_policy_set: bool,
+) -> (StorageCapacity, EvictionPolicy) {
+    let limits = match table {
+        Some(table) => {
+            for (field, is_set) in [
+                ("max_records", flat.max_records.is_some()),
+                ("max_bytes", flat.max_bytes.is_some()),
+                ("eviction_policy", flat_policy_set),
+            ] {
+                if is_set {
+                    v.error(
+                        &format!("{}_storage_{}", name, field),
+                        format!("conflicts with the [storage.{}] table", name),
+                    );
+                }
+            }
+            table
+        }
+        None => &flat,
+    };
+    let capacity = StorageCapacity {
+        max_records: limits.max_records,
+        max_bytes: limits.max_bytes,
+    };
+    (capacity, limits.eviction_policy)
+}
+
+/// Builds the delay distribution from the flat `delay_*` fields.
+fn parse_flat_delay(v: &mut Validator, toml: &ConfigTOML, kind: &str) -> Option<DelayDistribution> {
+    match kind {
+        "constant" => {
+            let context = "when delay_distribution is 'constant'";
+            v.require(toml.delay_mean, "delay_mean", context)
+                .map(|mean| {
+                    v.check(mean >= 0., "delay_mean", "must be non-negative");
+                    DelayDistribution::Constant { value: mean }
+                })
+        }
+        "uniform" => {
+            let context = "when delay_distribution is 'uniform'";
+            let left = v.require(toml.delay_min, "delay_min", context);
+            let right = v.require(toml.delay_max, "delay_max", context);
+            if let Some(left) = left {
+                v.check(left >= 0., "delay_min", "must be non-negative");
+            }
+            if let (Some(left), Some(right)) = (left, right) {
+                v.check(right > left, "delay_max", "must be greater than delay_min");
+            }
+            left.zip(right)
+                .map(|(left, right)| DelayDistribution::Uniform { left, right })
+        }
+        "positive_normal" => {
+            let context = "when delay_distribution is 'positive_normal'";
+            let mean = v.require(toml.delay_mean, "delay_mean", context);
+            let std_dev = v.require(toml.delay_std_dev, "delay_std_dev", context);
+            if let Some(mean) = mean {
+                v.check(mean >= 0., "delay_mean", "must be non-negative");
+            }
+            if let Some(std_dev) = std_dev {
+                v.check(std_dev >= 0., "delay_std_dev", "must be non-negative");
+            }
+            mean.zip(std_dev)
+                .map(|(mean, std_dev)| DelayDistribution::PositiveNormal { mean, std_dev })
+        }
+        other => {
+            v.error("delay_distribution", format!("invalid value '{}'", other));
+            None
+        }
+    }
+}
+
+/// Validates the delay distribution from the `network.delay` table.
+fn validate_delay(v: &mut Validator, delay: &DelayDistribution) {
+    match *delay {
+        DelayDistribution::Constant { value } => {
+            v.check(value >= 0., "network.delay.value", "must be non-negative");
+        }
+        DelayDistribution::Uniform { left, right } => {
+            v.check(left >= 0., "network.delay.min", "must be non-negative");
+            v.check(
+                right > left,
+                "network.delay.max",
+                "must be greater than network.delay.min",
+            );
+        }
+        DelayDistribution::PositiveNormal { mean, std_dev } => {
+            v.check(mean >= 0., "network.delay.mean", "must be non-negative");
+            v.check(
+                std_dev >= 0.,
+                "network.delay.std_dev",
+                "must be non-negative",
+            );
+        }
+    }
+}
+
+/// Validates the topology from the `network.topology` table.
+fn validate_topology(v: &mut Validator, topology: &Topology, num_peers: u32) {
+    match *topology {
+        Topology::Full => {}
+        Topology::Ring { first_id, last_id } => {
+            v.check(
+                first_id < last_id,
+                "network.topology.first_id",
+                "must be less than network.topology.last_id",
+            );
+            v.check(
+                last_id < num_peers,
+                "network.topology.last_id",
+                "must be less than num_peers",
+            );
+        }
+        Topology::Star { center_id } => {
+            v.check(
+                center_id < num_peers,
+                "network.topology.center_id",
+                "must be less than num_peers",
+            );
         }
     }
 }
@@ -145,6 +1015,264 @@ mod tests {
 
     #[test]
     fn test_from_default_config_file() {
-        let _config = SimulationConfig::from_default_config_file();
+        SimulationConfig::from_default_config_file().unwrap();
+    }
+
+    #[test]
+    fn test_all_errors_are_collected() {
+        let overrides = [
+            ("dht.alpha", "30"),
+            ("network.delay.min", "0.5"),
+            ("network.delay.max", "0.1"),
+            ("dht.record_publication_interval", "200000.0"),
+            ("network.topology.kind", "'star'"),
+            ("network.topology.center_id", "10000"),
+            ("trace_query_ids", "[1]"),
+            ("provide_retrieved_data", "true"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let toml =
+            ConfigTOML::from_toml_str(include_str!("../../config.toml"), &overrides).unwrap();
+        let Err(ConfigError::Invalid(errors)) = SimulationConfig::from_toml(toml) else {
+            panic!("expected the config to be invalid");
+        };
+        let fields = errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                "dht.alpha",
+                "dht.record_publication_interval",
+                "network.delay.max",
+                "network.topology.center_id",
+                "trace_query_ids",
+                "provide_retrieved_data"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_periodic_tasks() {
+        let overrides = [("dht.enable_republishing", "true"), ("gc_interval", "60.0")]
+            .map(|(key, value)| (key.to_string(), value.to_string()));
+        let config = SimulationConfig::from_toml_str(include_str!("../../config.toml")).unwrap();
+        assert!(config.periodic_tasks().is_empty());
+        let config = SimulationConfig::from_toml_str_with_overrides(
+            include_str!("../../config.toml"),
+            &overrides,
+        )
+        .unwrap();
+        assert_eq!(
+            config.periodic_tasks(),
+            vec!["dht.enable_republishing", "gc_interval"]
+        );
+    }
+
+    #[test]
+    fn test_flat_format() {
+        let config = SimulationConfig::from_toml_str(
+            r#"
+            log_level_filter = "info"
+            enable_user_load_generation = true
+            user_load_block_size = 8
+            user_load_blocks_pool_size = 100
+            user_load_events_interval = 1.5
+            seed = 1
+            k = 20
+            alpha = 3
+            num_peers = 100
+            delay_distribution = 'constant'
+            delay_mean = 0.05
+            topology = 'ring'
+            record_publication_interval = 79200.0
+            record_expiration_interval = 172800.0
+            kbuckets_refresh_interval = 600.0
+            query_timeout = 60.0
+            caching_max_peers = 1
+            enable_bootstrap = true
+            enable_republishing = false
+            lookup_termination = 'beta'
+            lookup_beta = 3
+            dht_storage_max_records = 10
+            file_storage_eviction_policy = 'random'
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.k, 20);
+        assert!(config.enable_user_load_generation);
+        assert!(config.enable_bootstrap);
+        assert_eq!(
+            config.lookup_termination,
+            LookupTermination::Beta { beta: 3 }
+        );
+        assert_eq!(config.dht_storage_capacity.max_records, Some(10));
+        assert_eq!(config.dht_storage_eviction_policy, EvictionPolicy::Lru);
+        assert_eq!(config.file_storage_eviction_policy, EvictionPolicy::Random);
+        assert!(matches!(
+            config.delay_distribution,
+            DelayDistribution::Constant { value } if value == 0.05
+        ));
+        assert!(matches!(
+            config.topology,
+            Topology::Ring {
+                first_id: 0,
+                last_id: 99
+            }
+        ));
+    }
+
+    #[test]
+    fn test_conflicting_formats() {
+        let overrides = [("k".to_string(), "8".to_string())];
+        let toml =
+            ConfigTOML::from_toml_str(include_str!("../../config.toml"), &overrides).unwrap();
+        let Err(ConfigError::Invalid(errors)) = SimulationConfig::from_toml(toml) else {
+            panic!("expected the config to be invalid");
+        };
+        assert_eq!(errors[0].field, "k");
+    }
+
+    #[test]
+    fn test_termination_and_storage_tables() {
+        let overrides = [
+            ("log_level_filter", "'warn'"),
+            (
+                "dht.lookup_termination",
+                "{ kind = 'max_hops', max_hops = 2 }",
+            ),
+            ("storage.dht.max_bytes", "1000"),
+            ("storage.file.eviction_policy", "'oldest_expiry_first'"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let config = SimulationConfig::from_toml_str_with_overrides(
+            include_str!("../../config.toml"),
+            &overrides,
+        )
+        .unwrap();
+        assert_eq!(config.log_level_filter, log::LevelFilter::Warn);
+        assert_eq!(
+            config.lookup_termination,
+            LookupTermination::MaxHops { max_hops: 2 }
+        );
+        assert_eq!(
+            config.dht_storage_capacity,
+            StorageCapacity {
+                max_records: None,
+                max_bytes: Some(1000)
+            }
+        );
+        assert_eq!(
+            config.file_storage_eviction_policy,
+            EvictionPolicy::OldestExpiryFirst
+        );
+
+        let overrides = [
+            ("dht.lookup_termination", "{ kind = 'beta', beta = 0 }"),
+            ("lookup_max_requests", "10"),
+            ("dht_storage_max_records", "10"),
+            ("file_storage_eviction_policy", "'lfu'"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let toml =
+            ConfigTOML::from_toml_str(include_str!("../../config.toml"), &overrides).unwrap();
+        let Err(ConfigError::Invalid(errors)) = SimulationConfig::from_toml(toml) else {
+            panic!("expected the config to be invalid");
+        };
+        let fields = errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                "lookup_max_requests",
+                "dht.lookup_termination.beta",
+                "dht_storage_max_records",
+                "file_storage_eviction_policy"
+            ]
+        );
+
+        for (key, value) in [
+            ("dht.lookup_termination", "{ kind = 'beta' }"),
+            ("storage.dht.eviction_policy", "'fifo'"),
+            ("log_level_filter", "'verbose'"),
+        ] {
+            let overrides = [(key.to_string(), value.to_string())];
+            let result = ConfigTOML::from_toml_str(include_str!("../../config.toml"), &overrides);
+            assert!(matches!(result, Err(ConfigError::Parse(_))), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_time_varying_patterns() {
+        let overrides = [
+            ("user_load.rate.kind", "'sinusoidal'"),
+            ("user_load.rate.amplitude", "1.5"),
+            ("regions", "[{ name = 'europe', fraction = 0.5 }]"),
+            ("churn.interval", "0.0"),
+            ("churn.online.kind", "'constant'"),
+            ("churn.online.value", "0.9"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let toml =
+            ConfigTOML::from_toml_str(include_str!("../../config.toml"), &overrides).unwrap();
+        let Err(ConfigError::Invalid(errors)) = SimulationConfig::from_toml(toml) else {
+            panic!("expected the config to be invalid");
+        };
+        let fields = errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec!["user_load.rate.amplitude", "regions", "churn.interval"]
+        );
+    }
+
+    #[test]
+    fn test_profile_assignment() {
+        let errors = |overrides: &[(&str, &str)]| {
+            let overrides = overrides
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<Vec<_>>();
+            let toml =
+                ConfigTOML::from_toml_str(include_str!("../../config.toml"), &overrides).unwrap();
+            match SimulationConfig::from_toml(toml) {
+                Ok(_) => vec![],
+                Err(ConfigError::Invalid(errors)) => errors
+                    .into_iter()
+                    .map(|e| (e.field, e.reason))
+                    .collect::<Vec<_>>(),
+                Err(_) => panic!("expected the config to be parsed"),
+            }
+        };
+        // the only peer gets the first profile, so no peer generates the load
+        let unassigned_load = errors(&[
+            ("num_peers", "1"),
+            (
+                "profiles",
+                "[{ name = 'a', share = 0.5, load_share = 0.0 }, { name = 'b', share = 0.5 }]",
+            ),
+        ]);
+        assert_eq!(unassigned_load.len(), 1);
+        assert_eq!(unassigned_load[0].0, "profiles");
+        assert!(unassigned_load[0].1.contains("load shares"));
+        let no_servers = errors(&[(
+            "profiles",
+            "[{ name = 'a', share = 1.0, dht_mode = 'client' }]",
+        )]);
+        assert_eq!(no_servers.len(), 1);
+        assert!(no_servers[0].1.contains("DHT mode"));
+        let churn_and_uptime = errors(&[
+            (
+                "profiles",
+                "[{ name = 'a', share = 1.0, uptime = { session = 10.0, downtime = 1.0 } }]",
+            ),
+            (
+                "churn",
+                "{ interval = 600.0, turnover = 0.1, online = { kind = 'constant', value = 0.5 } }",
+            ),
+        ]);
+        assert_eq!(churn_and_uptime.len(), 1);
+        assert_eq!(churn_and_uptime[0].0, "churn");
+        assert!(errors(&[(
+            "profiles",
+            "[{ name = 'a', share = 0.5 }, { name = 'b', share = 0.5, dht_mode = 'client' }]",
+        )])
+        .is_empty());
     }
 }
@@ -1,48 +1,291 @@
+use super::error::ConfigError;
+use crate::{
+    network::{
+        Arrivals, ChurnConfig, DelayDistribution, FlashCrowd, Popularity, RateFunction, Region,
+        Topology, WorkloadTraceConfig,
+    },
+    profile::PeerProfile,
+    query::LookupTermination,
+    storage::EvictionPolicy,
+};
 use serde::Deserialize;
 use std::path::Path;
 
 /// Represents the structure to parse the configuration file into.
+///
+/// The parameters of the network, the user load, the DHT and the storages are grouped into tables.
+/// For compatibility, they may also be specified at the top level as in the flat format,
+/// e.g. `k` instead of `dht.k` or `delay_distribution` and `delay_*` instead of `network.delay`.
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ConfigTOML {
-    pub log_level_filter: String,
+    pub log_level_filter: log::LevelFilter,
     pub log_file_path: Option<String>,
-    pub enable_user_load_generation: bool,
+    pub network: Option<NetworkTOML>,
+    pub user_load: Option<UserLoadTOML>,
+    pub dht: Option<DhtTOML>,
+    pub storage: Option<StorageTOML>,
+    pub workload_trace: Option<WorkloadTraceConfig>,
+    pub regions: Option<Vec<Region>>,
+    pub churn: Option<ChurnConfig>,
+    pub profiles: Option<Vec<PeerProfile>>,
+    pub enable_user_load_generation: Option<bool>,
     pub user_load_block_size: Option<usize>,
     pub user_load_blocks_pool_size: Option<usize>,
     pub user_load_events_interval: Option<f64>,
     pub seed: u64,
-    pub k: usize,
-    pub alpha: usize,
+    pub k: Option<usize>,
+    pub alpha: Option<usize>,
     pub num_peers: u32,
-    pub delay_distribution: String,
+    pub delay_distribution: Option<String>,
     pub delay_mean: Option<f64>,
     pub delay_std_dev: Option<f64>,
     pub delay_min: Option<f64>,
     pub delay_max: Option<f64>,
-    pub topology: String,
-    pub record_publication_interval: f64,
-    pub record_expiration_interval: f64,
-    pub kbuckets_refresh_interval: f64,
-    pub query_timeout: f64,
-    pub caching_max_peers: usize,
-    pub enable_bootstrap: bool,
-    pub enable_republishing: bool,
+    pub topology: Option<String>,
+    pub lookup_termination: Option<String>,
+    pub lookup_beta: Option<usize>,
+    pub lookup_max_hops: Option<u32>,
+    pub lookup_max_requests: Option<usize>,
+    pub record_publication_interval: Option<f64>,
+    pub record_expiration_interval: Option<f64>,
+    pub kbuckets_refresh_interval: Option<f64>,
+    pub query_timeout: Option<f64>,
+    pub request_timeout: Option<f64>,
+    #[serde(default)]
+    pub evict_unresponsive_peers: bool,
+    pub caching_max_peers: Option<usize>,
+    pub enable_bootstrap: Option<bool>,
+    pub enable_republishing: Option<bool>,
+    pub dht_storage_max_records: Option<usize>,
+    pub dht_storage_max_bytes: Option<usize>,
+    pub dht_storage_eviction_policy: Option<EvictionPolicy>,
+    pub file_storage_max_records: Option<usize>,
+    pub file_storage_max_bytes: Option<usize>,
+    pub file_storage_eviction_policy: Option<EvictionPolicy>,
+    #[serde(default)]
+    pub cache_retrieved_data: bool,
+    #[serde(default)]
+    pub provide_retrieved_data: bool,
+    pub retrieve_max_providers: Option<usize>,
+    pub record_expiration_sweep_interval: Option<f64>,
+    #[serde(default)]
+    pub collect_query_records: bool,
+    pub report_json_path: Option<String>,
+    pub report_csv_path: Option<String>,
+    #[serde(default = "default_report_busiest_peers")]
+    pub report_busiest_peers: usize,
+    pub trace_file_path: Option<String>,
+    pub metrics_sampling_interval: Option<f64>,
+    pub metrics_file_path: Option<String>,
+    pub routing_health_path: Option<String>,
+    pub replication_audit_interval: Option<f64>,
+    pub replication_audit_path: Option<String>,
+    pub trace_query_ids: Option<Vec<u64>>,
+    pub trace_peers: Option<Vec<u32>>,
+    pub gc_interval: Option<f64>,
+    pub gc_watermark_bytes: Option<usize>,
+}
+
+/// Represents the `[network]` table of the configuration file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkTOML {
+    pub delay: Option<DelayDistribution>,
+    pub topology: Option<Topology>,
+}
+
+/// Represents the `[user_load]` table of the configuration file.
+/// The user load is generated if the table is present, unless it is disabled explicitly.
+/// The arrivals, the popularity and the ratios can only be specified in the table.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserLoadTOML {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub block_size: usize,
+    pub blocks_pool_size: usize,
+    pub events_interval: f64,
+    #[serde(default)]
+    pub arrivals: Arrivals,
+    #[serde(default)]
+    pub popularity: Popularity,
+    #[serde(default = "default_publish_ratio")]
+    pub publish_ratio: f64,
+    #[serde(default)]
+    pub new_content_ratio: f64,
+    /// The factor the arrival rate is multiplied by, evaluated at the local time of the peers.
+    pub rate: Option<RateFunction>,
+    #[serde(default)]
+    pub flash_crowds: Vec<FlashCrowd>,
+}
+
+/// Represents the `[dht]` table of the configuration file.
+/// Every field may be specified at the top level instead.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DhtTOML {
+    pub k: Option<usize>,
+    pub alpha: Option<usize>,
+    pub record_publication_interval: Option<f64>,
+    pub record_expiration_interval: Option<f64>,
+    pub kbuckets_refresh_interval: Option<f64>,
+    pub query_timeout: Option<f64>,
+    pub request_timeout: Option<f64>,
+    pub caching_max_peers: Option<usize>,
+    pub enable_bootstrap: Option<bool>,
+    pub enable_republishing: Option<bool>,
+    pub lookup_termination: Option<LookupTermination>,
+}
+
+/// Represents the `[storage]` table of the configuration file,
+/// which holds the tables of the DHT storage and the file storage.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StorageTOML {
+    pub dht: Option<StorageLimitsTOML>,
+    pub file: Option<StorageLimitsTOML>,
+}
+
+/// Represents the `[storage.dht]` and `[storage.file]` tables of the configuration file.
+/// The limits that are not specified are not enforced.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StorageLimitsTOML {
+    pub max_records: Option<usize>,
+    pub max_bytes: Option<usize>,
+    #[serde(default)]
+    pub eviction_policy: EvictionPolicy,
+}
+
+fn default_report_busiest_peers() -> usize {
+    10
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_publish_ratio() -> f64 {
+    0.5
 }
 
 impl ConfigTOML {
     /// Parses the configuration from a TOML file.
-    pub fn from_file(path: impl AsRef<Path>) -> Self {
-        let data = std::fs::read_to_string(path).expect("Failed to read config file");
-        toml::from_str(&data).expect("Failed to parse config")
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the configuration file.
+    /// * `overrides` - The fields to override, see `from_toml_str`.
+    pub fn from_file(
+        path: impl AsRef<Path>,
+        overrides: &[(String, String)],
+    ) -> Result<Self, ConfigError> {
+        let data = std::fs::read_to_string(path)?;
+        Self::from_toml_str(&data, overrides)
+    }
+
+    /// Parses the configuration from a TOML string.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The contents of the configuration file.
+    /// * `overrides` - Pairs of field names and values that replace the values from `data`.
+    ///   Fields of the tables are addressed by dotted paths, e.g. `dht.k`.
+    ///   A value is parsed as a TOML value, and is treated as a string if it is not one,
+    ///   so both `topology='ring'` and `topology=ring` are accepted.
+    pub fn from_toml_str(data: &str, overrides: &[(String, String)]) -> Result<Self, ConfigError> {
+        let mut table = data
+            .parse::<toml::Table>()
+            .map_err(|err| ConfigError::Parse(err.to_string()))?;
+        for (key, value) in overrides {
+            set_field(&mut table, key, parse_override_value(value))?;
+        }
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|err: toml::de::Error| ConfigError::Parse(err.to_string()))
     }
 }
 
+/// Sets the field at the dotted path, creating the missing tables on the way.
+fn set_field(table: &mut toml::Table, path: &str, value: toml::Value) -> Result<(), ConfigError> {
+    let (tables, field) = match path.rsplit_once('.') {
+        Some((tables, field)) => (tables.split('.').collect::<Vec<_>>(), field),
+        None => (vec![], path),
+    };
+    let mut table = table;
+    for name in tables {
+        table = match table
+            .entry(name)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        {
+            toml::Value::Table(inner) => inner,
+            _ => {
+                return Err(ConfigError::Parse(format!(
+                    "cannot override '{}': '{}' is not a table",
+                    path, name
+                )))
+            }
+        };
+    }
+    table.insert(field.to_string(), value);
+    Ok(())
+}
+
+/// Parses the value of an overridden field.
+fn parse_override_value(value: &str) -> toml::Value {
+    format!("value = {}", value)
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_from_file() {
-        let _config = ConfigTOML::from_file("config.toml");
+        let _config = ConfigTOML::from_file("config.toml", &[]).unwrap();
+    }
+
+    #[test]
+    fn test_overrides() {
+        let overrides = [
+            ("num_peers".to_string(), "100".to_string()),
+            ("lookup_termination".to_string(), "beta".to_string()),
+            ("lookup_beta".to_string(), "3".to_string()),
+            ("trace_peers".to_string(), "[1, 2]".to_string()),
+        ];
+        let config =
+            ConfigTOML::from_toml_str(include_str!("../../config.toml"), &overrides).unwrap();
+        assert_eq!(config.num_peers, 100);
+        assert_eq!(config.lookup_termination.as_deref(), Some("beta"));
+        assert_eq!(config.lookup_beta, Some(3));
+        assert_eq!(config.trace_peers, Some(vec![1, 2]));
+    }
+
+    #[test]
+    fn test_nested_overrides() {
+        let overrides = [
+            ("dht.k".to_string(), "8".to_string()),
+            ("network.topology.kind".to_string(), "star".to_string()),
+            ("network.topology.center_id".to_string(), "5".to_string()),
+        ];
+        let config =
+            ConfigTOML::from_toml_str(include_str!("../../config.toml"), &overrides).unwrap();
+        assert_eq!(config.dht.unwrap().k, Some(8));
+        assert!(matches!(
+            config.network.unwrap().topology,
+            Some(Topology::Star { center_id: 5 })
+        ));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_unknown_field() {
+        let overrides = [("num_peer".to_string(), "100".to_string())];
+        let result = ConfigTOML::from_toml_str(include_str!("../../config.toml"), &overrides);
+        assert!(matches!(result, Err(ConfigError::Parse(_))));
+    }
+}
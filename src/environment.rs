@@ -0,0 +1,199 @@
+use crate::{
+    kbucket::KeysTree,
+    profile::{assign_profiles, DhtMode, PeerProfile},
+    Key, PeerId, SimulationConfig,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Represents the state shared by all the components of a single simulation:
+/// the configuration, the keys and the profiles of the peers.
+///
+/// The environment is created once per simulation and shared via `Rc`,
+/// so that multiple simulations with different configurations can run in one process.
+pub struct Environment {
+    config: SimulationConfig,
+    keys: Vec<Key>,
+    keys_tree: KeysTree,
+    peer_id_by_key: HashMap<Key, PeerId>,
+    profiles: Vec<usize>, // the index of the profile of every peer, empty if there are no profiles
+}
+
+impl Environment {
+    /// Creates a new `Environment`, generates the keys of all the peers
+    /// and assigns the profiles to them.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration of the simulation.
+    pub fn new(config: SimulationConfig) -> Self {
+        let keys = (0..config.num_peers)
+            .map(Key::from_peer_id)
+            .collect::<Vec<_>>();
+        let profiles = assign_profiles(&config.profiles, config.num_peers as usize);
+        let server_keys = keys
+            .iter()
+            .enumerate()
+            .filter(|&(id, _)| {
+                profiles
+                    .get(id)
+                    .is_none_or(|&profile| config.profiles[profile].dht_mode == DhtMode::Server)
+            })
+            .map(|(_, key)| key.clone())
+            .collect::<Vec<_>>();
+        let keys_tree = KeysTree::new(&server_keys);
+        let peer_id_by_key = keys
+            .iter()
+            .enumerate()
+            .map(|(id, key)| (key.clone(), id as PeerId))
+            .collect();
+        Self {
+            config,
+            keys,
+            keys_tree,
+            peer_id_by_key,
+            profiles,
+        }
+    }
+
+    /// Returns the configuration of the simulation.
+    pub fn config(&self) -> &SimulationConfig {
+        &self.config
+    }
+
+    /// Returns the replication parameter k.
+    pub fn k(&self) -> usize {
+        self.config.k
+    }
+
+    /// Returns the concurrency parameter alpha.
+    pub fn alpha(&self) -> usize {
+        self.config.alpha
+    }
+
+    /// Returns the precomputed key of the peer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the peer is not a part of the simulation.
+    pub fn peer_key(&self, peer_id: PeerId) -> &Key {
+        &self.keys[peer_id as usize]
+    }
+
+    /// Returns the profile of the peer, or `None` if no profiles are configured
+    /// or the ID does not belong to a peer.
+    pub fn peer_profile(&self, peer_id: PeerId) -> Option<&PeerProfile> {
+        self.profiles
+            .get(peer_id as usize)
+            .map(|&profile| &self.config.profiles[profile])
+    }
+
+    /// Returns `true` unless the peer is a DHT client, see `DhtMode`.
+    pub fn is_dht_server(&self, peer_id: PeerId) -> bool {
+        self.peer_profile(peer_id)
+            .is_none_or(|profile| profile.dht_mode == DhtMode::Server)
+    }
+
+    /// Returns the delay the profiles of the peers add to a message:
+    /// the processing delay of the sender and the transfer time
+    /// limited by the lower bandwidth of the two peers.
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - The ID of the sender.
+    /// * `dst` - The ID of the receiver.
+    /// * `size` - The size of the message in bytes.
+    pub fn profile_delay(&self, src: PeerId, dst: PeerId, size: usize) -> f64 {
+        let (src, dst) = (self.peer_profile(src), self.peer_profile(dst));
+        let processing_delay = src.map_or(0., |profile| profile.processing_delay);
+        let bandwidth = [src, dst]
+            .into_iter()
+            .flatten()
+            .filter_map(|profile| profile.bandwidth)
+            .reduce(f64::min);
+        processing_delay + bandwidth.map_or(0., |bandwidth| size as f64 / bandwidth)
+    }
+
+    /// Finds the closest DHT servers of the simulation to the given key.
+    /// This method uses information that is not available in the real world.
+    pub fn find_closest_peers(&self, key: &Key, count: usize) -> HashSet<PeerId> {
+        self.keys_tree
+            .find_closest_keys(key, count)
+            .iter()
+            .map(|key| *self.peer_id_by_key.get(key).expect("Got unexpected key"))
+            .collect()
+    }
+}
+
+impl std::fmt::Debug for Environment {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Environment")
+            .field("num_peers", &self.keys.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Creates a configuration for the unit tests with the given number of peers.
+/// The rest of the configuration is taken from the default configuration file.
+#[cfg(test)]
+pub(crate) fn test_config(num_peers: u32) -> SimulationConfig {
+    let mut config = SimulationConfig::from_toml_str(include_str!("../config.toml")).unwrap();
+    config.num_peers = num_peers;
+    config
+}
+
+/// Creates an environment for the unit tests with the given number of peers,
+/// see `test_config`.
+#[cfg(test)]
+pub(crate) fn test_env(num_peers: u32) -> std::rc::Rc<Environment> {
+    std::rc::Rc::new(Environment::new(test_config(num_peers)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_independent_environments() {
+        let small = test_env(4);
+        let large = test_env(64);
+        let key = Key::from_sha256(b"key");
+        assert_eq!(small.find_closest_peers(&key, 10).len(), 4);
+        assert_eq!(large.find_closest_peers(&key, 10).len(), 10);
+        assert_eq!(small.peer_key(3), large.peer_key(3));
+        assert_eq!(small.peer_key(3), &Key::from_peer_id(3));
+    }
+
+    #[test]
+    fn test_profiles() {
+        let data = format!(
+            "{}{}",
+            include_str!("../config.toml"),
+            r#"
+            [[profiles]]
+            name = "server"
+            share = 0.25
+            bandwidth = 1000.0
+            processing_delay = 0.5
+
+            [[profiles]]
+            name = "mobile"
+            share = 0.75
+            dht_mode = "client"
+            bandwidth = 100.0
+            "#
+        );
+        let mut config = SimulationConfig::from_toml_str(&data).unwrap();
+        config.num_peers = 16;
+        let env = Environment::new(config);
+        let servers = (0..16).filter(|&id| env.is_dht_server(id)).count();
+        assert_eq!(servers, 4);
+        let key = Key::from_sha256(b"key");
+        assert!(env
+            .find_closest_peers(&key, 10)
+            .iter()
+            .all(|&id| env.is_dht_server(id)));
+        assert_eq!(env.peer_profile(1).unwrap().name, "server");
+        assert_eq!(env.profile_delay(1, 0, 200), 0.5 + 2.);
+        assert_eq!(env.profile_delay(0, 1, 200), 2.);
+    }
+}
@@ -1,10 +1,11 @@
 use super::key::Key;
-use crate::{Distance, PeerId, CONFIG, K_VALUE};
-use std::collections::BinaryHeap;
+use crate::{Distance, Environment, PeerId};
+use std::{collections::BinaryHeap, rc::Rc};
 
 /// Represents a Kademlia buckets table.
 #[derive(Debug)]
 pub struct KBucketsTable {
+    env: Rc<Environment>,
     local_key: Key,
     buckets: Vec<Vec<KBucketEntry>>,
 }
@@ -17,8 +18,9 @@ struct KBucketEntry {
 
 impl KBucketsTable {
     /// Creates a new instance of `KBucketsTable` with the given local key.
-    pub fn new(local_key: &Key) -> Self {
+    pub fn new(env: Rc<Environment>, local_key: &Key) -> Self {
         Self {
+            env,
             local_key: local_key.clone(),
             buckets: vec![],
         }
@@ -34,6 +36,26 @@ impl KBucketsTable {
         self.buckets.len()
     }
 
+    /// Returns the number of peers in the Kademlia buckets table.
+    pub fn peers_count(&self) -> usize {
+        self.buckets.iter().map(Vec::len).sum()
+    }
+
+    /// Returns the number of peers in the bucket at the given index.
+    pub fn bucket_len(&self, index: usize) -> usize {
+        self.buckets.get(index).map_or(0, Vec::len)
+    }
+
+    /// Returns an iterator over the entries of the table as tuples of
+    /// the bucket index, the peer ID and the time the peer was last seen.
+    pub fn entries(&self) -> impl Iterator<Item = (usize, PeerId, f64)> + '_ {
+        self.buckets.iter().enumerate().flat_map(|(index, bucket)| {
+            bucket
+                .iter()
+                .map(move |entry| (index, entry.peer_id, entry.last_seen))
+        })
+    }
+
     /// Returns a precise list of the closest peers to the given key.
     pub fn local_closest_peers_precise(&self, key: &Key, count: usize) -> Vec<PeerId> {
         #[derive(PartialEq, Eq, PartialOrd, Ord)]
@@ -45,7 +67,7 @@ impl KBucketsTable {
         let mut heap = BinaryHeap::with_capacity(count);
         for entry in self.buckets.iter().flatten() {
             let peer_id = entry.peer_id;
-            let dist = Key::from_peer_id(peer_id).distance(key);
+            let dist = self.env.peer_key(peer_id).distance(key);
             if heap.len() < count {
                 heap.push(HeapItem { dist, peer_id });
             } else if dist < heap.peek().unwrap().dist {
@@ -72,11 +94,11 @@ impl KBucketsTable {
 
         if count < bucket.len() {
             let mut copy = bucket.iter().map(|entry| entry.peer_id).collect::<Vec<_>>();
-            copy.sort_by_key(|&id| Key::from_peer_id(id).distance(key));
+            copy.sort_by_key(|&id| self.env.peer_key(id).distance(key));
             return copy.into_iter().take(count).collect();
         }
 
-        let mut result = Vec::with_capacity(count.min(bucket.len() * *K_VALUE));
+        let mut result = Vec::with_capacity(count.min(bucket.len() * self.env.k()));
         let mut i = pos;
         while i < self.buckets.len() && result.len() < count {
             result.extend(self.buckets[i].iter().map(|entry| entry.peer_id));
@@ -102,13 +124,14 @@ impl KBucketsTable {
     ///
     /// Returns `true` if the peer was successfully added, `false` otherwise.
     pub fn add_peer(&mut self, peer_id: PeerId, curr_time: f64) -> bool {
-        let key = Key::from_peer_id(peer_id);
+        let key = self.env.peer_key(peer_id);
         if key == &self.local_key {
             return false;
         }
         let pos = self.local_key.distance(key).leading_zeros() as usize;
+        let k = self.env.k();
         if self.buckets.len() <= pos {
-            self.buckets.resize(pos + 1, Vec::with_capacity(*K_VALUE));
+            self.buckets.resize(pos + 1, Vec::with_capacity(k));
         }
         let bucket = &mut self.buckets[pos];
         let pos = bucket.iter().position(|entry| entry.peer_id == peer_id);
@@ -122,13 +145,14 @@ impl KBucketsTable {
                 bucket.push(entry);
             }
             None => {
-                if bucket.len() < *K_VALUE {
+                if bucket.len() < k {
                     bucket.push(entry);
                     return true;
                 }
                 let mut idx = None;
                 for (i, kb_entry) in bucket.iter().enumerate() {
-                    if curr_time - kb_entry.last_seen > CONFIG.kbuckets_refresh_interval {
+                    if curr_time - kb_entry.last_seen > self.env.config().kbuckets_refresh_interval
+                    {
                         idx = Some(i);
                     }
                 }
@@ -140,16 +164,38 @@ impl KBucketsTable {
         }
         true
     }
+
+    /// Removes a peer from the Kademlia buckets table.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_id` - The ID of the peer to remove.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the peer was removed, `false` if it was not in the table.
+    pub fn remove_peer(&mut self, peer_id: PeerId) -> bool {
+        let key = self.env.peer_key(peer_id);
+        let pos = self.local_key.distance(key).leading_zeros() as usize;
+        if let Some(bucket) = self.buckets.get_mut(pos) {
+            if let Some(idx) = bucket.iter().position(|entry| entry.peer_id == peer_id) {
+                bucket.remove(idx);
+                return true;
+            }
+        }
+        false
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::environment::test_env;
 
     #[test]
     fn test_local_closest_peers_approximate() {
         let local_key = Key::from_sha256(b"bytes");
-        let mut table = KBucketsTable::new(&local_key);
+        let mut table = KBucketsTable::new(test_env(8), &local_key);
 
         // Add some peers to the table
         table.add_peer(1, 0.0);
@@ -174,7 +220,7 @@ mod tests {
     #[test]
     fn test_local_closest_peers_precise() {
         let local_key = Key::from_sha256(b"bytes");
-        let mut table = KBucketsTable::new(&local_key);
+        let mut table = KBucketsTable::new(test_env(8), &local_key);
 
         // Add some peers to the table
         table.add_peer(1, 0.0);
@@ -199,7 +245,7 @@ mod tests {
     #[test]
     fn test_add_peer() {
         let local_key = Key::from_sha256(&2u32.to_le_bytes());
-        let mut table = KBucketsTable::new(&local_key);
+        let mut table = KBucketsTable::new(test_env(8), &local_key);
 
         // Add a peer to an empty table
         assert_eq!(table.add_peer(1, 0.0), true);
@@ -212,4 +258,18 @@ mod tests {
         assert_eq!(table.add_peer(3, 1.0), true);
         assert_eq!(table.add_peer(5, 2.0), true);
     }
+
+    #[test]
+    fn test_remove_peer() {
+        let local_key = Key::from_sha256(b"bytes");
+        let mut table = KBucketsTable::new(test_env(8), &local_key);
+
+        table.add_peer(1, 0.0);
+        table.add_peer(2, 0.0);
+        assert!(table.remove_peer(1));
+        assert!(!table.remove_peer(1));
+        assert!(!table.remove_peer(3));
+        let closest_peers = table.local_closest_peers_precise(&Key::from_sha256(b"all"), 10);
+        assert_eq!(closest_peers, vec![2]);
+    }
 }
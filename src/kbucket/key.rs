@@ -1,6 +1,5 @@
-use crate::{PeerId, KEYS_POOL, PEER_ID_BY_KEY};
+use crate::PeerId;
 use dslab_core::SimulationContext;
-use std::collections::HashSet;
 use uint::*;
 
 construct_uint! {
@@ -37,9 +36,10 @@ impl Key {
         Self(U256::from(Sha256::digest(bytes).as_slice()))
     }
 
-    /// Returns a static reference to the key that was lazily generated.
-    pub fn from_peer_id(peer_id: PeerId) -> &'static Self {
-        &KEYS_POOL[peer_id as usize]
+    /// Creates the key of the peer with the given ID.
+    /// Prefer `Environment::peer_key` that returns a precomputed key.
+    pub fn from_peer_id(peer_id: PeerId) -> Self {
+        Self::from_sha256(&peer_id.to_le_bytes())
     }
 
     /// Generates a random key in the bucket at the given index.
@@ -258,14 +258,6 @@ impl KeysTree {
         ans.truncate(count);
         ans
     }
-
-    /// Finds the closest peers to the given key in the tree.
-    pub fn find_closest_peers(&self, key: &Key, count: usize) -> HashSet<PeerId> {
-        self.find_closest_keys(key, count)
-            .iter()
-            .map(|key| *PEER_ID_BY_KEY.get(key).expect("Got unexpected key"))
-            .collect()
-    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,121 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use std::{cell::RefCell, fs::File, io::Write, path::Path, sync::Once, time::Instant};
+
+/// Represents the destination of the log records of a thread.
+struct Sink {
+    level: LevelFilter,
+    start: Instant,
+    writer: Box<dyn Write>,
+}
+
+thread_local! {
+    static SINK: RefCell<Option<Sink>> = const { RefCell::new(None) };
+}
+
+/// Passes the log records to the sink of the thread they are made in.
+///
+/// A simulation runs in a single thread, so the simulations run in parallel,
+/// e.g. by a sweep, write their logs into separate sinks with their own levels.
+struct ThreadLogger;
+
+static LOGGER: ThreadLogger = ThreadLogger;
+
+impl Log for ThreadLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        SINK.with(|sink| {
+            sink.borrow()
+                .as_ref()
+                .is_some_and(|sink| metadata.level() <= sink.level)
+        })
+    }
+
+    fn log(&self, record: &Record) {
+        SINK.with(|sink| {
+            let mut sink = sink.borrow_mut();
+            let Some(sink) = sink.as_mut().filter(|sink| record.level() <= sink.level) else {
+                return;
+            };
+            let elapsed = sink.start.elapsed();
+            let seconds = elapsed.as_secs();
+            let _ = writeln!(
+                sink.writer,
+                "[{:02}:{:02}:{:02}.{:03}] {:6} {}",
+                seconds / 3600,
+                (seconds / 60) % 60,
+                seconds % 60,
+                elapsed.subsec_millis(),
+                record.level(),
+                record.args()
+            );
+        });
+    }
+
+    fn flush(&self) {
+        SINK.with(|sink| {
+            if let Some(sink) = sink.borrow_mut().as_mut() {
+                let _ = sink.writer.flush();
+            }
+        });
+    }
+}
+
+/// Directs the log records made in the current thread to the given file,
+/// or to stderr if it is not specified. The sinks of the other threads are not affected.
+///
+/// # Arguments
+///
+/// * `path` - The path to the log file, which is truncated.
+/// * `level` - The maximum level of the records to write.
+pub fn log_to(path: Option<impl AsRef<Path>>, level: LevelFilter) -> std::io::Result<()> {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        // the levels are filtered by the sinks
+        if log::set_logger(&LOGGER).is_ok() {
+            log::set_max_level(LevelFilter::Trace);
+        }
+    });
+    let writer: Box<dyn Write> = match path {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(std::io::stderr()),
+    };
+    SINK.with(|sink| {
+        *sink.borrow_mut() = Some(Sink {
+            level,
+            start: Instant::now(),
+            writer,
+        })
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sinks_of_threads() {
+        let dir = std::env::temp_dir().join(format!("logger-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = [dir.join("first.log"), dir.join("second.log")];
+        std::thread::scope(|scope| {
+            for (i, (path, level)) in paths
+                .iter()
+                .zip([LevelFilter::Info, LevelFilter::Warn])
+                .enumerate()
+            {
+                scope.spawn(move || {
+                    log_to(Some(path), level).unwrap();
+                    log::info!("info of {}", i);
+                    log::warn!("warning of {}", i);
+                });
+            }
+        });
+        let first = std::fs::read_to_string(&paths[0]).unwrap();
+        let second = std::fs::read_to_string(&paths[1]).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(first.contains("info of 0") && first.contains("warning of 0"));
+        assert!(!first.contains("of 1"));
+        assert!(!second.contains("info of 1") && second.contains("warning of 1"));
+        assert!(!second.contains("of 0"));
+    }
+}
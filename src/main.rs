@@ -1,10 +1,236 @@
-use ipfs_simulator::app::App;
-use std::time::Instant;
+use clap::{Parser, ValueEnum};
+use ipfs_simulator::{
+    app::App,
+    config::{ConfigError, FieldError},
+    query::QueriesStats,
+    scenario::ScenarioSpec,
+    sweep::{Sweep, SweepReport, SweepSpec},
+    SimulationConfig,
+};
+use std::{path::PathBuf, time::Instant};
+
+/// Runs a simulation of the IPFS network.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Path to the configuration file.
+    #[arg(long, default_value = "config.toml")]
+    config: PathBuf,
+
+    /// Overrides a field of the configuration file, e.g. `--set num_peers=1000`.
+    /// May be repeated.
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_override)]
+    overrides: Vec<(String, String)>,
+
+    /// The scenario to run.
+    #[arg(long, value_enum, default_value_t = Scenario::PublishingRetrievingRace)]
+    scenario: Scenario,
+
+    /// Runs the scenario described by the given file instead of `--scenario`,
+    /// see the examples in the `scenarios` directory.
+    #[arg(long, value_name = "FILE", conflicts_with = "scenario")]
+    scenario_file: Option<PathBuf>,
+
+    /// The time between the publishing and the retrieving in the
+    /// `publishing-retrieving-race` scenario. If it is negative, the retrieving happens first.
+    #[arg(long, default_value_t = -0.2, allow_negative_numbers = true)]
+    timedelta: f64,
+
+    /// Overrides the seed of the random number generator.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// The directory to write the reports and the other output files to.
+    /// In the sweep mode, every run writes into its own subdirectory.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Runs a parameter sweep described by the given file instead of a single simulation.
+    #[arg(long, value_name = "SPEC")]
+    sweep: Option<PathBuf>,
+
+    /// The number of simulations run in parallel in the sweep mode.
+    /// Defaults to the number of available CPUs.
+    #[arg(long)]
+    threads: Option<usize>,
+}
+
+/// The scenarios defined by the `App::run*` methods.
+#[derive(Clone, Copy, ValueEnum)]
+enum Scenario {
+    /// Only summarizes the statistics, see `App::run`.
+    Run,
+    /// Runs for an hour with no queries to explore the background tasks.
+    NoQueries,
+    /// Publishes new data every 0.1 seconds for an hour.
+    IntensivePublishing,
+    /// Publishes a pool of blocks and then retrieves them at random.
+    IntensiveRetrieving,
+    /// Publishes and retrieves the same blocks with the given `--timedelta`.
+    PublishingRetrievingRace,
+}
+
+/// Parses an override of a configuration field in the `KEY=VALUE` format.
+fn parse_override(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("expected KEY=VALUE, got '{}'", s)),
+    }
+}
+
+impl Scenario {
+    /// Checks that the scenario can be run with the given configuration.
+    /// The publishing-retrieving race runs until there are no events left,
+    /// so the periodic tasks, which never stop, must be disabled for it.
+    fn validate(self, config: &SimulationConfig) -> Result<(), ConfigError> {
+        let tasks = match self {
+            Scenario::PublishingRetrievingRace => config.periodic_tasks(),
+            _ => vec![],
+        };
+        if tasks.is_empty() {
+            return Ok(());
+        }
+        let reason = "must be disabled, since the scenario runs until there are no events left";
+        Err(ConfigError::Invalid(
+            tasks
+                .into_iter()
+                .map(|task| FieldError {
+                    field: task.to_string(),
+                    reason: reason.to_string(),
+                })
+                .collect(),
+        ))
+    }
+
+    /// Runs the scenario in the given application.
+    fn run(self, app: &mut App, timedelta: f64) -> QueriesStats {
+        match self {
+            Scenario::Run => app.run(),
+            Scenario::NoQueries => app.run_scenario_no_queries(),
+            Scenario::IntensivePublishing => app.run_scenario_intensive_publishing(),
+            Scenario::IntensiveRetrieving => app.run_scenario_intensive_retrieving(),
+            Scenario::PublishingRetrievingRace => {
+                app.run_scenario_publishing_retrieving_race(timedelta)
+            }
+        }
+    }
+}
+
+/// Runs the parameter sweep and writes the aggregated report.
+fn run_sweep(
+    args: Args,
+    spec_path: PathBuf,
+    scenario_file: Option<ScenarioSpec>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut overrides = args.overrides;
+    if let Some(seed) = args.seed {
+        overrides.push(("seed".to_string(), seed.to_string()));
+    }
+    let data = std::fs::read_to_string(&args.config)
+        .map_err(|err| format!("{}: {}", args.config.display(), err))?;
+    let base_seed = SimulationConfig::from_toml_str_with_overrides(&data, &overrides)
+        .map_err(|err| format!("{}: {}", args.config.display(), err))?
+        .seed;
+    let spec = SweepSpec::from_file(&spec_path, base_seed)
+        .map_err(|err| format!("{}: {}", spec_path.display(), err))?;
+    let mut sweep = Sweep::new(spec, &data, &overrides)?;
+    for run in sweep.runs() {
+        match (scenario_file.as_ref(), args.scenario_file.as_ref()) {
+            (Some(scenario), Some(path)) => scenario
+                .validate(&run.config)
+                .map_err(|err| format!("{}: {}", path.display(), err))?,
+            _ => args
+                .scenario
+                .validate(&run.config)
+                .map_err(|err| format!("{}: {}", args.config.display(), err))?,
+        }
+    }
+    let output = args.output.unwrap_or_else(|| PathBuf::from("sweep"));
+    sweep.set_output_dir(&output)?;
+
+    let threads = args
+        .threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |threads| threads.get()));
+    println!(
+        "Running {} simulations in {} threads",
+        sweep.runs().len(),
+        threads
+    );
+    let (scenario, timedelta) = (args.scenario, args.timedelta);
+    let report = sweep
+        .run(threads, |app| match scenario_file.as_ref() {
+            Some(scenario) => app
+                .run_scenario(scenario)
+                .expect("The scenario is validated in advance"),
+            None => scenario.run(app, timedelta),
+        })
+        .map_err(|err| format!("{}: {}", args.config.display(), err))?;
+    let path = SweepReport::default_path(&output);
+    report.write_csv(&path)?;
+    println!("Sweep report written to {}", path.display());
+    Ok(())
+}
 
 fn main() {
-    let mut app = App::new();
+    let mut args = Args::parse();
+    let scenario_file = args.scenario_file.as_ref().map(|path| {
+        ScenarioSpec::from_file(path).unwrap_or_else(|err| {
+            eprintln!("{}: {}", path.display(), err);
+            std::process::exit(1);
+        })
+    });
+    if let Some(spec_path) = args.sweep.take() {
+        let timer = Instant::now();
+        if let Err(err) = run_sweep(args, spec_path, scenario_file) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        println!(
+            "Sweep finished in {} (real) seconds",
+            timer.elapsed().as_secs_f64()
+        );
+        return;
+    }
+
+    let config = SimulationConfig::from_file_with_overrides(&args.config, &args.overrides);
+    let mut config = config.unwrap_or_else(|err| {
+        eprintln!("{}: {}", args.config.display(), err);
+        std::process::exit(1);
+    });
+    if let Some(seed) = args.seed {
+        config.seed = seed;
+    }
+    if args.scenario_file.is_none() {
+        if let Err(err) = args.scenario.validate(&config) {
+            eprintln!("{}: {}", args.config.display(), err);
+            std::process::exit(1);
+        }
+    }
+    if let Some(dir) = args.output.as_ref() {
+        if let Err(err) = std::fs::create_dir_all(dir) {
+            eprintln!("{}: {}", dir.display(), err);
+            std::process::exit(1);
+        }
+        config.set_output_dir(dir);
+    }
+
+    let mut app = App::new(config).unwrap_or_else(|err| {
+        eprintln!("{}: {}", args.config.display(), err);
+        std::process::exit(1);
+    });
     let timer = Instant::now();
-    // app.run();
-    app.run_scenario_publishing_retrieving_race(-0.2);
+    match (scenario_file, args.scenario_file.as_ref()) {
+        (Some(scenario), Some(path)) => {
+            if let Err(err) = app.run_scenario(&scenario) {
+                eprintln!("{}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            args.scenario.run(&mut app, args.timedelta);
+        }
+    }
     println!("Simulation finished in {} (real) seconds", timer.elapsed().as_secs_f64());
 }
@@ -1,4 +1,5 @@
 use crate::{query::QueryId, storage::Record, Key, PeerId};
+use dslab_core::EventData;
 use serde::Serialize;
 
 /// Request to find the closest peers to a key.
@@ -25,6 +26,14 @@ pub struct FindNodeQueryTimeout {
     pub query_id: QueryId,
 }
 
+/// Timeout event for a single request of a FindNode query.
+#[derive(Clone, Serialize)]
+pub struct FindNodeRequestTimeout {
+    pub query_id: QueryId,
+    /// The peer the request was sent to.
+    pub peer_id: PeerId,
+}
+
 /// Request to get the value associated with a key.
 #[derive(Clone, Serialize)]
 pub struct GetValueRequest {
@@ -52,6 +61,8 @@ pub struct GetValueQueryTimeout {
 /// Request to store a value associated with a key.
 #[derive(Clone, Serialize)]
 pub struct PutValueRequest {
+    /// The ID of the query that originated the request.
+    pub query_id: QueryId,
     /// The key of the value to store.
     pub key: Key,
     /// The value to store.
@@ -105,3 +116,204 @@ pub struct BootstrapTimer {}
 pub struct RepublishTimer {
     pub key: Key,
 }
+
+/// Timer for removing expired records from the DHT storage.
+#[derive(Clone, Serialize)]
+pub struct ExpirationSweepTimer {}
+
+/// Timer for collecting the garbage in the file storage.
+#[derive(Clone, Serialize)]
+pub struct GarbageCollectionTimer {}
+
+/// Represents the kind of a message sent between peers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum MessageKind {
+    FindNodeRequest,
+    FindNodeResponse,
+    GetValueRequest,
+    GetValueResponse,
+    PutValueRequest,
+    RetrieveDataRequest,
+    RetrieveDataResponse,
+    PingRequest,
+    PingResponse,
+}
+
+impl MessageKind {
+    /// Returns `true` if the message is a request, i.e. it is sent by the peer
+    /// that started the query.
+    pub fn is_request(&self) -> bool {
+        matches!(
+            self,
+            Self::FindNodeRequest
+                | Self::GetValueRequest
+                | Self::PutValueRequest
+                | Self::RetrieveDataRequest
+                | Self::PingRequest
+        )
+    }
+}
+
+/// A message sent between peers over the network.
+pub trait Message: EventData {
+    /// The kind of the message.
+    const KIND: MessageKind;
+
+    /// Returns the ID of the query that originated the message, if any.
+    fn query_id(&self) -> Option<QueryId>;
+
+    /// Returns the approximate size of the message on the wire in bytes.
+    fn size(&self) -> usize;
+}
+
+const QUERY_ID_SIZE: usize = std::mem::size_of::<QueryId>();
+const KEY_SIZE: usize = std::mem::size_of::<Key>();
+
+impl Message for FindNodeRequest {
+    const KIND: MessageKind = MessageKind::FindNodeRequest;
+
+    fn query_id(&self) -> Option<QueryId> {
+        Some(self.query_id)
+    }
+
+    fn size(&self) -> usize {
+        QUERY_ID_SIZE + KEY_SIZE
+    }
+}
+
+impl Message for FindNodeResponse {
+    const KIND: MessageKind = MessageKind::FindNodeResponse;
+
+    fn query_id(&self) -> Option<QueryId> {
+        Some(self.query_id)
+    }
+
+    fn size(&self) -> usize {
+        QUERY_ID_SIZE + self.closest_peers.len() * std::mem::size_of::<PeerId>()
+    }
+}
+
+impl Message for GetValueRequest {
+    const KIND: MessageKind = MessageKind::GetValueRequest;
+
+    fn query_id(&self) -> Option<QueryId> {
+        Some(self.query_id)
+    }
+
+    fn size(&self) -> usize {
+        QUERY_ID_SIZE + KEY_SIZE
+    }
+}
+
+impl Message for GetValueResponse {
+    const KIND: MessageKind = MessageKind::GetValueResponse;
+
+    fn query_id(&self) -> Option<QueryId> {
+        Some(self.query_id)
+    }
+
+    fn size(&self) -> usize {
+        QUERY_ID_SIZE + 1 + self.record.as_ref().map_or(0, Record::size)
+    }
+}
+
+impl Message for PutValueRequest {
+    const KIND: MessageKind = MessageKind::PutValueRequest;
+
+    fn query_id(&self) -> Option<QueryId> {
+        Some(self.query_id)
+    }
+
+    fn size(&self) -> usize {
+        QUERY_ID_SIZE + KEY_SIZE + self.record.size()
+    }
+}
+
+impl Message for RetrieveDataRequest {
+    const KIND: MessageKind = MessageKind::RetrieveDataRequest;
+
+    fn query_id(&self) -> Option<QueryId> {
+        Some(self.query_id)
+    }
+
+    fn size(&self) -> usize {
+        QUERY_ID_SIZE + KEY_SIZE
+    }
+}
+
+impl Message for RetrieveDataResponse {
+    const KIND: MessageKind = MessageKind::RetrieveDataResponse;
+
+    fn query_id(&self) -> Option<QueryId> {
+        Some(self.query_id)
+    }
+
+    fn size(&self) -> usize {
+        QUERY_ID_SIZE + 1 + self.data.as_ref().map_or(0, String::len)
+    }
+}
+
+impl Message for PingRequest {
+    const KIND: MessageKind = MessageKind::PingRequest;
+
+    fn query_id(&self) -> Option<QueryId> {
+        None
+    }
+
+    fn size(&self) -> usize {
+        0
+    }
+}
+
+impl Message for PingResponse {
+    const KIND: MessageKind = MessageKind::PingResponse;
+
+    fn query_id(&self) -> Option<QueryId> {
+        None
+    }
+
+    fn size(&self) -> usize {
+        0
+    }
+}
+
+/// Describes a message sent between peers.
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageInfo {
+    pub kind: MessageKind,
+    pub query_id: Option<QueryId>,
+    pub size: usize,
+}
+
+impl MessageInfo {
+    /// Creates a description of the given message.
+    pub fn new<M: Message>(message: &M) -> Self {
+        Self {
+            kind: M::KIND,
+            query_id: message.query_id(),
+            size: message.size(),
+        }
+    }
+
+    /// Describes the event data if it is a message sent between peers.
+    ///
+    /// # Returns
+    ///
+    /// The description of the message, or `None` if the data is not a message,
+    /// e.g. it is a timer.
+    pub fn from_event_data(data: &dyn EventData) -> Option<Self> {
+        fn describe<M: Message>(data: &dyn EventData) -> Option<MessageInfo> {
+            data.downcast_ref::<M>().map(MessageInfo::new)
+        }
+
+        describe::<FindNodeRequest>(data)
+            .or_else(|| describe::<FindNodeResponse>(data))
+            .or_else(|| describe::<GetValueRequest>(data))
+            .or_else(|| describe::<GetValueResponse>(data))
+            .or_else(|| describe::<PutValueRequest>(data))
+            .or_else(|| describe::<RetrieveDataRequest>(data))
+            .or_else(|| describe::<RetrieveDataResponse>(data))
+            .or_else(|| describe::<PingRequest>(data))
+            .or_else(|| describe::<PingResponse>(data))
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{
+    query::{QueryFinished, QueryId, QueryKind},
+    PeerId,
+};
+use dslab_core::{cast, Event, EventHandler, Id, Simulation};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+type Callback = dyn FnMut(&QueryFinished);
+type OneShotCallback = dyn FnOnce(&QueryFinished);
+type OneShotCallbacks = HashMap<(PeerId, QueryId, QueryKind), Vec<Box<OneShotCallback>>>;
+
+/// Receives the notifications about the finished queries of the peers
+/// and passes them to the registered callbacks.
+///
+/// The callbacks are called from the event loop of the simulation, so they may
+/// start new queries on the peers and register other callbacks. A callback registered
+/// while a notification is dispatched is called only for the later notifications.
+pub struct QueryMonitor {
+    id: Id,
+    callbacks: RefCell<Vec<Box<Callback>>>,
+    one_shot_callbacks: RefCell<OneShotCallbacks>,
+}
+
+/// Passes the events of the simulation to the monitor, which stays available
+/// for the registration of callbacks while they are dispatched.
+struct QueryMonitorHandler {
+    monitor: Rc<QueryMonitor>,
+}
+
+impl QueryMonitor {
+    /// Registers a new `QueryMonitor` within the given simulation.
+    /// The peers must subscribe the monitor with `Peer::subscribe` to be observed.
+    pub fn register(sim: &mut Simulation) -> Rc<Self> {
+        let name = "query_monitor";
+        let monitor = Rc::new(Self {
+            id: sim.create_context(name).id(),
+            callbacks: RefCell::new(vec![]),
+            one_shot_callbacks: RefCell::new(HashMap::new()),
+        });
+        let handler = QueryMonitorHandler {
+            monitor: monitor.clone(),
+        };
+        sim.add_handler(name, Rc::new(RefCell::new(handler)));
+        monitor
+    }
+
+    /// Returns the ID of the monitor to subscribe it to the peers.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Registers a callback that is called for every finished query.
+    pub fn on_query_finished(&self, callback: impl FnMut(&QueryFinished) + 'static) {
+        self.callbacks.borrow_mut().push(Box::new(callback));
+    }
+
+    /// Registers a callback that is called once the given query of the peer finishes.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_id` - The ID of the peer that started the query.
+    /// * `query_id` - The ID of the query.
+    /// * `kind` - The kind of the query. A `RetrieveData` query shares its ID
+    ///   with the `GetValueQuery` it starts.
+    /// * `callback` - The callback to call.
+    pub fn on_query(
+        &self,
+        peer_id: PeerId,
+        query_id: QueryId,
+        kind: QueryKind,
+        callback: impl FnOnce(&QueryFinished) + 'static,
+    ) {
+        self.one_shot_callbacks
+            .borrow_mut()
+            .entry((peer_id, query_id, kind))
+            .or_default()
+            .push(Box::new(callback));
+    }
+
+    fn on_query_finished_event(&self, event: QueryFinished) {
+        let one_shot_callbacks = self.one_shot_callbacks.borrow_mut().remove(&(
+            event.peer_id,
+            event.query_id,
+            event.kind,
+        ));
+        for callback in one_shot_callbacks.into_iter().flatten() {
+            callback(&event);
+        }
+        // the callbacks registered meanwhile are queued after the existing ones
+        let mut callbacks = std::mem::take(&mut *self.callbacks.borrow_mut());
+        for callback in callbacks.iter_mut() {
+            callback(&event);
+        }
+        let mut registered = self.callbacks.borrow_mut();
+        callbacks.append(&mut registered);
+        *registered = callbacks;
+    }
+}
+
+impl EventHandler for QueryMonitorHandler {
+    fn on(&mut self, event: Event) {
+        cast!(match event.data {
+            QueryFinished {
+                peer_id,
+                query_id,
+                kind,
+                outcome,
+                started_at,
+                finished_at,
+                result,
+            } => {
+                self.monitor.on_query_finished_event(QueryFinished {
+                    peer_id,
+                    query_id,
+                    kind,
+                    outcome,
+                    started_at,
+                    finished_at,
+                    result,
+                });
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        environment::test_config,
+        network::NetworkAgent,
+        peer::test_peers,
+        query::{QueryOutcome, QueryResult},
+        Key,
+    };
+    use std::cell::Cell;
+
+    fn test_monitor(
+        sim: &mut Simulation,
+    ) -> (Vec<Rc<RefCell<crate::peer::Peer>>>, Rc<QueryMonitor>) {
+        let network = NetworkAgent::from_function(|_, _, _| Some(0.1));
+        let peers = test_peers(sim, test_config(16), network);
+        let monitor = QueryMonitor::register(sim);
+        for peer in peers.iter() {
+            peer.borrow_mut().subscribe(monitor.id());
+        }
+        (peers, monitor)
+    }
+
+    #[test]
+    fn test_on_query() {
+        let mut sim = Simulation::new(0);
+        let (peers, monitor) = test_monitor(&mut sim);
+        let query_id = peers[0].borrow_mut().publish_data("data".to_string());
+        let finished = Rc::new(RefCell::new(None));
+        monitor.on_query(0, query_id, QueryKind::PutValue, {
+            let finished = finished.clone();
+            move |event| *finished.borrow_mut() = Some(event.clone())
+        });
+        sim.step_until_time(10.);
+
+        let finished = finished.borrow();
+        let event = finished.as_ref().unwrap();
+        assert_eq!(event.outcome, QueryOutcome::Completed);
+        assert!(event.latency() > 0.);
+        let QueryResult::PutValue { key, peers } = &event.result else {
+            panic!("unexpected result {:?}", event.result);
+        };
+        assert_eq!(key, &Key::from_sha256(b"data"));
+        assert!(!peers.is_empty());
+    }
+
+    #[test]
+    fn test_chained_registration() {
+        let mut sim = Simulation::new(0);
+        let (peers, monitor) = test_monitor(&mut sim);
+        let query_id = peers[0].borrow_mut().publish_data("data".to_string());
+        let retrieved = Rc::new(RefCell::new(None));
+        monitor.on_query(0, query_id, QueryKind::PutValue, {
+            let (peers, monitor, retrieved) = (peers.clone(), monitor.clone(), retrieved.clone());
+            move |_| {
+                let key = Key::from_sha256(b"data");
+                let query_id = peers[5].borrow_mut().retrieve_data(key);
+                monitor.on_query(5, query_id, QueryKind::RetrieveData, move |event| {
+                    *retrieved.borrow_mut() = Some(event.result.clone());
+                });
+            }
+        });
+        sim.step_until_time(10.);
+
+        let retrieved = retrieved.borrow();
+        let Some(QueryResult::RetrieveData { data }) = retrieved.as_ref() else {
+            panic!("unexpected result {:?}", retrieved);
+        };
+        assert_eq!(data, "data");
+    }
+
+    #[test]
+    fn test_registration_during_dispatch() {
+        let mut sim = Simulation::new(0);
+        let (peers, monitor) = test_monitor(&mut sim);
+        let first = Rc::new(Cell::new(0));
+        let second = Rc::new(Cell::new(0));
+        monitor.on_query_finished({
+            let (monitor, first, second) = (monitor.clone(), first.clone(), second.clone());
+            move |_| {
+                first.set(first.get() + 1);
+                if first.get() == 1 {
+                    let second = second.clone();
+                    monitor.on_query_finished(move |_| second.set(second.get() + 1));
+                }
+            }
+        });
+        let key = Key::from_sha256(b"key");
+        peers[0]
+            .borrow_mut()
+            .find_node(&key, crate::query::QueryTrigger::Manual);
+        sim.step_until_time(10.);
+        assert_eq!((first.get(), second.get()), (1, 0));
+
+        peers[1]
+            .borrow_mut()
+            .find_node(&key, crate::query::QueryTrigger::Manual);
+        sim.step_until_time(20.);
+        assert_eq!((first.get(), second.get()), (2, 1));
+    }
+}
@@ -1,7 +1,7 @@
 use dslab_core::SimulationContext;
 
 use crate::PeerId;
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use super::{DelayDistribution, Topology};
 
@@ -12,6 +12,7 @@ type Agent = dyn FnMut(&SimulationContext, PeerId, PeerId) -> Option<f64>;
 #[derive(Clone)]
 pub struct NetworkAgent {
     filter: Rc<RefCell<Agent>>,
+    partition: Rc<RefCell<HashMap<PeerId, usize>>>,
 }
 
 impl NetworkAgent {
@@ -30,6 +31,7 @@ impl NetworkAgent {
     ) -> Self {
         Self {
             filter: Rc::new(RefCell::new(filter)),
+            partition: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 
@@ -52,6 +54,31 @@ impl NetworkAgent {
         Self::from_function(filter)
     }
 
+    /// Splits the network into isolated groups of peers.
+    /// The messages between peers of different groups are filtered out.
+    /// The peers not listed in any group form one more group.
+    ///
+    /// The partition is shared by all the clones of the agent
+    /// and replaces the previous one.
+    ///
+    /// # Arguments
+    ///
+    /// * `groups` - The IDs of the peers of every group.
+    pub fn set_partition(&self, groups: &[Vec<PeerId>]) {
+        let mut partition = self.partition.borrow_mut();
+        partition.clear();
+        for (i, group) in groups.iter().enumerate() {
+            for &peer_id in group {
+                partition.insert(peer_id, i);
+            }
+        }
+    }
+
+    /// Removes the partition of the network, see `set_partition`.
+    pub fn heal_partition(&self) {
+        self.partition.borrow_mut().clear();
+    }
+
     /// Samples the delay of a message between two peers.
     ///
     /// If the function returns `None`, it means the message is filtered out
@@ -67,6 +94,12 @@ impl NetworkAgent {
         if src == dst {
             return Some(0.);
         }
+        {
+            let partition = self.partition.borrow();
+            if !partition.is_empty() && partition.get(&src) != partition.get(&dst) {
+                return None;
+            }
+        }
         self.filter.borrow_mut()(ctx, src, dst)
     }
 }
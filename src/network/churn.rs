@@ -0,0 +1,234 @@
+use super::{peer_time_offsets, RateFunction};
+use crate::{peer::Peer, profile::Uptime, Environment};
+use dslab_core::{cast, Event, EventHandler, Simulation, SimulationContext};
+use rand_distr::Exp;
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, rc::Rc};
+
+/// Represents the configuration of the churn model, the `[churn]` table of the configuration file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChurnConfig {
+    /// The interval between the updates of the states of the peers.
+    pub interval: f64,
+    /// The fraction of the peers whose states are resampled on every update.
+    /// The mean session duration is about `interval / turnover`.
+    #[serde(default = "default_turnover")]
+    pub turnover: f64,
+    /// The probability of a peer to be online, evaluated at the local time of the peer.
+    /// The values above 1 are treated as 1.
+    pub online: RateFunction,
+}
+
+fn default_turnover() -> f64 {
+    1.
+}
+
+/// Takes the peers online and offline, so that the fraction of the online peers
+/// follows the probability given in the configuration, e.g. a daily cycle.
+pub struct ChurnModel {
+    ctx: SimulationContext,
+    interval: f64,
+    turnover: f64,
+    online: RateFunction,
+    peers: Vec<Rc<RefCell<Peer>>>,
+    time_offsets: Vec<f64>, // the differences between the local times of the peers and the simulation time
+}
+
+#[derive(Clone, Serialize)]
+pub struct ChurnTimer {}
+
+impl ChurnModel {
+    /// Registers the churn model in the simulation.
+    /// The initial states of all the peers are sampled immediately.
+    pub fn register(
+        sim: &mut Simulation,
+        env: &Environment,
+        peers: Vec<Rc<RefCell<Peer>>>,
+    ) -> Rc<RefCell<Self>> {
+        let name = "churn_model";
+        let ctx = sim.create_context(name);
+        let config = env.config();
+        let churn = config.churn.clone().unwrap();
+        let model = Self {
+            ctx,
+            interval: churn.interval,
+            turnover: churn.turnover,
+            online: churn.online,
+            time_offsets: peer_time_offsets(&config.regions, peers.len()),
+            peers,
+        };
+        model.update(1.);
+        model.ctx.emit_self(ChurnTimer {}, model.interval);
+        let model = Rc::new(RefCell::new(model));
+        sim.add_handler(name, model.clone());
+        model
+    }
+
+    /// Resamples the states of the given fraction of the peers.
+    fn update(&self, turnover: f64) {
+        let now = self.ctx.time();
+        for (peer, offset) in self.peers.iter().zip(self.time_offsets.iter()) {
+            if turnover < 1. && self.ctx.rand() >= turnover {
+                continue;
+            }
+            let probability = self.online.value(now + offset);
+            peer.borrow_mut().set_online(self.ctx.rand() < probability);
+        }
+    }
+}
+
+impl EventHandler for ChurnModel {
+    fn on(&mut self, event: Event) {
+        cast!(match event.data {
+            ChurnTimer {} => {
+                self.update(self.turnover);
+                self.ctx.emit_self(ChurnTimer {}, self.interval);
+            }
+        })
+    }
+}
+
+/// Alternates the online sessions and the offline periods of the peers
+/// whose profiles specify the uptime, see `Uptime`.
+pub struct UptimeModel {
+    ctx: SimulationContext,
+    peers: Vec<Rc<RefCell<Peer>>>,
+    uptimes: Vec<Option<Uptime>>, // the uptime of every peer, if any
+}
+
+#[derive(Clone, Serialize)]
+pub struct SessionTimer {
+    peer: usize,
+    online: bool, // the state the peer switches to
+}
+
+impl UptimeModel {
+    /// Registers the uptime model in the simulation.
+    /// The initial states of the peers are sampled from the stationary distribution.
+    pub fn register(
+        sim: &mut Simulation,
+        env: &Environment,
+        peers: Vec<Rc<RefCell<Peer>>>,
+    ) -> Rc<RefCell<Self>> {
+        let name = "uptime_model";
+        let ctx = sim.create_context(name);
+        let uptimes = peers
+            .iter()
+            .map(|peer| {
+                env.peer_profile(peer.borrow().id())
+                    .and_then(|profile| profile.uptime)
+            })
+            .collect::<Vec<_>>();
+        let model = Self {
+            ctx,
+            peers,
+            uptimes,
+        };
+        for (peer, uptime) in model.uptimes.iter().enumerate() {
+            if let Some(uptime) = uptime {
+                let online = model.ctx.rand() < uptime.availability();
+                model.peers[peer].borrow_mut().set_online(online);
+                model.schedule(peer, uptime, online);
+            }
+        }
+        let model = Rc::new(RefCell::new(model));
+        sim.add_handler(name, model.clone());
+        model
+    }
+
+    /// Schedules the end of the current session or offline period of the peer.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer` - The index of the peer.
+    /// * `uptime` - The uptime of the peer.
+    /// * `online` - Whether the peer has just started an online session.
+    fn schedule(&self, peer: usize, uptime: &Uptime, online: bool) {
+        let mean = if online {
+            uptime.session
+        } else {
+            uptime.downtime
+        };
+        let duration = self
+            .ctx
+            .sample_from_distribution(&Exp::new(1. / mean).unwrap());
+        self.ctx.emit_self(
+            SessionTimer {
+                peer,
+                online: !online,
+            },
+            duration,
+        );
+    }
+}
+
+impl EventHandler for UptimeModel {
+    fn on(&mut self, event: Event) {
+        cast!(match event.data {
+            SessionTimer { peer, online } => {
+                self.peers[peer].borrow_mut().set_online(online);
+                if let Some(uptime) = self.uptimes[peer].as_ref() {
+                    self.schedule(peer, uptime, online);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{network::NetworkAgent, peer::test_peers, SimulationConfig};
+
+    #[test]
+    fn test_online_fraction() {
+        let config = || {
+            let overrides = [
+                ("log_level_filter", "'off'"),
+                ("num_peers", "400"),
+                ("churn.interval", "100.0"),
+                ("churn.turnover", "0.5"),
+                (
+                    "churn.online",
+                    "{ kind = 'sinusoidal', mean = 0.5, amplitude = 0.8, period = 2000.0 }",
+                ),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()));
+            SimulationConfig::from_toml_str_with_overrides(
+                include_str!("../../config.toml"),
+                &overrides,
+            )
+            .unwrap()
+        };
+        let env = Environment::new(config());
+        let mut sim = Simulation::new(0);
+        let network = NetworkAgent::from_function(|_, _, _| Some(0.1));
+        let peers = test_peers(&mut sim, config(), network);
+        let model = ChurnModel::register(&mut sim, &env, peers.clone());
+        let online = |peers: &[Rc<RefCell<Peer>>]| {
+            peers
+                .iter()
+                .filter(|peer| peer.borrow().is_online())
+                .count() as f64
+                / peers.len() as f64
+        };
+
+        assert!((online(&peers) - 0.5).abs() < 0.1);
+        // a half of the peers is resampled on every update, so the expected fraction
+        // moves halfway from the previous one to the current probability
+        let (mut min, mut max, mut max_error) = (1f64, 0f64, 0f64);
+        let mut fraction = online(&peers);
+        for i in 1..=40 {
+            let time = 100. * i as f64;
+            sim.step_until_time(time + 1.);
+            let expected = (fraction + model.borrow().online.value(time)) / 2.;
+            fraction = online(&peers);
+            max_error = max_error.max((fraction - expected).abs());
+            (min, max) = (min.min(fraction), max.max(fraction));
+        }
+        assert!(max_error < 0.08, "{}", max_error);
+        // the probability is between 0.1 and 0.9
+        assert!(min < 0.25 && max > 0.75, "{} {}", min, max);
+    }
+}
@@ -1,13 +1,23 @@
 use rand::distributions::{Distribution, Uniform};
 use rand_distr::Normal;
+use serde::{Deserialize, Serialize};
 
 /// Represents the distribution of delay values.
-#[derive(Clone, Debug)]
+///
+/// In the configuration file, the distribution is a table with the `kind` key,
+/// e.g. `{ kind = "uniform", min = 0.01, max = 0.1 }`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
 pub enum DelayDistribution {
     /// Represents a constant delay value.
-    Constant(f64),
+    Constant { value: f64 },
     /// Represents a uniform distribution of delay values.
-    Uniform { left: f64, right: f64 },
+    Uniform {
+        #[serde(rename = "min")]
+        left: f64,
+        #[serde(rename = "max")]
+        right: f64,
+    },
     /// Represents a normal distribution of delay values.
     /// If sampled value is negative, it is replaced with 0.
     PositiveNormal { mean: f64, std_dev: f64 },
@@ -16,7 +26,7 @@ pub enum DelayDistribution {
 impl Distribution<f64> for DelayDistribution {
     fn sample<R: rand::Rng + ?Sized>(&self, _rng: &mut R) -> f64 {
         match self {
-            Self::Constant(delay) => *delay,
+            Self::Constant { value } => *value,
             Self::Uniform { left, right } => {
                 let distr = Uniform::new_inclusive(*left, *right);
                 distr.sample(_rng)
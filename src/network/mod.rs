@@ -1,9 +1,19 @@
 mod agent;
+mod churn;
 mod delay_distribution;
+mod popularity;
+mod rate;
 mod topology;
 mod user_load;
+mod workload_trace;
 
 pub use agent::NetworkAgent;
+pub use churn::{ChurnConfig, ChurnModel, UptimeModel};
 pub use delay_distribution::DelayDistribution;
+pub use popularity::{Popularity, Rank};
+pub use rate::{peer_time_offsets, RateFunction, RateStep, Region};
 pub use topology::Topology;
-pub use user_load::UserLoadGenerator;
+pub use user_load::{Arrivals, FlashCrowd, UserLoadGenerator};
+pub use workload_trace::{
+    PeerMapping, TraceReplayer, WorkloadOp, WorkloadRecord, WorkloadTrace, WorkloadTraceConfig,
+};
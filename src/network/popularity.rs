@@ -0,0 +1,73 @@
+use rand::{distributions::Distribution, Rng};
+use rand_distr::Zipf;
+use serde::{Deserialize, Serialize};
+
+/// Represents the popularity distribution of the retrieved blocks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Popularity {
+    /// Every block is equally likely to be retrieved.
+    #[default]
+    Uniform,
+    /// The blocks are retrieved in turn.
+    Sequential,
+    /// The n-th block is retrieved with the probability proportional to `1 / n^exponent`.
+    /// The blocks are ranked in the order they were published or listed,
+    /// except for the blocks created by the user load, which are ranked first.
+    Zipf { exponent: f64 },
+}
+
+impl Popularity {
+    /// Returns the distribution of the index of the next retrieved block.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - The number of the blocks, must be positive.
+    /// * `seq` - The number of the retrieval, used by `Popularity::Sequential`.
+    pub fn rank(&self, count: usize, seq: usize) -> Rank {
+        Rank {
+            popularity: *self,
+            count,
+            seq,
+        }
+    }
+}
+
+/// Represents the distribution of the index of the next retrieved block, see `Popularity::rank`.
+pub struct Rank {
+    popularity: Popularity,
+    count: usize,
+    seq: usize,
+}
+
+impl Distribution<usize> for Rank {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        match self.popularity {
+            Popularity::Uniform => rng.gen_range(0..self.count),
+            Popularity::Sequential => self.seq % self.count,
+            Popularity::Zipf { exponent } => {
+                let zipf = Zipf::new(self.count as u64, exponent).unwrap();
+                zipf.sample(rng) as usize - 1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_rank() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut counts = [0; 10];
+        let zipf = Popularity::Zipf { exponent: 1. };
+        for _ in 0..10_000 {
+            counts[zipf.rank(10, 0).sample(&mut rng)] += 1;
+        }
+        assert!(counts.windows(2).take(3).all(|w| w[0] > w[1]));
+        assert!(counts[0] > 5 * counts[9]);
+        assert_eq!(Popularity::Sequential.rank(10, 13).sample(&mut rng), 3);
+    }
+}
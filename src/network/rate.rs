@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Represents a non-negative function of time that modulates a rate or a probability,
+/// such as the arrival rate of the user load or the probability of a peer to be online.
+///
+/// In the configuration file, the function is a table with the `kind` key,
+/// e.g. `{ kind = "sinusoidal", period = 86400.0, amplitude = 0.5 }`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RateFunction {
+    /// The value does not change over time.
+    Constant { value: f64 },
+    /// A cycle, e.g. a daily one: `mean * (1 + amplitude * sin(2 * pi * (t - phase) / period))`.
+    Sinusoidal {
+        #[serde(default = "default_mean")]
+        mean: f64,
+        /// Must be in `[0, 1]`, so the value is never negative.
+        amplitude: f64,
+        #[serde(default = "default_period")]
+        period: f64,
+        #[serde(default)]
+        phase: f64,
+    },
+    /// Step changes: the value of the last step started by the time,
+    /// or `initial` before the first step.
+    Steps {
+        #[serde(default = "default_mean")]
+        initial: f64,
+        steps: Vec<RateStep>,
+    },
+    /// A spike, e.g. a flash crowd: `value` during `[start, start + duration)`, 1 otherwise.
+    Spike {
+        start: f64,
+        duration: f64,
+        value: f64,
+    },
+    /// The product of the functions, e.g. a daily cycle with a spike.
+    Product { factors: Vec<RateFunction> },
+}
+
+/// Represents a step change of `RateFunction::Steps`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateStep {
+    pub at: f64,
+    pub value: f64,
+}
+
+fn default_mean() -> f64 {
+    1.
+}
+
+fn default_period() -> f64 {
+    86400.
+}
+
+impl RateFunction {
+    /// Returns the value of the function at the given time.
+    pub fn value(&self, time: f64) -> f64 {
+        match self {
+            Self::Constant { value } => *value,
+            Self::Sinusoidal {
+                mean,
+                amplitude,
+                period,
+                phase,
+            } => mean * (1. + amplitude * (2. * PI * (time - phase) / period).sin()),
+            Self::Steps { initial, steps } => steps
+                .iter()
+                .take_while(|step| step.at <= time)
+                .last()
+                .map_or(*initial, |step| step.value),
+            Self::Spike {
+                start,
+                duration,
+                value,
+            } => {
+                if *start <= time && time < start + duration {
+                    *value
+                } else {
+                    1.
+                }
+            }
+            Self::Product { factors } => factors.iter().map(|f| f.value(time)).product(),
+        }
+    }
+
+    /// Returns the upper bound of the values of the function.
+    pub fn max_value(&self) -> f64 {
+        match self {
+            Self::Constant { value } => *value,
+            Self::Sinusoidal {
+                mean, amplitude, ..
+            } => mean * (1. + amplitude),
+            Self::Steps { initial, steps } => {
+                steps.iter().map(|step| step.value).fold(*initial, f64::max)
+            }
+            Self::Spike { value, .. } => value.max(1.),
+            Self::Product { factors } => factors.iter().map(|f| f.max_value()).product(),
+        }
+    }
+
+    /// Checks the parameters of the function.
+    ///
+    /// # Returns
+    ///
+    /// The path to the invalid parameter relative to the function and the reason,
+    /// for every problem found.
+    pub fn validate(&self) -> Vec<(String, &'static str)> {
+        let mut errors = vec![];
+        let mut check = |condition: bool, field: &str, reason| {
+            if !condition {
+                errors.push((field.to_string(), reason));
+            }
+        };
+        match self {
+            Self::Constant { value } => check(*value >= 0., "value", "must be non-negative"),
+            Self::Sinusoidal {
+                mean,
+                amplitude,
+                period,
+                ..
+            } => {
+                check(*mean >= 0., "mean", "must be non-negative");
+                check(
+                    (0. ..=1.).contains(amplitude),
+                    "amplitude",
+                    "must be between 0 and 1",
+                );
+                check(*period > 0., "period", "must be positive");
+            }
+            Self::Steps { initial, steps } => {
+                check(*initial >= 0., "initial", "must be non-negative");
+                for (i, step) in steps.iter().enumerate() {
+                    check(
+                        step.value >= 0.,
+                        &format!("steps.{}.value", i),
+                        "must be non-negative",
+                    );
+                }
+                check(
+                    steps.windows(2).all(|w| w[0].at <= w[1].at),
+                    "steps",
+                    "must be ordered by time",
+                );
+            }
+            Self::Spike {
+                duration, value, ..
+            } => {
+                check(*duration > 0., "duration", "must be positive");
+                check(*value >= 0., "value", "must be non-negative");
+            }
+            Self::Product { factors } => {
+                for (i, factor) in factors.iter().enumerate() {
+                    for (field, reason) in factor.validate() {
+                        errors.push((format!("factors.{}.{}", i, field), reason));
+                    }
+                }
+            }
+        }
+        errors
+    }
+}
+
+/// Represents a group of peers sharing the local time, e.g. the peers of a time zone.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+    pub name: String,
+    /// The fraction of the peers in the region.
+    pub fraction: f64,
+    /// The difference between the local time of the region and the simulation time.
+    /// The rate functions of the peers of the region are evaluated at the local time.
+    #[serde(default)]
+    pub time_offset: f64,
+}
+
+/// Returns the time offsets of the peers.
+/// The peers are assigned to the regions in the order of their indices,
+/// according to the fractions of the regions.
+/// The offset is 0 for all the peers if there are no regions.
+///
+/// # Arguments
+///
+/// * `regions` - The regions, the fractions must sum to 1.
+/// * `num_peers` - The number of the peers.
+pub fn peer_time_offsets(regions: &[Region], num_peers: usize) -> Vec<f64> {
+    let mut offsets = Vec::with_capacity(num_peers);
+    let mut bound = 0.;
+    for (i, region) in regions.iter().enumerate() {
+        bound += region.fraction;
+        let end = if i + 1 == regions.len() {
+            num_peers
+        } else {
+            ((bound * num_peers as f64).round() as usize).min(num_peers)
+        };
+        while offsets.len() < end {
+            offsets.push(region.time_offset);
+        }
+    }
+    offsets.resize(num_peers, 0.);
+    offsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value() {
+        let daily = RateFunction::Sinusoidal {
+            mean: 2.,
+            amplitude: 0.5,
+            period: 86400.,
+            phase: 0.,
+        };
+        assert!((daily.value(21600.) - 3.).abs() < 1e-9);
+        assert!((daily.value(64800.) - 1.).abs() < 1e-9);
+        assert_eq!(daily.max_value(), 3.);
+
+        let f = RateFunction::Product {
+            factors: vec![
+                RateFunction::Steps {
+                    initial: 1.,
+                    steps: vec![
+                        RateStep { at: 10., value: 2. },
+                        RateStep {
+                            at: 20.,
+                            value: 0.5,
+                        },
+                    ],
+                },
+                RateFunction::Spike {
+                    start: 15.,
+                    duration: 10.,
+                    value: 10.,
+                },
+            ],
+        };
+        assert_eq!(f.value(5.), 1.);
+        assert_eq!(f.value(16.), 20.);
+        assert_eq!(f.value(21.), 5.);
+        assert_eq!(f.value(30.), 0.5);
+        assert_eq!(f.max_value(), 20.);
+    }
+
+    #[test]
+    fn test_peer_time_offsets() {
+        let region = |fraction, time_offset| Region {
+            name: String::new(),
+            fraction,
+            time_offset,
+        };
+        let offsets = peer_time_offsets(&[region(0.3, 1.), region(0.7, 2.)], 5);
+        assert_eq!(offsets, vec![1., 1., 2., 2., 2.]);
+        assert_eq!(peer_time_offsets(&[], 2), vec![0., 0.]);
+    }
+}
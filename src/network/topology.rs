@@ -1,7 +1,12 @@
 use crate::PeerId;
+use serde::{Deserialize, Serialize};
 
 /// Represents different network topologies.
-#[derive(Clone, Debug)]
+///
+/// In the configuration file, the topology is a table with the `kind` key,
+/// e.g. `{ kind = "star", center_id = 0 }`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
 pub enum Topology {
     /// A full network topology where all peers are connected to each other.
     Full,
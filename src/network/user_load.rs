@@ -1,57 +1,434 @@
-use crate::{peer::Peer, Key, CONFIG};
-use dslab_core::{cast, Event, EventHandler, Simulation, SimulationContext};
-use serde::Serialize;
-use std::{cell::RefCell, rc::Rc};
+use super::{peer_time_offsets, Popularity, RateFunction};
+use crate::{peer::Peer, Environment, Key};
+use dslab_core::{cast, Event, EventHandler, EventId, Simulation, SimulationContext};
+use rand_distr::{Exp, WeightedIndex};
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+/// Represents the process of the arrivals of the user load events.
+/// The mean interval between the events is `events_interval`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Arrivals {
+    /// The events happen exactly every `events_interval` seconds.
+    #[default]
+    Fixed,
+    /// The intervals between the events are exponentially distributed.
+    Poisson,
+    /// The events arrive as a Poisson process during the ON periods and do not happen
+    /// during the OFF periods. The durations of the periods are exponentially distributed
+    /// with the given means.
+    Bursty { on_duration: f64, off_duration: f64 },
+}
+
+/// Represents a flash crowd: a burst of retrievals of a single block of the pool
+/// on top of the regular load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlashCrowd {
+    /// The simulation time the flash crowd starts at.
+    pub start: f64,
+    pub duration: f64,
+    /// The mean interval between the retrievals, which arrive as a Poisson process.
+    pub events_interval: f64,
+    /// The index of the retrieved block in the pool.
+    pub block: usize,
+}
 
 pub struct UserLoadGenerator {
     ctx: SimulationContext,
+    events_interval: f64,
+    arrivals: Arrivals,
+    popularity: Popularity,
+    rate: Option<RateFunction>,
+    flash_crowds: Vec<FlashCrowd>,
+    publish_ratio: f64,
+    new_content_ratio: f64,
+    block_size: usize,
     peers: Vec<Rc<RefCell<Peer>>>, // peers to publish and retrieve data
-    blocks: Vec<String>,           // data to publish and retrieve
+    blocks: Vec<String>,           // data to publish and retrieve, in creation order
     keys: Vec<Key>,                // keys of the data
+    ranking: VecDeque<usize>,      // indices of the blocks from the most to the least popular
+    choices: usize,                // number of the blocks chosen so far
+    burst_end: f64,                // end of the current ON period of the bursty arrivals
+    time_offsets: Vec<f64>,        // local time offsets of the peers
+    peer_weights: Option<WeightedIndex<f64>>, // load shares of the peers, if there are profiles
+    timer: Option<EventId>,        // the pending timer, if the generation is active
 }
 
 #[derive(Clone, Serialize)]
 pub struct UserLoadTimer {}
 
+#[derive(Clone, Serialize)]
+pub struct FlashCrowdTimer {
+    crowd: usize,
+}
+
 impl UserLoadGenerator {
-    pub fn register(sim: &mut Simulation, peers: Vec<Rc<RefCell<Peer>>>) -> Rc<RefCell<Self>> {
+    pub fn register(
+        sim: &mut Simulation,
+        env: &Environment,
+        peers: Vec<Rc<RefCell<Peer>>>,
+    ) -> Rc<RefCell<Self>> {
         let name = "user_load_generator";
         let ctx = sim.create_context(name);
-        ctx.emit_self(UserLoadTimer {}, CONFIG.user_load_events_interval.unwrap());
-        let blocks = (0..CONFIG.user_load_blocks_pool_size.unwrap())
-            .map(|_| ctx.random_string(CONFIG.user_load_block_size.unwrap()))
+        let config = env.config();
+        let block_size = config.user_load_block_size.unwrap();
+        let blocks = (0..config.user_load_blocks_pool_size.unwrap())
+            .map(|_| ctx.random_string(block_size))
             .collect::<Vec<_>>();
         let keys = blocks
             .iter()
             .map(|data| Key::from_sha256(data.as_bytes()))
             .collect::<Vec<_>>();
-        let generator = Rc::new(RefCell::new(Self {
+        let mut generator = Self {
             ctx,
+            events_interval: config.user_load_events_interval.unwrap(),
+            arrivals: config.user_load_arrivals,
+            popularity: config.user_load_popularity,
+            rate: config.user_load_rate.clone(),
+            flash_crowds: config.user_load_flash_crowds.clone(),
+            publish_ratio: config.user_load_publish_ratio,
+            new_content_ratio: config.user_load_new_content_ratio,
+            block_size,
+            ranking: (0..blocks.len()).collect(),
             blocks,
             keys,
+            choices: 0,
+            burst_end: 0.,
+            time_offsets: peer_time_offsets(&config.regions, peers.len()),
+            peer_weights: (!config.profiles.is_empty()).then(|| {
+                let weights = peers.iter().map(|peer| {
+                    env.peer_profile(peer.borrow().id())
+                        .map_or(1., |profile| profile.load_share)
+                });
+                WeightedIndex::new(weights).unwrap()
+            }),
             peers,
-        }));
+            timer: None,
+        };
+        if let Arrivals::Bursty { on_duration, .. } = generator.arrivals {
+            generator.burst_end = generator.ctx.time() + generator.exponential(on_duration);
+        }
+        let now = generator.ctx.time();
+        for (crowd, flash_crowd) in generator.flash_crowds.iter().enumerate() {
+            if flash_crowd.start + flash_crowd.duration > now {
+                generator
+                    .ctx
+                    .emit_self(FlashCrowdTimer { crowd }, (flash_crowd.start - now).max(0.));
+            }
+        }
+        generator.set_active(true);
+        let generator = Rc::new(RefCell::new(generator));
         sim.add_handler(name, generator.clone());
         generator
     }
+
+    /// Starts or stops the generation of the load.
+    pub fn set_active(&mut self, active: bool) {
+        match (self.timer, active) {
+            (None, true) => {
+                let delay = self.next_delay();
+                self.timer = Some(self.ctx.emit_self(UserLoadTimer {}, delay));
+            }
+            (Some(timer), false) => {
+                self.ctx.cancel_event(timer);
+                self.timer = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns `true` if the load is being generated.
+    pub fn is_active(&self) -> bool {
+        self.timer.is_some()
+    }
+
+    /// Returns the number of the blocks, including the ones created during the simulation.
+    pub fn blocks_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the delay until the next event.
+    /// If the rate is modulated, the events are generated at the maximum rate
+    /// and then thinned, see `UserLoadGenerator::accept`.
+    fn next_delay(&mut self) -> f64 {
+        let events_interval = match self.rate.as_ref() {
+            Some(rate) => self.events_interval / rate.max_value(),
+            None => self.events_interval,
+        };
+        match self.arrivals {
+            Arrivals::Fixed => events_interval,
+            Arrivals::Poisson => self.exponential(events_interval),
+            Arrivals::Bursty {
+                on_duration,
+                off_duration,
+            } => {
+                let now = self.ctx.time();
+                let mut time = now + self.exponential(events_interval);
+                // the events falling into an OFF period are moved to the next ON period
+                while time > self.burst_end {
+                    let start = self.burst_end.max(now) + self.exponential(off_duration);
+                    self.burst_end = start + self.exponential(on_duration);
+                    time = start + self.exponential(events_interval);
+                }
+                time - now
+            }
+        }
+    }
+
+    /// Chooses the index of the peer of an event according to the load shares of the peers.
+    fn choose_peer(&self) -> usize {
+        match self.peer_weights.as_ref() {
+            Some(weights) => self.ctx.sample_from_distribution(weights),
+            None => self.ctx.gen_range(0..self.peers.len()),
+        }
+    }
+
+    /// Decides whether the event of the given peer happens, with the probability
+    /// proportional to the rate at the local time of the peer.
+    fn accept(&self, peer: usize) -> bool {
+        match self.rate.as_ref() {
+            Some(rate) => {
+                let time = self.ctx.time() + self.time_offsets[peer];
+                self.ctx.rand() * rate.max_value() < rate.value(time)
+            }
+            None => true,
+        }
+    }
+
+    /// Samples an exponentially distributed value with the given mean.
+    fn exponential(&self, mean: f64) -> f64 {
+        self.ctx
+            .sample_from_distribution(&Exp::new(1. / mean).unwrap())
+    }
+
+    /// Chooses the index of a block according to the popularity distribution.
+    /// The blocks of the pool are ranked in their order, and every new block
+    /// is ranked before all the others, so the newest content is the most popular.
+    fn choose_block(&mut self) -> usize {
+        self.choices += 1;
+        let rank = self
+            .ctx
+            .sample_from_distribution(&self.popularity.rank(self.ranking.len(), self.choices - 1));
+        self.ranking[rank]
+    }
+
+    /// Creates a new block and adds it to the pool as the most popular one.
+    fn create_block(&mut self) -> String {
+        let block = self.ctx.random_string(self.block_size);
+        self.ranking.push_front(self.blocks.len());
+        self.keys.push(Key::from_sha256(block.as_bytes()));
+        self.blocks.push(block.clone());
+        block
+    }
 }
 
 impl EventHandler for UserLoadGenerator {
     fn on(&mut self, event: Event) {
         cast!(match event.data {
             UserLoadTimer {} => {
-                let peer = self.peers[self.ctx.gen_range(0..self.peers.len())].clone();
-                if self.ctx.rand() < 0.5 {
-                    let random_block =
-                        self.blocks[self.ctx.gen_range(0..self.blocks.len())].clone();
-                    peer.borrow_mut().publish_data(random_block);
-                } else {
-                    let random_key = self.keys[self.ctx.gen_range(0..self.keys.len())].clone();
-                    peer.borrow_mut().retrieve_data(random_key);
+                let idx = self.choose_peer();
+                let peer = self.peers[idx].clone();
+                if !self.accept(idx) {
+                    // the event is thinned out by the rate modulation
+                } else if self.ctx.rand() < self.publish_ratio {
+                    let new_content = self.blocks.is_empty()
+                        || (self.new_content_ratio > 0.
+                            && self.ctx.rand() < self.new_content_ratio);
+                    let block = if new_content {
+                        self.create_block()
+                    } else {
+                        let idx = self.choose_block();
+                        self.blocks[idx].clone()
+                    };
+                    peer.borrow_mut().publish_data(block);
+                } else if !self.keys.is_empty() {
+                    let idx = self.choose_block();
+                    peer.borrow_mut().retrieve_data(self.keys[idx].clone());
+                }
+                let delay = self.next_delay();
+                self.timer = Some(self.ctx.emit_self(UserLoadTimer {}, delay));
+            }
+            FlashCrowdTimer { crowd } => {
+                let flash_crowd = &self.flash_crowds[crowd];
+                let (end, events_interval) = (
+                    flash_crowd.start + flash_crowd.duration,
+                    flash_crowd.events_interval,
+                );
+                if self.is_active() {
+                    let peer = &self.peers[self.choose_peer()];
+                    peer.borrow_mut()
+                        .retrieve_data(self.keys[flash_crowd.block].clone());
+                }
+                let delay = self.exponential(events_interval);
+                if self.ctx.time() + delay < end {
+                    self.ctx.emit_self(FlashCrowdTimer { crowd }, delay);
                 }
-                self.ctx
-                    .emit_self(UserLoadTimer {}, CONFIG.user_load_events_interval.unwrap());
             }
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{network::NetworkAgent, peer::test_peers, SimulationConfig};
+
+    /// Runs the user load with the given overrides of the `[user_load]` table
+    /// among 16 peers, whose messages are all lost, so only the queries started are counted.
+    ///
+    /// # Returns
+    ///
+    /// The generator, and the numbers of the publications and the retrievals of every peer.
+    fn run_per_peer(
+        overrides: &[(&str, &str)],
+        duration: f64,
+    ) -> (Rc<RefCell<UserLoadGenerator>>, Vec<(u32, u32)>) {
+        let config = || {
+            let mut all = vec![
+                ("log_level_filter", "'off'"),
+                ("num_peers", "16"),
+                ("user_load.enabled", "true"),
+                ("user_load.events_interval", "1.0"),
+            ];
+            all.extend_from_slice(overrides);
+            let all = all
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<Vec<_>>();
+            SimulationConfig::from_toml_str_with_overrides(include_str!("../../config.toml"), &all)
+                .unwrap()
+        };
+        let env = Environment::new(config());
+        let mut sim = Simulation::new(0);
+        let peers = test_peers(
+            &mut sim,
+            config(),
+            NetworkAgent::from_function(|_, _, _| None),
+        );
+        let generator = UserLoadGenerator::register(&mut sim, &env, peers.clone());
+        sim.step_until_time(duration);
+        let events = peers
+            .iter()
+            .map(|peer| {
+                let peer = peer.borrow();
+                let stats = peer.peek_stats();
+                (
+                    stats.put_value_queries_started,
+                    stats.retrieve_data_queries_started,
+                )
+            })
+            .collect();
+        (generator, events)
+    }
+
+    /// Same as `run_per_peer`, but returns the total numbers of the publications
+    /// and the retrievals.
+    fn run(
+        overrides: &[(&str, &str)],
+        duration: f64,
+    ) -> (Rc<RefCell<UserLoadGenerator>>, u32, u32) {
+        let (generator, events) = run_per_peer(overrides, duration);
+        let publications = events.iter().map(|&(publications, _)| publications).sum();
+        let retrievals = events.iter().map(|&(_, retrievals)| retrievals).sum();
+        (generator, publications, retrievals)
+    }
+
+    #[test]
+    fn test_arrivals() {
+        let (_, publications, retrievals) = run(&[], 1000.5);
+        assert_eq!(publications + retrievals, 1000);
+
+        let (_, publications, retrievals) =
+            run(&[("user_load.arrivals", "{ kind = 'poisson' }")], 2000.);
+        let events = (publications + retrievals) as f64;
+        assert!((events - 2000.).abs() < 200., "{}", events);
+
+        // the load is generated only during a quarter of the time
+        let bursty = "{ kind = 'bursty', on_duration = 10.0, off_duration = 30.0 }";
+        let (_, publications, retrievals) = run(&[("user_load.arrivals", bursty)], 8000.);
+        let events = (publications + retrievals) as f64;
+        assert!((events - 2000.).abs() < 500., "{}", events);
+    }
+
+    #[test]
+    fn test_thinning_at_local_time() {
+        // the rate is 2 until the local time 1000 and 0 after it, and the peers
+        // of the second region are 1000 seconds ahead, so only the first region is active
+        let overrides = [
+            (
+                "user_load.rate",
+                "{ kind = 'steps', initial = 2.0, steps = [{ at = 1000.0, value = 0.0 }] }",
+            ),
+            (
+                "regions",
+                "[{ name = 'a', fraction = 0.5 }, { name = 'b', fraction = 0.5, time_offset = 1000.0 }]",
+            ),
+        ];
+        let (_, events) = run_per_peer(&overrides, 1000.);
+        let count = |peers: &[(u32, u32)]| {
+            peers
+                .iter()
+                .map(|&(publications, retrievals)| publications + retrievals)
+                .sum::<u32>() as f64
+        };
+        // the events are generated at the maximum rate of 2 per second,
+        // and a half of them falls on the active region
+        assert!((count(&events[..8]) - 1000.).abs() < 100., "{:?}", events);
+        assert_eq!(count(&events[8..]), 0.);
+    }
+
+    #[test]
+    fn test_publish_ratio() {
+        let (_, publications, retrievals) = run(&[("user_load.publish_ratio", "0.2")], 2000.5);
+        assert_eq!(publications + retrievals, 2000);
+        assert!((publications as f64 - 400.).abs() < 80., "{}", publications);
+
+        let (_, publications, retrievals) = run(&[("user_load.publish_ratio", "1.0")], 100.5);
+        assert_eq!((publications, retrievals), (100, 0));
+    }
+
+    #[test]
+    fn test_new_content() {
+        let overrides = [
+            ("user_load.publish_ratio", "1.0"),
+            ("user_load.new_content_ratio", "0.5"),
+            ("user_load.blocks_pool_size", "10"),
+        ];
+        let (generator, publications, _) = run(&overrides, 2000.5);
+        assert_eq!(publications, 2000);
+        let created = generator.borrow().blocks_count() - 10;
+        assert!((created as f64 - 1000.).abs() < 100., "{}", created);
+
+        // the first publication creates a block if the pool is empty
+        let overrides = [
+            ("user_load.publish_ratio", "1.0"),
+            ("user_load.new_content_ratio", "0.01"),
+            ("user_load.blocks_pool_size", "0"),
+        ];
+        let (generator, publications, _) = run(&overrides, 1.5);
+        assert_eq!(publications, 1);
+        assert_eq!(generator.borrow().blocks_count(), 1);
+    }
+
+    #[test]
+    fn test_new_content_is_most_popular() {
+        let overrides = [
+            ("user_load.publish_ratio", "1.0"),
+            ("user_load.blocks_pool_size", "10"),
+            ("user_load.popularity", "{ kind = 'zipf', exponent = 2.0 }"),
+        ];
+        let (generator, _, _) = run(&overrides, 0.);
+        let mut generator = generator.borrow_mut();
+        let (oldest, newest) = (generator.create_block(), generator.create_block());
+        let mut counts = [0; 12];
+        for _ in 0..1000 {
+            counts[generator.choose_block()] += 1;
+        }
+        assert_eq!(generator.blocks[11], newest);
+        assert_eq!(generator.blocks[10], oldest);
+        assert!(counts[11] > counts[10] && counts[10] > counts[0]);
+        assert!(counts[0] > counts[9]);
+    }
+}
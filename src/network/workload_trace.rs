@@ -0,0 +1,391 @@
+use crate::peer::Peer;
+use dslab_core::{cast, Event, EventHandler, Simulation, SimulationContext};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{Error, ErrorKind},
+    rc::Rc,
+};
+
+/// Represents the way the peers of a workload trace are mapped onto the simulated peers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerMapping {
+    /// The n-th distinct peer of the trace is mapped onto the simulated peer `n mod num_peers`.
+    #[default]
+    Sequential,
+    /// A peer of the trace is mapped onto the simulated peer chosen by the hash of its name,
+    /// so the mapping does not depend on the other records of the trace.
+    Hash,
+}
+
+/// Represents the configuration of the replay of a workload trace,
+/// the `[workload_trace]` table of the configuration file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkloadTraceConfig {
+    /// The path to the CSV file with the trace.
+    pub path: String,
+    #[serde(default)]
+    pub peer_mapping: PeerMapping,
+    /// The factor the intervals between the records are multiplied by.
+    #[serde(default = "default_time_scale")]
+    pub time_scale: f64,
+    /// Whether to publish the content that is retrieved before it is published in the trace
+    /// from random peers at the start of the replay.
+    #[serde(default = "default_preload_missing")]
+    pub preload_missing: bool,
+}
+
+fn default_time_scale() -> f64 {
+    1.
+}
+
+fn default_preload_missing() -> bool {
+    true
+}
+
+/// Represents the operations of a workload trace.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkloadOp {
+    Publish,
+    Retrieve,
+}
+
+/// Represents a record of a workload trace mapped onto the simulation.
+#[derive(Clone, Debug)]
+pub struct WorkloadRecord {
+    /// The time of the operation since the start of the replay.
+    pub time: f64,
+    /// The index of the simulated peer.
+    pub peer: usize,
+    pub op: WorkloadOp,
+    /// The index of the content in `WorkloadTrace::contents`.
+    pub content: usize,
+}
+
+/// Represents a workload trace: a timestamped sequence of publications and retrievals.
+///
+/// The trace is a CSV file with a header and the `time`, `peer`, `op` and `content` columns
+/// in any order, and an optional `size` column, e.g.
+///
+/// ```text
+/// time,peer,op,content,size
+/// 1700000000.25,12D3KooWA,publish,bafybeib,1024
+/// 1700000001.5,12D3KooWB,retrieve,bafybeib,1024
+/// ```
+///
+/// The `op` is either `publish` or `retrieve`. The times are counted from the first record.
+/// Every content ID is mapped onto a block that starts with the ID and is padded
+/// to the first size given for the content; blocks with no size are the IDs themselves.
+/// Empty lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug)]
+pub struct WorkloadTrace {
+    records: Vec<WorkloadRecord>,
+    contents: Vec<String>,
+}
+
+impl WorkloadTrace {
+    /// Loads the trace from the file given in the configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration of the replay.
+    /// * `num_peers` - The number of the simulated peers.
+    pub fn from_file(config: &WorkloadTraceConfig, num_peers: u32) -> std::io::Result<Self> {
+        let data = std::fs::read_to_string(&config.path)?;
+        Self::from_csv_str(&data, config, num_peers)
+    }
+
+    /// Parses the trace from a CSV string, see `WorkloadTrace`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The contents of the trace file.
+    /// * `config` - The configuration of the replay.
+    /// * `num_peers` - The number of the simulated peers.
+    pub fn from_csv_str(
+        data: &str,
+        config: &WorkloadTraceConfig,
+        num_peers: u32,
+    ) -> std::io::Result<Self> {
+        let invalid = |line: usize, reason: String| {
+            Error::new(ErrorKind::InvalidData, format!("line {}: {}", line, reason))
+        };
+        let mut lines = data
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let (header_line, header) = lines
+            .next()
+            .ok_or_else(|| invalid(1, "the header is missing".to_string()))?;
+        let header = header.split(',').map(str::trim).collect::<Vec<_>>();
+        let column = |name: &str| header.iter().position(|&column| column == name);
+        let (Some(time_col), Some(peer_col), Some(op_col), Some(content_col)) = (
+            column("time"),
+            column("peer"),
+            column("op"),
+            column("content"),
+        ) else {
+            return Err(invalid(
+                header_line,
+                "the header must contain the time, peer, op and content columns".to_string(),
+            ));
+        };
+        let size_col = column("size");
+
+        let mut peers = HashMap::new();
+        let mut content_ids = HashMap::new();
+        let mut contents = vec![];
+        let mut records = vec![];
+        for (line, row) in lines {
+            let fields = row.split(',').map(str::trim).collect::<Vec<_>>();
+            if fields.len() != header.len() {
+                return Err(invalid(
+                    line,
+                    format!("expected {} fields, got {}", header.len(), fields.len()),
+                ));
+            }
+            let time = fields[time_col]
+                .parse::<f64>()
+                .map_err(|err| invalid(line, format!("invalid time: {}", err)))?;
+            if !time.is_finite() {
+                return Err(invalid(line, "time must be finite".to_string()));
+            }
+            let op = match fields[op_col].to_ascii_lowercase().as_str() {
+                "publish" => WorkloadOp::Publish,
+                "retrieve" => WorkloadOp::Retrieve,
+                other => return Err(invalid(line, format!("invalid op '{}'", other))),
+            };
+            let size = match size_col.map(|col| fields[col]) {
+                None | Some("") => None,
+                Some(size) => Some(
+                    size.parse::<usize>()
+                        .map_err(|err| invalid(line, format!("invalid size: {}", err)))?,
+                ),
+            };
+
+            let next_peer = peers.len();
+            let peer = *peers
+                .entry(fields[peer_col].to_string())
+                .or_insert_with(|| match config.peer_mapping {
+                    PeerMapping::Sequential => next_peer % num_peers as usize,
+                    PeerMapping::Hash => (hash(fields[peer_col]) % num_peers as u64) as usize,
+                });
+            let content = *content_ids
+                .entry(fields[content_col].to_string())
+                .or_insert_with(|| {
+                    contents.push(content_data(fields[content_col], size));
+                    contents.len() - 1
+                });
+            records.push(WorkloadRecord {
+                time,
+                peer,
+                op,
+                content,
+            });
+        }
+
+        records.sort_by(|a, b| a.time.total_cmp(&b.time));
+        if let Some(start) = records.first().map(|record| record.time) {
+            for record in records.iter_mut() {
+                record.time = (record.time - start) * config.time_scale;
+            }
+        }
+        Ok(Self { records, contents })
+    }
+
+    /// Returns the records of the trace ordered by time.
+    pub fn records(&self) -> &[WorkloadRecord] {
+        &self.records
+    }
+
+    /// Returns the blocks of the content of the trace.
+    pub fn contents(&self) -> &[String] {
+        &self.contents
+    }
+
+    /// Returns the indices of the content that is retrieved before it is published,
+    /// or is never published.
+    pub fn missing_contents(&self) -> Vec<usize> {
+        let mut first_op = vec![None; self.contents.len()];
+        for record in self.records.iter() {
+            first_op[record.content].get_or_insert(record.op);
+        }
+        first_op
+            .into_iter()
+            .enumerate()
+            .filter(|(_, op)| *op == Some(WorkloadOp::Retrieve))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+/// Returns a stable hash of the string.
+fn hash(s: &str) -> u64 {
+    let digest = Sha256::digest(s.as_bytes());
+    u64::from_le_bytes(digest[..8].try_into().unwrap())
+}
+
+/// Returns the block of the content with the given ID.
+fn content_data(id: &str, size: Option<usize>) -> String {
+    match size {
+        Some(size) if size > id.len() => format!("{}#{}", id, "0".repeat(size - id.len() - 1)),
+        _ => id.to_string(),
+    }
+}
+
+/// Replays a workload trace: the peers publish and retrieve the content of the trace
+/// at the times of its records.
+pub struct TraceReplayer {
+    ctx: SimulationContext,
+    peers: Vec<Rc<RefCell<Peer>>>,
+    trace: WorkloadTrace,
+    next: usize, // the index of the next record to replay
+}
+
+#[derive(Clone, Serialize)]
+pub struct ReplayTimer {}
+
+impl TraceReplayer {
+    /// Registers the replayer in the simulation. The replay starts at the current time.
+    ///
+    /// # Arguments
+    ///
+    /// * `sim` - The simulation.
+    /// * `peers` - The simulated peers indexed as in the records of the trace.
+    /// * `trace` - The trace to replay.
+    /// * `preload_missing` - Whether to publish the missing content from random peers first,
+    ///   see `WorkloadTrace::missing_contents`.
+    pub fn register(
+        sim: &mut Simulation,
+        peers: Vec<Rc<RefCell<Peer>>>,
+        trace: WorkloadTrace,
+        preload_missing: bool,
+    ) -> Rc<RefCell<Self>> {
+        let name = "trace_replayer";
+        let ctx = sim.create_context(name);
+        if preload_missing {
+            for content in trace.missing_contents() {
+                let peer = &peers[ctx.gen_range(0..peers.len())];
+                peer.borrow_mut()
+                    .publish_data(trace.contents[content].clone());
+            }
+        }
+        if let Some(record) = trace.records.first() {
+            ctx.emit_self(ReplayTimer {}, record.time);
+        }
+        let replayer = Rc::new(RefCell::new(Self {
+            ctx,
+            peers,
+            trace,
+            next: 0,
+        }));
+        sim.add_handler(name, replayer.clone());
+        replayer
+    }
+
+    /// Returns the number of the records replayed so far.
+    pub fn replayed(&self) -> usize {
+        self.next
+    }
+}
+
+impl EventHandler for TraceReplayer {
+    fn on(&mut self, event: Event) {
+        cast!(match event.data {
+            ReplayTimer {} => {
+                let time = self.trace.records[self.next].time;
+                while let Some(record) = self.trace.records.get(self.next) {
+                    if record.time > time {
+                        self.ctx.emit_self(ReplayTimer {}, record.time - time);
+                        break;
+                    }
+                    let mut peer = self.peers[record.peer].borrow_mut();
+                    let data = &self.trace.contents[record.content];
+                    match record.op {
+                        WorkloadOp::Publish => {
+                            peer.publish_data(data.clone());
+                        }
+                        WorkloadOp::Retrieve => {
+                            peer.retrieve_data(crate::Key::from_sha256(data.as_bytes()));
+                        }
+                    }
+                    self.next += 1;
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_csv_str() {
+        let config = WorkloadTraceConfig {
+            path: String::new(),
+            peer_mapping: PeerMapping::Sequential,
+            time_scale: 2.,
+            preload_missing: true,
+        };
+        let trace = WorkloadTrace::from_csv_str(
+            "# gateway log\n\
+             op,time,peer,content,size\n\
+             retrieve,105.0,b,x,\n\
+             publish,100.0,a,y,8\n\
+             retrieve,101.0,c,y,8\n\
+             publish,106.0,a,x,\n",
+            &config,
+            2,
+        )
+        .unwrap();
+        let records = trace.records();
+        assert_eq!(
+            records.iter().map(|r| r.time).collect::<Vec<_>>(),
+            vec![0., 2., 10., 12.]
+        );
+        assert_eq!(
+            records.iter().map(|r| r.peer).collect::<Vec<_>>(),
+            vec![1, 0, 0, 1]
+        );
+        assert_eq!(trace.contents(), &["x".to_string(), "y#000000".to_string()]);
+        assert_eq!(trace.missing_contents(), vec![0]);
+    }
+
+    #[test]
+    fn test_invalid_op() {
+        let config = WorkloadTraceConfig {
+            path: String::new(),
+            peer_mapping: PeerMapping::Hash,
+            time_scale: 1.,
+            preload_missing: false,
+        };
+        let err = WorkloadTrace::from_csv_str("time,peer,op,content\n0,a,delete,x\n", &config, 2)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "line 2: invalid op 'delete'");
+    }
+
+    #[test]
+    fn test_non_finite_time() {
+        let config = WorkloadTraceConfig {
+            path: String::new(),
+            peer_mapping: PeerMapping::Sequential,
+            time_scale: 1.,
+            preload_missing: false,
+        };
+        for time in ["nan", "inf", "-inf"] {
+            let data = format!(
+                "time,peer,op,content\n0,a,publish,x\n{},a,retrieve,x\n",
+                time
+            );
+            let err = WorkloadTrace::from_csv_str(&data, &config, 2).unwrap_err();
+            assert_eq!(err.to_string(), "line 3: time must be finite");
+        }
+    }
+}
@@ -1,31 +1,48 @@
 use crate::{
     kbucket::KBucketsTable,
     message::{
-        BootstrapTimer, FindNodeQueryTimeout, FindNodeRequest, FindNodeResponse,
-        GetValueQueryTimeout, GetValueRequest, GetValueResponse, PingRequest, PingResponse,
+        BootstrapTimer, ExpirationSweepTimer, FindNodeQueryTimeout, FindNodeRequest,
+        FindNodeRequestTimeout, FindNodeResponse, GarbageCollectionTimer, GetValueQueryTimeout,
+        GetValueRequest, GetValueResponse, Message, MessageInfo, PingRequest, PingResponse,
         PingTimeout, PutValueQueryTimeout, PutValueRequest, RepublishTimer,
         RetrieveDataQueryTimeout, RetrieveDataRequest, RetrieveDataResponse,
     },
     network::NetworkAgent,
     query::{
-        FindNodeQuery, GetValueQuery, PutValueQuery, QueriesPool, QueriesStats, QueryId,
-        QueryState, QueryTrigger,
+        FindNodeQuery, FindNodeQueryState, GetValueQuery, PutValueQuery, QueriesPool, QueriesStats,
+        QueryFinished, QueryId, QueryKind, QueryOutcome, QueryProgress, QueryRecord, QueryResult,
+        QueryState, QueryTrigger, TrafficOrigin,
     },
-    storage::{LocalDHTStorage, LocalFileStorage, Record, RecordData},
-    Key, PeerId, CONFIG, K_VALUE,
+    storage::{EvictionPolicy, LocalDHTStorage, LocalFileStorage, Provider, Record, RecordData},
+    trace::{QueryTransition, TraceEvent, Tracer},
+    Environment, Key, PeerId,
 };
-use dslab_core::{cast, Event, EventData, EventHandler, Simulation, SimulationContext};
+use dslab_core::{cast, Event, EventHandler, EventId, Id, Simulation, SimulationContext};
 use log::Level;
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 /// Represents a peer in the IPFS simulator.
 pub struct Peer {
     ctx: SimulationContext,
+    env: Rc<Environment>,
     kbuckets: KBucketsTable,
     queries: QueriesPool,
     network: NetworkAgent,
     dht_storage: LocalDHTStorage,
     file_storage: LocalFileStorage,
+    provided_keys: HashSet<Key>,
+    published_keys: HashSet<Key>, // every key ever published by the peer
+    republish_timers: HashMap<Key, EventId>, // the pending republication of every provided key
     stats: QueriesStats,
+    tracer: Option<Tracer>,
+    online: bool,
+    query_origins: HashMap<QueryId, TrafficOrigin>,
+    evicted_peers: HashMap<QueryId, HashSet<PeerId>>, // evicted on request timeouts, until the query times out
+    cancelled_queries: HashSet<QueryId>,              // until the query times out
+    subscribers: Vec<Id>,
 }
 
 impl Peer {
@@ -34,35 +51,119 @@ impl Peer {
     /// # Arguments
     ///
     /// * `sim` - A mutable reference to the simulation.
+    /// * `env` - The environment of the simulation.
     /// * `name` - The name of the peer. Should be unique.
     /// * `network` - The network agent associated with the peer.
     ///
     /// # Returns
     ///
     /// A new instance of `Peer`.
-    pub fn new(sim: &mut Simulation, name: impl AsRef<str>, network: NetworkAgent) -> Self {
+    pub fn new(
+        sim: &mut Simulation,
+        env: Rc<Environment>,
+        name: impl AsRef<str>,
+        network: NetworkAgent,
+    ) -> Self {
         let ctx = sim.create_context(name);
-        let local_key = Key::from_peer_id(ctx.id());
+        let config = env.config();
 
-        if CONFIG.enable_bootstrap {
+        if config.enable_bootstrap {
             // Schedule the first refresh of the k-buckets table.
             let delay = ctx.sample_from_distribution(&rand::distributions::Uniform::new(
                 0.0,
-                CONFIG.kbuckets_refresh_interval,
+                config.kbuckets_refresh_interval,
             ));
             ctx.emit_self(BootstrapTimer {}, delay);
         }
+        if let Some(interval) = config.record_expiration_sweep_interval {
+            // Schedule the first sweep of the expired records.
+            let delay =
+                ctx.sample_from_distribution(&rand::distributions::Uniform::new(0.0, interval));
+            ctx.emit_self(ExpirationSweepTimer {}, delay);
+        }
+        if let Some(interval) = config.gc_interval {
+            // Schedule the first garbage collection.
+            let delay =
+                ctx.sample_from_distribution(&rand::distributions::Uniform::new(0.0, interval));
+            ctx.emit_self(GarbageCollectionTimer {}, delay);
+        }
+        // The seeds are only drawn for the random eviction,
+        // so that the other policies do not shift the random number stream.
+        let eviction_seed = |policy| match policy {
+            EvictionPolicy::Random => ctx.gen_range(0..u64::MAX),
+            _ => 0,
+        };
+        let profile = env.peer_profile(ctx.id());
+        let dht_storage = LocalDHTStorage::with_capacity(
+            profile.map_or(config.dht_storage_capacity, |profile| {
+                profile.dht_storage_capacity(config.dht_storage_capacity)
+            }),
+            config.dht_storage_eviction_policy,
+            eviction_seed(config.dht_storage_eviction_policy),
+        );
+        let file_storage = LocalFileStorage::with_capacity(
+            profile.map_or(config.file_storage_capacity, |profile| {
+                profile.file_storage_capacity(config.file_storage_capacity)
+            }),
+            config.file_storage_eviction_policy,
+            eviction_seed(config.file_storage_eviction_policy),
+        );
+        let kbuckets = KBucketsTable::new(env.clone(), env.peer_key(ctx.id()));
         Self {
             ctx,
-            kbuckets: KBucketsTable::new(local_key),
+            env,
+            kbuckets,
             queries: QueriesPool::new(),
             network,
-            dht_storage: LocalDHTStorage::new(),
-            file_storage: LocalFileStorage::new(),
+            dht_storage,
+            file_storage,
+            provided_keys: HashSet::new(),
+            published_keys: HashSet::new(),
+            republish_timers: HashMap::new(),
             stats: QueriesStats::new(),
+            tracer: None,
+            online: true,
+            query_origins: HashMap::new(),
+            evicted_peers: HashMap::new(),
+            cancelled_queries: HashSet::new(),
+            subscribers: vec![],
+        }
+    }
+
+    /// Subscribes the component to the notifications about the finished queries of the peer.
+    /// The component receives a `QueryFinished` event when a query completes or fails.
+    pub fn subscribe(&mut self, subscriber: Id) {
+        if !self.subscribers.contains(&subscriber) {
+            self.subscribers.push(subscriber);
         }
     }
 
+    /// Sets whether the peer is online.
+    /// An offline peer neither sends nor receives messages, but its timers keep firing.
+    pub fn set_online(&mut self, online: bool) {
+        if self.online != online {
+            self.log(
+                Level::Debug,
+                if online {
+                    "Went online"
+                } else {
+                    "Went offline"
+                },
+            );
+        }
+        self.online = online;
+    }
+
+    /// Returns `true` if the peer is online.
+    pub fn is_online(&self) -> bool {
+        self.online
+    }
+
+    /// Sets the tracer that records the messages and the query state transitions of the peer.
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
     /// Adds a peer to the k-buckets table.
     ///
     /// # Arguments
@@ -91,13 +192,59 @@ impl Peer {
         std::mem::take(&mut self.stats)
     }
 
+    /// Returns the statistics related to queries without resetting them.
+    pub fn peek_stats(&self) -> &QueriesStats {
+        &self.stats
+    }
+
+    /// Returns the number of records in the DHT storage.
+    pub fn dht_records_count(&self) -> usize {
+        self.dht_storage.len()
+    }
+
+    /// Returns the number of blocks in the file storage.
+    pub fn file_blocks_count(&self) -> usize {
+        self.file_storage.len()
+    }
+
+    /// Returns the unexpired record stored in the DHT storage without marking it as used.
+    pub fn peek_dht_record(&self, key: &Key) -> Option<&Record> {
+        self.dht_storage.peek(key, self.ctx.time())
+    }
+
+    /// Returns `true` if the file storage holds the data associated with the key.
+    pub fn has_data(&self, key: &Key) -> bool {
+        self.file_storage.contains(key)
+    }
+
+    /// Returns the keys of the data the peer announces itself as a provider of.
+    pub fn provided_keys(&self) -> impl Iterator<Item = &Key> {
+        self.provided_keys.iter()
+    }
+
+    /// Returns the keys of all the data ever published by the peer,
+    /// including the data it has removed or lost since then.
+    pub fn published_keys(&self) -> impl Iterator<Item = &Key> {
+        self.published_keys.iter()
+    }
+
+    /// Returns the number of peers in the k-buckets table.
+    pub fn routing_table_size(&self) -> usize {
+        self.kbuckets.peers_count()
+    }
+
+    /// Returns the number of queries that are still running.
+    pub fn queries_in_flight(&self) -> usize {
+        self.queries.len()
+    }
+
     /// Effectively fills the k-buckets table with random peers.
     /// This method uses information that is not available in the real world.
     pub fn fill_kbuckets_unfair(&mut self) {
-        for i in 0..CONFIG.num_peers.ilog2() as usize {
-            for _ in 0..*crate::K_VALUE {
+        for i in 0..self.env.config().num_peers.ilog2() as usize {
+            for _ in 0..self.env.k() {
                 let key = Key::random_in_bucket(&self.ctx, self.kbuckets.local_key(), i);
-                let peers = crate::KEYS_TREE.find_closest_peers(&key, 1);
+                let peers = self.env.find_closest_peers(&key, 1);
                 let peer_id = peers.iter().next().unwrap();
                 self.kbuckets.add_peer(*peer_id, self.ctx.time());
             }
@@ -109,17 +256,38 @@ impl Peer {
         self.ctx.id()
     }
 
+    /// Returns the k-buckets table of the peer.
+    pub fn kbuckets(&self) -> &KBucketsTable {
+        &self.kbuckets
+    }
+
     /// Sends a message to the specified destination peer.
     ///
     /// # Arguments
     ///
     /// * `data` - The data to send as the message.
     /// * `dst` - The ID of the destination peer.
-    fn send_message(&mut self, data: impl EventData, dst: PeerId) {
+    fn send_message<M: Message>(&mut self, data: M, dst: PeerId) {
+        if !self.online {
+            return;
+        }
+        let message = MessageInfo::new(&data);
+        let size = message.size;
+        let origin = self.traffic_origin(&message, true);
+        self.stats.traffic.add_sent(&message, origin);
+        if let Some(tracer) = &self.tracer {
+            tracer.record(TraceEvent::Sent {
+                time: self.ctx.time(),
+                src: self.ctx.id(),
+                dst,
+                message,
+            });
+        }
         if let Some(delay) = self
             .network
             .sample_message_delay(&self.ctx, self.ctx.id(), dst)
         {
+            let delay = delay + self.env.profile_delay(self.ctx.id(), dst, size);
             self.ctx.emit(data, dst, delay);
         }
     }
@@ -154,12 +322,31 @@ impl Peer {
             Level::Debug,
             &format!("Initiated FindNodeQuery with id={}", query_id),
         );
-        self.ctx
-            .emit_self(FindNodeQueryTimeout { query_id }, CONFIG.query_timeout);
-        let (query_request, request) =
-            FindNodeQuery::new(query_id, trigger, key.clone(), self.ctx.id());
+        self.ctx.emit_self(
+            FindNodeQueryTimeout { query_id },
+            self.env.config().query_timeout,
+        );
+        let origin = match trigger {
+            QueryTrigger::Manual => TrafficOrigin::User,
+            QueryTrigger::Bootstrap => TrafficOrigin::Bootstrap,
+            QueryTrigger::GetValue(parent_id) | QueryTrigger::PutValue(parent_id) => self
+                .query_origins
+                .get(&parent_id)
+                .copied()
+                .unwrap_or(TrafficOrigin::User),
+        };
+        self.query_origins.insert(query_id, origin);
+        let (query_request, request) = FindNodeQuery::new(
+            self.env.clone(),
+            query_id,
+            trigger,
+            key.clone(),
+            self.ctx.id(),
+            self.ctx.time(),
+        );
         self.queries.add_find_node_query(query_id, query_request);
         self.stats.find_node_queries_started += 1;
+        self.trace_query(query_id, QueryKind::FindNode, QueryTransition::Started);
         self.send_message(request, self.ctx.id());
         query_id
     }
@@ -179,10 +366,14 @@ impl Peer {
             Level::Debug,
             &format!("Initiated GetValueQuery with id={}", query_id),
         );
-        self.ctx
-            .emit_self(GetValueQueryTimeout { query_id }, CONFIG.query_timeout);
+        self.trace_query(query_id, QueryKind::GetValue, QueryTransition::Started);
+        self.query_origins.insert(query_id, TrafficOrigin::User);
+        self.ctx.emit_self(
+            GetValueQueryTimeout { query_id },
+            self.env.config().query_timeout,
+        );
         self.find_node(&key, QueryTrigger::GetValue(query_id));
-        let query = GetValueQuery::new(key);
+        let query = GetValueQuery::new(key, self.env.config().caching_max_peers, self.ctx.time());
         self.queries.add_get_value_query(query_id, query);
         self.stats.get_value_queries_started += 1;
         query_id
@@ -198,14 +389,23 @@ impl Peer {
     ///
     /// The ID of the initiated query.
     pub fn put_value(&mut self, record: Record) -> QueryId {
+        self.start_put_value(record, TrafficOrigin::User)
+    }
+
+    /// Initiates a `PutValueQuery` whose traffic is attributed to the given origin.
+    fn start_put_value(&mut self, record: Record, origin: TrafficOrigin) -> QueryId {
         let query_id = self.queries.next_query_id();
         self.log(
             Level::Debug,
             &format!("Initiated PutValueQuery with id={}", query_id),
         );
-        self.ctx
-            .emit_self(PutValueQueryTimeout { query_id }, CONFIG.query_timeout);
-        let query = PutValueQuery::new(record);
+        self.trace_query(query_id, QueryKind::PutValue, QueryTransition::Started);
+        self.query_origins.insert(query_id, origin);
+        self.ctx.emit_self(
+            PutValueQueryTimeout { query_id },
+            self.env.config().query_timeout,
+        );
+        let query = PutValueQuery::new(record, self.ctx.time());
         let key = query.key();
         self.queries.add_put_value_query(query_id, query);
         self.stats.put_value_queries_started += 1;
@@ -213,6 +413,77 @@ impl Peer {
         query_id
     }
 
+    /// Cancels the query together with the queries linked to it by `QueryTrigger`:
+    /// the lookups started by the query, or the query that started the lookup.
+    /// The responses that arrive later, until the query times out, are ignored
+    /// without side effects: they are neither accounted in the traffic statistics
+    /// nor add their senders to the k-buckets table.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_id` - The ID of the query to cancel.
+    ///
+    /// # Returns
+    ///
+    /// `true` if any query was cancelled, `false` if there was no such running query.
+    pub fn cancel_query(&mut self, query_id: QueryId) -> bool {
+        let root_id = match self
+            .queries
+            .get_mut_find_node_query(query_id)
+            .map(|query| query.trigger())
+        {
+            Some(QueryTrigger::GetValue(parent_id) | QueryTrigger::PutValue(parent_id)) => {
+                parent_id
+            }
+            _ => query_id,
+        };
+        let mut cancelled = false;
+        let mut find_node_ids = self.queries.find_node_queries_triggered_by(root_id);
+        find_node_ids.push(root_id);
+        for id in find_node_ids {
+            if let Some(query) = self.queries.remove_find_node_query(id) {
+                self.cancelled_queries.insert(id);
+                self.stats.find_node_queries_cancelled += 1;
+                self.on_query_cancelled(id, QueryKind::FindNode, &query.progress());
+                cancelled = true;
+            }
+        }
+        if let Some(query) = self.queries.remove_get_value_query(root_id) {
+            self.cancelled_queries.insert(root_id);
+            self.stats.get_value_queries_cancelled += 1;
+            self.on_query_cancelled(root_id, QueryKind::GetValue, query.progress());
+            cancelled = true;
+        }
+        if let Some(query) = self.queries.remove_put_value_query(root_id) {
+            self.cancelled_queries.insert(root_id);
+            self.stats.put_value_queries_cancelled += 1;
+            self.on_query_cancelled(root_id, QueryKind::PutValue, query.progress());
+            cancelled = true;
+        }
+        if let Some(progress) = self.queries.remove_retrieve_data_query(root_id) {
+            self.cancelled_queries.insert(root_id);
+            self.stats.retrieve_data_queries_cancelled += 1;
+            self.on_query_cancelled(root_id, QueryKind::RetrieveData, &progress);
+            cancelled = true;
+        }
+        cancelled
+    }
+
+    /// Logs and records a cancelled query.
+    fn on_query_cancelled(&mut self, query_id: QueryId, kind: QueryKind, progress: &QueryProgress) {
+        self.log(
+            Level::Debug,
+            &format!("Cancelled {:?} query with id={}", kind, query_id),
+        );
+        self.record_query(
+            query_id,
+            kind,
+            progress,
+            QueryOutcome::Cancelled,
+            QueryResult::None,
+        );
+    }
+
     /// Publishes data into the IPFS network.
     ///
     /// # Arguments
@@ -221,24 +492,42 @@ impl Peer {
     ///
     /// # Returns
     ///
-    /// The key associated with the published data.
-    pub fn publish_data(&mut self, data: String) -> Key {
+    /// The ID of the initiated `PutValueQuery` that puts the provider record into the DHT.
+    /// The key associated with the data is its SHA-256 hash, see `Key::from_sha256`.
+    pub fn publish_data(&mut self, data: String) -> QueryId {
         self.log(
             Level::Info,
             &format!("Initiated publishing data \"{}\"", data),
         );
         let key = Key::from_sha256(data.as_bytes());
-        let record = Record::new_provider_record(self.id(), key.clone(), self.ctx.time());
-        self.file_storage.put(key.clone(), data);
-        self.dht_storage.put(key.clone(), record.clone());
-        self.put_value(record);
-        if CONFIG.enable_republishing {
-            self.ctx.emit_self(
+        self.put_file(key.clone(), data, true);
+        self.published_keys.insert(key.clone());
+        self.provide(key, TrafficOrigin::User)
+    }
+
+    /// Announces the peer as a provider of the data associated with the given key
+    /// by putting the provider record into the DHT.
+    /// The record is republished periodically if republishing is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the data to provide.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the initiated `PutValueQuery`.
+    fn provide(&mut self, key: Key, origin: TrafficOrigin) -> QueryId {
+        let expires_at = self.ctx.time() + self.env.config().record_expiration_interval;
+        let record = Record::new_provider_record(self.id(), key.clone(), expires_at);
+        self.put_record(key.clone(), record.clone());
+        if self.provided_keys.insert(key.clone()) && self.env.config().enable_republishing {
+            let event_id = self.ctx.emit_self(
                 RepublishTimer { key: key.clone() },
-                CONFIG.record_publication_interval,
+                self.env.config().record_publication_interval,
             );
+            self.republish_timers.insert(key.clone(), event_id);
         }
-        key
+        self.start_put_value(record, origin)
     }
 
     /// Removes the data associated with the given key from the IPFS network
@@ -248,13 +537,47 @@ impl Peer {
     ///
     /// * `key` - The key associated with the data to remove.
     pub fn remove_data(&mut self, key: Key) {
-        if let (Some(_), Some(_)) = (self.dht_storage.get(&key), self.file_storage.get(&key)) {
+        if self.file_storage.remove(&key) {
             self.log(Level::Info, &format!("Removed data by key \"{}\"", key));
             self.dht_storage.remove(&key);
-            self.file_storage.remove(&key);
+            self.stop_providing(&key);
         }
     }
 
+    /// Pins the data associated with the given key,
+    /// protecting it from eviction and garbage collection.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the data to pin.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the peer stores the data, `false` otherwise.
+    pub fn pin(&mut self, key: &Key) -> bool {
+        self.file_storage.pin(key)
+    }
+
+    /// Unpins the data associated with the given key,
+    /// so it can be evicted or garbage collected.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the data to unpin.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the peer stores the data, `false` otherwise.
+    pub fn unpin(&mut self, key: &Key) -> bool {
+        self.file_storage.unpin(key)
+    }
+
+    /// Returns `true` if the peer stores the data associated with the given key
+    /// and it is pinned.
+    pub fn is_pinned(&self, key: &Key) -> bool {
+        self.file_storage.is_pinned(key)
+    }
+
     /// Retrieves the data associated with the given key from IPFS network.
     ///
     /// # Arguments
@@ -270,10 +593,14 @@ impl Peer {
             &format!("Initiated retrieving data by key \"{}\"", key),
         );
         let query_id = self.get_value(key);
-        self.ctx
-            .emit_self(RetrieveDataQueryTimeout { query_id }, CONFIG.query_timeout);
-        self.queries.add_retrieve_data_query(query_id);
+        self.ctx.emit_self(
+            RetrieveDataQueryTimeout { query_id },
+            self.env.config().query_timeout,
+        );
+        self.queries
+            .add_retrieve_data_query(query_id, QueryProgress::new(self.ctx.time()));
         self.stats.retrieve_data_queries_started += 1;
+        self.trace_query(query_id, QueryKind::RetrieveData, QueryTransition::Started);
         query_id
     }
 
@@ -287,7 +614,7 @@ impl Peer {
     fn on_find_node_request(&mut self, src_id: PeerId, query_id: QueryId, key: Key) {
         let closest_peers = self
             .kbuckets
-            .local_closest_peers_approximate(&key, *K_VALUE);
+            .local_closest_peers_approximate(&key, self.env.k());
         self.send_message(
             FindNodeResponse {
                 query_id,
@@ -311,61 +638,162 @@ impl Peer {
         closest_peers: Vec<PeerId>,
     ) {
         if let Some(query) = self.queries.get_mut_find_node_query(query_id) {
-            match query.on_response(src_id, query_id, closest_peers) {
-                QueryState::InProgress(requests) => {
-                    for (dst, request) in requests {
-                        self.send_message(request, dst);
-                    }
+            let trigger = query.trigger();
+            let state = query.on_response(src_id, query_id, closest_peers);
+            self.on_find_node_query_state(query_id, trigger, state);
+        }
+    }
+
+    /// Handles a `FindNodeRequestTimeout` event.
+    ///
+    /// If the peer has not responded yet, it is marked as failed, which frees
+    /// the slot of the request for the next peer. The peer is also removed from
+    /// the k-buckets table if it is enabled in the configuration, and its late response
+    /// to the query does not add it back.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_id` - The ID of the query that made the request.
+    /// * `peer_id` - The ID of the peer the request was sent to.
+    fn on_find_node_request_timeout(&mut self, query_id: QueryId, peer_id: PeerId) {
+        if let Some(query) = self.queries.get_mut_find_node_query(query_id) {
+            let trigger = query.trigger();
+            if let Some(state) = query.on_request_timeout(peer_id, query_id) {
+                self.log(
+                    Level::Debug,
+                    &format!(
+                        "Request of FindNodeQuery with id={} to peer {} timed out",
+                        query_id, peer_id
+                    ),
+                );
+                self.stats.find_node_requests_timed_out += 1;
+                if self.env.config().evict_unresponsive_peers {
+                    self.kbuckets.remove_peer(peer_id);
+                    self.evicted_peers
+                        .entry(query_id)
+                        .or_default()
+                        .insert(peer_id);
                 }
-                QueryState::Completed((target_key, peers)) => {
-                    self.stats.evaluate(target_key, &peers);
+                self.on_find_node_query_state(query_id, trigger, state);
+            }
+        }
+    }
 
-                    for &id in peers.iter() {
-                        self.kbuckets.add_peer(id, self.ctx.time());
-                    }
+    /// Sends the requests of a `FindNodeQuery` and schedules their timeouts.
+    ///
+    /// # Arguments
+    ///
+    /// * `requests` - The pairs of destination peers and requests to send to them.
+    fn send_find_node_requests(&mut self, requests: Vec<(PeerId, FindNodeRequest)>) {
+        for (dst, request) in requests {
+            if let Some(timeout) = self.env.config().request_timeout {
+                self.ctx.emit_self(
+                    FindNodeRequestTimeout {
+                        query_id: request.query_id,
+                        peer_id: dst,
+                    },
+                    timeout,
+                );
+            }
+            self.send_message(request, dst);
+        }
+    }
+
+    /// Handles the new state of a `FindNodeQuery`.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_id` - The ID of the query.
+    /// * `trigger` - The trigger that initiated the query.
+    /// * `state` - The state of the query.
+    fn on_find_node_query_state(
+        &mut self,
+        query_id: QueryId,
+        trigger: QueryTrigger,
+        state: FindNodeQueryState,
+    ) {
+        match state {
+            QueryState::InProgress(requests) => {
+                self.send_find_node_requests(requests);
+            }
+            QueryState::Completed((target_key, peers)) => {
+                self.stats.evaluate(&self.env, target_key.clone(), &peers);
+
+                for &id in peers.iter() {
+                    self.kbuckets.add_peer(id, self.ctx.time());
+                }
 
-                    match query.trigger() {
-                        QueryTrigger::PutValue(query_id) => {
-                            if let Some(query) = self.queries.remove_put_value_query(query_id) {
-                                self.log(
-                                    Level::Debug,
-                                    &format!("Completed PutValueQuery with id={}", query_id),
+                let progress = match self.queries.remove_find_node_query(query_id) {
+                    Some(query) => query.progress(),
+                    None => return,
+                };
+                self.log(
+                    Level::Debug,
+                    &format!("Completed FindNodeQuery with id={}", query_id),
+                );
+                self.stats.find_node_queries_completed += 1;
+                self.record_query(
+                    query_id,
+                    QueryKind::FindNode,
+                    &progress,
+                    QueryOutcome::Completed,
+                    QueryResult::FindNode {
+                        key: target_key,
+                        closest_peers: peers.clone(),
+                    },
+                );
+
+                match trigger {
+                    QueryTrigger::PutValue(query_id) => {
+                        if let Some(mut query) = self.queries.remove_put_value_query(query_id) {
+                            self.log(
+                                Level::Debug,
+                                &format!("Completed PutValueQuery with id={}", query_id),
+                            );
+                            self.stats.put_value_queries_completed += 1;
+                            query.progress_mut().absorb(&progress);
+                            query.progress_mut().add_round(&peers);
+                            // the requests are sent before the query is recorded,
+                            // which forgets the origin of its traffic
+                            for &peer in peers.iter() {
+                                self.send_message(
+                                    PutValueRequest {
+                                        query_id,
+                                        key: query.key(),
+                                        record: query.record(),
+                                    },
+                                    peer,
                                 );
-                                self.stats.put_value_queries_completed += 1;
-                                for peer in peers {
-                                    self.send_message(
-                                        PutValueRequest {
-                                            key: query.key(),
-                                            record: query.record(),
-                                        },
-                                        peer,
-                                    );
-                                }
                             }
+                            self.record_query(
+                                query_id,
+                                QueryKind::PutValue,
+                                query.progress(),
+                                QueryOutcome::Completed,
+                                QueryResult::PutValue {
+                                    key: query.key(),
+                                    peers,
+                                },
+                            );
                         }
-                        QueryTrigger::GetValue(query_id) => {
-                            if let Some(query) = self.queries.get_mut_get_value_query(query_id) {
-                                let key = query.key();
-                                for peer in peers {
-                                    self.send_message(
-                                        GetValueRequest {
-                                            query_id,
-                                            key: key.clone(),
-                                        },
-                                        peer,
-                                    );
-                                }
+                    }
+                    QueryTrigger::GetValue(query_id) => {
+                        if let Some(query) = self.queries.get_mut_get_value_query(query_id) {
+                            query.progress_mut().absorb(&progress);
+                            query.progress_mut().add_round(&peers);
+                            let key = query.key();
+                            for peer in peers {
+                                self.send_message(
+                                    GetValueRequest {
+                                        query_id,
+                                        key: key.clone(),
+                                    },
+                                    peer,
+                                );
                             }
                         }
-                        _ => {}
                     }
-
-                    self.queries.remove_find_node_query(query_id);
-                    self.log(
-                        Level::Debug,
-                        &format!("Completed FindNodeQuery with id={}", query_id),
-                    );
-                    self.stats.find_node_queries_completed += 1;
+                    _ => {}
                 }
             }
         }
@@ -377,12 +805,21 @@ impl Peer {
     ///
     /// * `query_id` - The ID of the query to remove.
     fn on_find_node_query_timeout(&mut self, query_id: QueryId) {
-        if self.queries.remove_find_node_query(query_id).is_some() {
+        self.evicted_peers.remove(&query_id);
+        self.cancelled_queries.remove(&query_id);
+        if let Some(query) = self.queries.remove_find_node_query(query_id) {
             self.log(
                 Level::Warn,
                 &format!("FindNodeQuery with id={} timed out", query_id),
             );
             self.stats.find_node_queries_failed += 1;
+            self.record_query(
+                query_id,
+                QueryKind::FindNode,
+                &query.progress(),
+                QueryOutcome::Failed,
+                QueryResult::None,
+            );
         }
     }
 
@@ -394,22 +831,45 @@ impl Peer {
     /// * `query_id` - The ID of the query that made the request.
     /// * `key` - The key to get the value for.
     fn on_get_value_request(&mut self, src_id: PeerId, query_id: QueryId, key: Key) {
-        let record = self.dht_storage.get(&key).cloned();
+        let record = self.dht_storage.get(&key, self.ctx.time()).cloned();
+        if record.is_none() && self.dht_storage.has_expired(&key, self.ctx.time()) {
+            self.stats.stale_records_suppressed += 1;
+        }
         self.send_message(GetValueResponse { query_id, record }, src_id);
     }
 
     fn on_get_value_response(&mut self, src_id: PeerId, query_id: QueryId, record: Option<Record>) {
         if let Some(query) = self.queries.get_mut_get_value_query(query_id) {
-            match query.on_response(src_id, record) {
+            match query.on_response(query_id, src_id, record) {
                 QueryState::InProgress(()) => {}
                 QueryState::Completed((record, requests)) => {
+                    query.progress_mut().rpcs_sent += requests.len() as u32;
                     for (dst, request) in requests {
                         self.send_message(request, dst);
                     }
-                    self.queries.remove_get_value_query(query_id);
+                    let progress = match self.queries.remove_get_value_query(query_id) {
+                        Some(query) => query.progress().clone(),
+                        None => return,
+                    };
                     self.stats.get_value_queries_completed += 1;
+                    self.record_query(
+                        query_id,
+                        QueryKind::GetValue,
+                        &progress,
+                        QueryOutcome::Completed,
+                        QueryResult::GetValue {
+                            record: record.clone(),
+                        },
+                    );
                     match record.data {
                         RecordData::ProviderRecord { key, providers } => {
+                            let providers = self.choose_providers(&providers);
+                            if let Some(retrieve) =
+                                self.queries.get_mut_retrieve_data_query(query_id)
+                            {
+                                retrieve.absorb(&progress);
+                                retrieve.add_round(&providers);
+                            }
                             for provider in providers {
                                 self.send_message(
                                     RetrieveDataRequest {
@@ -426,18 +886,51 @@ impl Peer {
         }
     }
 
+    /// Chooses the unexpired providers to request the data from: all of them,
+    /// or up to `retrieve_max_providers` random ones if it is configured,
+    /// so that the load is spread among all the providers of popular data.
+    ///
+    /// # Arguments
+    ///
+    /// * `providers` - The providers listed in the provider record.
+    fn choose_providers(&self, providers: &[Provider]) -> Vec<PeerId> {
+        let now = self.ctx.time();
+        let mut providers = providers
+            .iter()
+            .filter(|provider| provider.expires_at > now)
+            .map(|provider| provider.id)
+            .collect::<Vec<_>>();
+        if let Some(max_providers) = self.env.config().retrieve_max_providers {
+            let count = providers.len().min(max_providers);
+            for i in 0..count {
+                let j = self.ctx.gen_range(i..providers.len());
+                providers.swap(i, j);
+            }
+            providers.truncate(count);
+        }
+        providers
+    }
+
     /// Removes a `GetValueQuery` from the pool of queries if it hasn't completed yet.
     ///
     /// # Arguments
     ///
     /// * `query_id` - The ID of the query to remove.
     fn on_get_value_query_timeout(&mut self, query_id: QueryId) {
-        if self.queries.remove_get_value_query(query_id).is_some() {
+        self.cancelled_queries.remove(&query_id);
+        if let Some(query) = self.queries.remove_get_value_query(query_id) {
             self.log(
                 Level::Warn,
                 &format!("GetValueQuery with id={} timed out", query_id),
             );
             self.stats.get_value_queries_failed += 1;
+            self.record_query(
+                query_id,
+                QueryKind::GetValue,
+                query.progress(),
+                QueryOutcome::Failed,
+                QueryResult::None,
+            );
         }
     }
 
@@ -448,7 +941,55 @@ impl Peer {
     /// * `key` - The key to put the value for.
     /// * `record` - The record to put.
     fn on_put_value_request(&mut self, key: Key, record: Record) {
-        self.dht_storage.put(key, record);
+        self.put_record(key, record);
+    }
+
+    /// Puts a record into the local DHT storage and counts the evicted records.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the record.
+    /// * `record` - The record to put.
+    fn put_record(&mut self, key: Key, record: Record) {
+        let evicted = self.dht_storage.put(key, record, self.ctx.time());
+        self.stats.dht_records_evicted += evicted.len() as u64;
+    }
+
+    /// Puts data into the local file storage and counts the evicted blocks.
+    /// The evicted blocks are no longer provided.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the data.
+    /// * `data` - The data to put.
+    /// * `pinned` - Whether the data is pinned.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the data was stored, `false` if it did not fit into the storage.
+    fn put_file(&mut self, key: Key, data: String, pinned: bool) -> bool {
+        let evicted = self.file_storage.put(key.clone(), data, pinned);
+        self.stats.file_blocks_evicted += evicted.len() as u64;
+        let stored = !evicted.contains(&key);
+        for key in evicted {
+            self.stop_providing(&key);
+        }
+        stored
+    }
+
+    /// Stops providing the data associated with the given key:
+    /// cancels its republication and removes the peer from the local provider record.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the data.
+    fn stop_providing(&mut self, key: &Key) {
+        if self.provided_keys.remove(key) {
+            if let Some(event_id) = self.republish_timers.remove(key) {
+                self.ctx.cancel_event(event_id);
+            }
+            self.dht_storage.remove_provider(key, self.id());
+        }
     }
 
     /// Removes a `PutValueQuery` from the pool of queries if it hasn't completed yet.
@@ -457,12 +998,20 @@ impl Peer {
     ///
     /// * `query_id` - The ID of the query to remove.
     fn on_put_value_query_timeout(&mut self, query_id: QueryId) {
-        if self.queries.remove_put_value_query(query_id).is_some() {
+        self.cancelled_queries.remove(&query_id);
+        if let Some(query) = self.queries.remove_put_value_query(query_id) {
             self.log(
                 Level::Warn,
                 &format!("PutValueQuery with id={} timed out", query_id),
             );
             self.stats.put_value_queries_failed += 1;
+            self.record_query(
+                query_id,
+                QueryKind::PutValue,
+                query.progress(),
+                QueryOutcome::Failed,
+                QueryResult::None,
+            );
         }
     }
 
@@ -475,6 +1024,7 @@ impl Peer {
     /// * `key` - The key to retrieve the data for.
     fn on_retrieve_data_request(&mut self, src_id: PeerId, query_id: QueryId, key: Key) {
         if let Some(data) = self.file_storage.get(&key) {
+            self.stats.retrieve_data_requests_served += 1;
             self.send_message(
                 RetrieveDataResponse {
                     query_id,
@@ -482,20 +1032,42 @@ impl Peer {
                 },
                 src_id,
             );
+        } else {
+            self.stats.retrieve_data_requests_missed += 1;
         }
     }
 
     /// Handles a `RetrieveDataResponse` message.
     ///
+    /// If enabled in the configuration, the retrieved data is cached in the file storage
+    /// and the peer announces itself as a provider of it.
+    ///
     /// # Arguments
     ///
     /// * `query_id` - The ID of the query that made the request.
     /// * `data` - The data retrieved.
     fn on_retrieve_data_response(&mut self, query_id: QueryId, data: Option<String>) {
         if let Some(data) = data {
-            if self.queries.remove_retrieve_data_query(query_id) {
+            if let Some(progress) = self.queries.remove_retrieve_data_query(query_id) {
                 self.stats.retrieve_data_queries_completed += 1;
+                self.record_query(
+                    query_id,
+                    QueryKind::RetrieveData,
+                    &progress,
+                    QueryOutcome::Completed,
+                    QueryResult::RetrieveData { data: data.clone() },
+                );
                 self.log(Level::Info, &format!("Data retrieved: {}", data));
+                if self.env.config().cache_retrieved_data {
+                    let key = Key::from_sha256(data.as_bytes());
+                    if !self.file_storage.contains(&key) && self.put_file(key.clone(), data, false)
+                    {
+                        self.stats.retrieved_blocks_cached += 1;
+                        if self.env.config().provide_retrieved_data {
+                            self.provide(key, TrafficOrigin::User);
+                        }
+                    }
+                }
             }
         }
     }
@@ -506,12 +1078,20 @@ impl Peer {
     ///
     /// * `query_id` - The ID of the query to remove.
     fn on_retrieve_data_query_timeout(&mut self, query_id: QueryId) {
-        if self.queries.remove_retrieve_data_query(query_id) {
+        self.cancelled_queries.remove(&query_id);
+        if let Some(progress) = self.queries.remove_retrieve_data_query(query_id) {
             self.log(
                 Level::Warn,
                 &format!("RetrieveDataQuery with id={} timed out", query_id),
             );
             self.stats.retrieve_data_queries_failed += 1;
+            self.record_query(
+                query_id,
+                QueryKind::RetrieveData,
+                &progress,
+                QueryOutcome::Failed,
+                QueryResult::None,
+            );
         }
     }
 
@@ -543,35 +1123,180 @@ impl Peer {
     ///
     /// The method also removes expired records from the DHT storage.
     fn refresh_kbuckets_table(&mut self) {
-        self.dht_storage.remove_expired(self.ctx.time());
+        let removed = self.dht_storage.remove_expired(
+            self.ctx.time(),
+            self.env.config().record_expiration_interval,
+        );
+        self.stats.expired_records_removed += removed as u64;
         for i in 0..self.kbuckets.buckets_count().min(15) {
             let key = Key::random_in_bucket(&self.ctx, self.kbuckets.local_key(), i);
             self.find_node(&key, QueryTrigger::Bootstrap);
         }
         let local_key = self.kbuckets.local_key();
         self.find_node(&local_key, QueryTrigger::Bootstrap);
-        self.ctx
-            .emit_self(BootstrapTimer {}, CONFIG.kbuckets_refresh_interval);
+        self.ctx.emit_self(
+            BootstrapTimer {},
+            self.env.config().kbuckets_refresh_interval,
+        );
     }
 
-    /// Republishes the record associated with the given key.
-    /// This method is called periodically to republish the record.
+    /// Republishes the provider record associated with the given key.
+    /// This method is called periodically to republish the record
+    /// while the peer still stores the data.
     ///
     /// # Arguments
     ///
     /// * `key` - The key associated with the record to republish.
     fn on_republish_timer(&mut self, key: Key) {
-        if let (Some(record), Some(_)) = (
-            self.dht_storage.get(&key).cloned(),
-            self.file_storage.get(&key),
-        ) {
-            self.dht_storage.remove(&key);
-            self.put_value(record.refreshed(self.ctx.time()));
-            self.ctx
-                .emit_self(RepublishTimer { key }, CONFIG.record_publication_interval);
+        self.republish_timers.remove(&key);
+        if self.provided_keys.remove(&key) && self.file_storage.contains(&key) {
+            self.provide(key, TrafficOrigin::Republish);
         }
     }
 
+    /// Removes expired records from the DHT storage.
+    ///
+    /// This method is called periodically, independently of the bootstrap.
+    fn on_expiration_sweep_timer(&mut self) {
+        let removed = self.dht_storage.remove_expired(
+            self.ctx.time(),
+            self.env.config().record_expiration_interval,
+        );
+        self.stats.expired_records_removed += removed as u64;
+        if let Some(interval) = self.env.config().record_expiration_sweep_interval {
+            self.ctx.emit_self(ExpirationSweepTimer {}, interval);
+        }
+    }
+
+    /// Frees the unpinned data if the file storage exceeds the watermark
+    /// and stops providing it.
+    ///
+    /// This method is called periodically to collect the garbage.
+    fn on_garbage_collection_timer(&mut self) {
+        let (freed_keys, freed_bytes) = self
+            .file_storage
+            .collect_garbage(self.env.config().gc_watermark_bytes);
+        self.stats.gc_runs += 1;
+        self.stats.gc_freed_blocks += freed_keys.len() as u64;
+        self.stats.gc_freed_bytes += freed_bytes as u64;
+        if !freed_keys.is_empty() {
+            self.log(
+                Level::Debug,
+                &format!("Garbage collected {} blocks", freed_keys.len()),
+            );
+        }
+        for key in freed_keys {
+            self.stop_providing(&key);
+        }
+        if let Some(interval) = self.env.config().gc_interval {
+            self.ctx.emit_self(GarbageCollectionTimer {}, interval);
+        }
+    }
+
+    /// Adds the record of a finished query to the statistics
+    /// and notifies the subscribers of the peer.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_id` - The ID of the query.
+    /// * `kind` - The kind of the query.
+    /// * `progress` - The progress of the query.
+    /// * `outcome` - The outcome of the query.
+    /// * `result` - The result of the query.
+    fn record_query(
+        &mut self,
+        query_id: QueryId,
+        kind: QueryKind,
+        progress: &QueryProgress,
+        outcome: QueryOutcome,
+        result: QueryResult,
+    ) {
+        let transition = match outcome {
+            QueryOutcome::Completed => QueryTransition::Completed,
+            QueryOutcome::Failed => QueryTransition::Failed,
+            QueryOutcome::Cancelled => QueryTransition::Cancelled,
+        };
+        self.trace_query(query_id, kind, transition);
+        if !self.queries.contains(query_id) {
+            self.query_origins.remove(&query_id);
+        }
+        let record = QueryRecord {
+            peer_id: self.id(),
+            query_id,
+            kind,
+            outcome,
+            started_at: progress.started_at,
+            finished_at: self.ctx.time(),
+            rpcs_sent: progress.rpcs_sent,
+            hops: progress.hops,
+            peers_contacted: progress.peers_contacted,
+        };
+        self.stats
+            .add_query_record(record, self.env.config().collect_query_records);
+        if !self.subscribers.is_empty() {
+            let event = QueryFinished {
+                peer_id: self.id(),
+                query_id,
+                kind,
+                outcome,
+                started_at: progress.started_at,
+                finished_at: self.ctx.time(),
+                result,
+            };
+            for &subscriber in self.subscribers.iter() {
+                self.ctx.emit_now(event.clone(), subscriber);
+            }
+        }
+    }
+
+    /// Returns the activity the message is attributed to.
+    /// Requests sent and responses received belong to the queries of the peer.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The description of the message.
+    /// * `sent` - Whether the message is sent or received by the peer.
+    fn traffic_origin(&self, message: &MessageInfo, sent: bool) -> TrafficOrigin {
+        if message.kind.is_request() != sent {
+            return TrafficOrigin::Serving;
+        }
+        message
+            .query_id
+            .and_then(|query_id| self.query_origins.get(&query_id).copied())
+            .unwrap_or(TrafficOrigin::Unknown)
+    }
+
+    /// Records the state transition of the query if the tracing is enabled.
+    fn trace_query(&self, query_id: QueryId, kind: QueryKind, transition: QueryTransition) {
+        if let Some(tracer) = &self.tracer {
+            tracer.record(TraceEvent::Query {
+                time: self.ctx.time(),
+                peer_id: self.ctx.id(),
+                query_id,
+                kind,
+                transition,
+            });
+        }
+    }
+
+    /// Checks if the event is a late response of a peer evicted
+    /// after its request of the same query timed out.
+    fn is_late_response_of_evicted(&self, event: &Event) -> bool {
+        event
+            .data
+            .downcast_ref::<FindNodeResponse>()
+            .and_then(|response| self.evicted_peers.get(&response.query_id))
+            .is_some_and(|evicted| evicted.contains(&event.src))
+    }
+
+    /// Checks if the message is a response to a cancelled query of the peer.
+    fn is_response_of_cancelled(&self, message: &MessageInfo) -> bool {
+        !message.kind.is_request()
+            && message
+                .query_id
+                .is_some_and(|id| self.cancelled_queries.contains(&id))
+    }
+
     /// Logs a message with the current time and the name of the peer.
     fn log(&self, level: Level, msg: &str) {
         log::log!(target: "simulation",level, "[{:.3} {}] {}", self.ctx.time(), self.ctx.name(), msg);
@@ -580,7 +1305,24 @@ impl Peer {
 
 impl EventHandler for Peer {
     fn on(&mut self, event: Event) {
-        self.kbuckets.add_peer(event.src, self.ctx.time());
+        if let Some(message) = MessageInfo::from_event_data(event.data.as_ref()) {
+            if !self.online || self.is_response_of_cancelled(&message) {
+                return;
+            }
+            let origin = self.traffic_origin(&message, false);
+            self.stats.traffic.add_received(&message, origin);
+            if let Some(tracer) = &self.tracer {
+                tracer.record(TraceEvent::Received {
+                    time: self.ctx.time(),
+                    src: event.src,
+                    dst: self.ctx.id(),
+                    message,
+                });
+            }
+        }
+        if self.env.is_dht_server(event.src) && !self.is_late_response_of_evicted(&event) {
+            self.kbuckets.add_peer(event.src, self.ctx.time());
+        }
 
         cast!(match event.data {
             FindNodeRequest { query_id, key } => {
@@ -595,6 +1337,9 @@ impl EventHandler for Peer {
             FindNodeQueryTimeout { query_id } => {
                 self.on_find_node_query_timeout(query_id);
             }
+            FindNodeRequestTimeout { query_id, peer_id } => {
+                self.on_find_node_request_timeout(query_id, peer_id);
+            }
             GetValueRequest { query_id, key } => {
                 self.on_get_value_request(event.src, query_id, key);
             }
@@ -604,7 +1349,7 @@ impl EventHandler for Peer {
             GetValueQueryTimeout { query_id } => {
                 self.on_get_value_query_timeout(query_id);
             }
-            PutValueRequest { key, record } => {
+            PutValueRequest { key, record, .. } => {
                 self.on_put_value_request(key, record);
             }
             PutValueQueryTimeout { query_id } => {
@@ -634,6 +1379,156 @@ impl EventHandler for Peer {
             RepublishTimer { key } => {
                 self.on_republish_timer(key);
             }
+            ExpirationSweepTimer {} => {
+                self.on_expiration_sweep_timer();
+            }
+            GarbageCollectionTimer {} => {
+                self.on_garbage_collection_timer();
+            }
         });
     }
 }
+
+/// Creates the peers of a simulation for the unit tests, connected by the given network
+/// and with their k-buckets filled.
+#[cfg(test)]
+pub(crate) fn test_peers(
+    sim: &mut Simulation,
+    config: crate::SimulationConfig,
+    network: NetworkAgent,
+) -> Vec<Rc<std::cell::RefCell<Peer>>> {
+    let env = Rc::new(Environment::new(config));
+    let peers = (0..env.config().num_peers)
+        .map(|i| {
+            let name = format!("peer-{}", i);
+            let peer = Rc::new(std::cell::RefCell::new(Peer::new(
+                sim,
+                env.clone(),
+                &name,
+                network.clone(),
+            )));
+            sim.add_handler(&name, peer.clone());
+            peer
+        })
+        .collect::<Vec<_>>();
+    for peer in peers.iter() {
+        peer.borrow_mut().fill_kbuckets_unfair();
+    }
+    peers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{environment::test_config, message::MessageKind};
+    use std::cell::Cell;
+
+    #[test]
+    fn test_late_response_of_evicted_peer() {
+        let mut config = test_config(16);
+        config.request_timeout = Some(1.);
+        config.evict_unresponsive_peers = true;
+        let mut sim = Simulation::new(0);
+        let slow = Rc::new(Cell::new(None));
+        let network = NetworkAgent::from_function({
+            let slow = slow.clone();
+            move |_, src, _| Some(if Some(src) == slow.get() { 10. } else { 0.1 })
+        });
+        let peers = test_peers(&mut sim, config, network);
+        let known = |peer_id| {
+            peers[0]
+                .borrow()
+                .kbuckets()
+                .entries()
+                .any(|(_, id, _)| id == peer_id)
+        };
+        let slow_id = peers[0]
+            .borrow()
+            .kbuckets()
+            .entries()
+            .map(|(_, id, _)| id)
+            .find(|&id| id != 0)
+            .unwrap();
+        slow.set(Some(slow_id));
+
+        let key = peers[0].borrow().env.peer_key(slow_id).clone();
+        peers[0].borrow_mut().find_node(&key, QueryTrigger::Manual);
+        sim.step_until_time(5.);
+        assert_eq!(
+            peers[0].borrow().peek_stats().find_node_requests_timed_out,
+            1
+        );
+        assert!(!known(slow_id));
+
+        // the response arrives after the timeout, but before the query times out
+        sim.step_until_time(20.);
+        assert!(!known(slow_id));
+    }
+
+    #[test]
+    fn test_publish_traffic_origin() {
+        let mut sim = Simulation::new(0);
+        let network = NetworkAgent::from_function(|_, _, _| Some(0.1));
+        let peers = test_peers(&mut sim, test_config(16), network);
+        peers[0].borrow_mut().publish_data("data".to_string());
+        sim.step_until_time(10.);
+
+        let peer = peers[0].borrow();
+        let traffic = &peer.peek_stats().traffic;
+        let stores = traffic.by_kind[&MessageKind::PutValueRequest].sent;
+        assert!(stores > 0);
+        assert!(traffic.by_origin[&TrafficOrigin::User].sent > stores);
+        assert!(!traffic.by_origin.contains_key(&TrafficOrigin::Unknown));
+    }
+
+    #[test]
+    fn test_retrieved_data_not_fitting_the_cache() {
+        let mut config = test_config(16);
+        config.cache_retrieved_data = true;
+        config.provide_retrieved_data = true;
+        config.file_storage_capacity.max_bytes = Some(2);
+        let mut sim = Simulation::new(0);
+        let network = NetworkAgent::from_function(|_, _, _| Some(0.1));
+        let peers = test_peers(&mut sim, config, network);
+        // the published data is pinned, so it is stored despite the capacity
+        peers[0].borrow_mut().publish_data("data".to_string());
+        sim.step_until_time(10.);
+        peers[1]
+            .borrow_mut()
+            .retrieve_data(Key::from_sha256("data".as_bytes()));
+        sim.step_until_time(20.);
+
+        let peer = peers[1].borrow();
+        assert_eq!(peer.peek_stats().retrieve_data_queries_completed, 1);
+        assert_eq!(peer.peek_stats().retrieved_blocks_cached, 0);
+        assert_eq!(peer.file_blocks_count(), 0);
+        assert_eq!(peer.provided_keys().count(), 0);
+    }
+
+    #[test]
+    fn test_late_responses_of_cancelled_query() {
+        let mut sim = Simulation::new(0);
+        let network = NetworkAgent::from_function(|_, _, _| Some(0.1));
+        let peers = test_peers(&mut sim, test_config(16), network);
+        let key = Key::from_sha256(b"key");
+        let query_id = peers[0].borrow_mut().find_node(&key, QueryTrigger::Manual);
+        let responses = |peer: &Peer| {
+            let traffic = &peer.peek_stats().traffic;
+            traffic
+                .by_kind
+                .get(&MessageKind::FindNodeResponse)
+                .map_or(0, |counter| counter.received)
+        };
+        sim.step_until_time(0.05);
+        assert!(peers[0].borrow_mut().cancel_query(query_id));
+        let received = responses(&peers[0].borrow());
+        sim.step_until_time(10.);
+
+        let peer = peers[0].borrow();
+        let traffic = &peer.peek_stats().traffic;
+        assert!(traffic.by_kind[&MessageKind::FindNodeRequest].sent > received);
+        assert_eq!(responses(&peer), received);
+        assert!(!traffic.by_origin.contains_key(&TrafficOrigin::Unknown));
+        assert_eq!(peer.peek_stats().find_node_queries_cancelled, 1);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{query::QueriesStats, storage::StorageCapacity};
+use serde::{Deserialize, Serialize};
+
+/// Represents the kind of peers sharing the same characteristics,
+/// e.g. cloud servers, home desktops or mobile devices.
+///
+/// In the configuration file, the profiles are listed in the `[[profiles]]` tables.
+/// The shares of the profiles must sum to 1.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PeerProfile {
+    pub name: String,
+    /// The fraction of the peers with the profile.
+    pub share: f64,
+    /// The relative weight of a peer when the peer of a user load event is chosen.
+    #[serde(default = "default_load_share")]
+    pub load_share: f64,
+    #[serde(default)]
+    pub dht_mode: DhtMode,
+    /// The bandwidth of the peer in bytes per second. The transfer of a message takes
+    /// its size divided by the lower bandwidth of the two peers. Unlimited if not specified.
+    pub bandwidth: Option<f64>,
+    /// The delay added to every message sent by the peer.
+    #[serde(default)]
+    pub processing_delay: f64,
+    /// The sessions of the peer. The peer is always online if it is not specified.
+    pub uptime: Option<Uptime>,
+    /// Override the capacities of the storages configured at the top level.
+    pub dht_storage_max_records: Option<usize>,
+    pub dht_storage_max_bytes: Option<usize>,
+    pub file_storage_max_records: Option<usize>,
+    pub file_storage_max_bytes: Option<usize>,
+}
+
+fn default_load_share() -> f64 {
+    1.
+}
+
+/// Represents the role of a peer in the DHT.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DhtMode {
+    /// The peer answers the DHT requests and is added to the routing tables of the other peers.
+    #[default]
+    Server,
+    /// The peer only makes DHT queries: it is never added to the routing tables
+    /// of the other peers, so it neither stores records nor routes the queries.
+    Client,
+}
+
+/// Represents the alternating online sessions and offline periods of a peer.
+/// Their durations are exponentially distributed with the given means.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Uptime {
+    pub session: f64,
+    pub downtime: f64,
+}
+
+impl Uptime {
+    /// Returns the long-run fraction of the time the peer is online.
+    pub fn availability(&self) -> f64 {
+        self.session / (self.session + self.downtime)
+    }
+}
+
+impl PeerProfile {
+    /// Returns the capacity of the DHT storage, taking the unspecified limits from the default.
+    pub fn dht_storage_capacity(&self, default: StorageCapacity) -> StorageCapacity {
+        StorageCapacity {
+            max_records: self.dht_storage_max_records.or(default.max_records),
+            max_bytes: self.dht_storage_max_bytes.or(default.max_bytes),
+        }
+    }
+
+    /// Returns the capacity of the file storage, taking the unspecified limits from the default.
+    pub fn file_storage_capacity(&self, default: StorageCapacity) -> StorageCapacity {
+        StorageCapacity {
+            max_records: self.file_storage_max_records.or(default.max_records),
+            max_bytes: self.file_storage_max_bytes.or(default.max_bytes),
+        }
+    }
+}
+
+/// Represents the statistics merged from the peers of a profile.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileStats {
+    /// The name of the profile.
+    pub name: String,
+    /// The number of the peers with the profile.
+    pub peers: usize,
+    pub stats: QueriesStats,
+}
+
+/// Assigns the profiles to the peers according to their shares.
+/// The profiles are interleaved, so that every range of peers gets about the same mix,
+/// and the number of the peers with a profile is within 1 of its share.
+///
+/// # Arguments
+///
+/// * `profiles` - The profiles, the shares must sum to 1.
+/// * `num_peers` - The number of the peers.
+///
+/// # Returns
+///
+/// The index of the profile of every peer, or an empty vector if there are no profiles.
+pub fn assign_profiles(profiles: &[PeerProfile], num_peers: usize) -> Vec<usize> {
+    if profiles.is_empty() {
+        return vec![];
+    }
+    // smooth weighted round-robin
+    let mut current = vec![0.; profiles.len()];
+    (0..num_peers)
+        .map(|_| {
+            for (value, profile) in current.iter_mut().zip(profiles.iter()) {
+                *value += profile.share;
+            }
+            let chosen = (0..profiles.len())
+                .max_by(|&a, &b| current[a].total_cmp(&current[b]).then(b.cmp(&a)))
+                .unwrap();
+            current[chosen] -= 1.;
+            chosen
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assign_profiles() {
+        let profile = |name: &str, share| PeerProfile {
+            name: name.to_string(),
+            share,
+            load_share: 1.,
+            dht_mode: DhtMode::Server,
+            bandwidth: None,
+            processing_delay: 0.,
+            uptime: None,
+            dht_storage_max_records: None,
+            dht_storage_max_bytes: None,
+            file_storage_max_records: None,
+            file_storage_max_bytes: None,
+        };
+        let profiles = [profile("server", 0.25), profile("desktop", 0.75)];
+        assert_eq!(assign_profiles(&profiles, 8), vec![1, 0, 1, 1, 1, 0, 1, 1]);
+        let counts = assign_profiles(&profiles, 1001)
+            .iter()
+            .filter(|&&i| i == 0)
+            .count();
+        assert!((250..=251).contains(&counts));
+        assert!(assign_profiles(&[], 10).is_empty());
+    }
+}
@@ -0,0 +1,40 @@
+use super::{QueryId, QueryKind, QueryOutcome};
+use crate::{storage::Record, Key, PeerId};
+use serde::Serialize;
+
+/// Represents the result of a finished query.
+#[derive(Debug, Clone, Serialize)]
+pub enum QueryResult {
+    /// The closest peers to the key found by a `FindNodeQuery`.
+    FindNode {
+        key: Key,
+        closest_peers: Vec<PeerId>,
+    },
+    /// The record found by a `GetValueQuery`.
+    GetValue { record: Record },
+    /// The peers the record was put to by a `PutValueQuery`.
+    PutValue { key: Key, peers: Vec<PeerId> },
+    /// The data retrieved by a `RetrieveData` query.
+    RetrieveData { data: String },
+    /// The query failed or was cancelled, so there is no result.
+    None,
+}
+
+/// Notification about a finished query that is sent to the subscribers of the peer.
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryFinished {
+    pub peer_id: PeerId,
+    pub query_id: QueryId,
+    pub kind: QueryKind,
+    pub outcome: QueryOutcome,
+    pub started_at: f64,
+    pub finished_at: f64,
+    pub result: QueryResult,
+}
+
+impl QueryFinished {
+    /// Returns the time elapsed between the start and the end of the query.
+    pub fn latency(&self) -> f64 {
+        self.finished_at - self.started_at
+    }
+}
@@ -0,0 +1,418 @@
+use super::QueryId;
+use crate::PeerId;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+/// Represents the kind of a query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum QueryKind {
+    FindNode,
+    GetValue,
+    PutValue,
+    RetrieveData,
+}
+
+/// Represents the outcome of a query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum QueryOutcome {
+    Completed,
+    Failed,
+    /// The query was cancelled before it finished.
+    Cancelled,
+}
+
+/// Represents the progress of a query that is accumulated while it is running.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct QueryProgress {
+    /// The time the query was started at.
+    pub started_at: f64,
+    /// The number of requests sent to other peers.
+    pub rpcs_sent: u32,
+    /// The number of lookup rounds, i.e. the length of the longest chain of requests.
+    pub hops: u32,
+    /// The number of distinct peers the requests were sent to.
+    pub peers_contacted: u32,
+    #[serde(skip)]
+    contacted: HashSet<PeerId>, // the peers the requests were sent to
+}
+
+impl QueryProgress {
+    /// Creates a new `QueryProgress` of a query started at the given time.
+    pub fn new(started_at: f64) -> Self {
+        Self {
+            started_at,
+            ..Default::default()
+        }
+    }
+
+    /// Adds the progress of a subquery that was executed as a part of this query.
+    /// The hops of the subquery precede the hops of this query.
+    /// The peers contacted by both queries are counted once.
+    pub fn absorb(&mut self, other: &QueryProgress) {
+        self.rpcs_sent += other.rpcs_sent;
+        self.hops += other.hops;
+        self.add_contacted(other.contacted.iter().copied());
+    }
+
+    /// Accounts one more round of requests sent to the given peers.
+    pub fn add_round(&mut self, peers: &[PeerId]) {
+        self.add_requests(peers);
+        self.hops += 1;
+    }
+
+    /// Accounts the requests sent to the given peers, without adding a round.
+    pub fn add_requests(&mut self, peers: &[PeerId]) {
+        self.rpcs_sent += peers.len() as u32;
+        self.add_contacted(peers.iter().copied());
+    }
+
+    fn add_contacted(&mut self, peers: impl IntoIterator<Item = PeerId>) {
+        self.contacted.extend(peers);
+        self.peers_contacted = self.contacted.len() as u32;
+    }
+}
+
+/// Represents the record of a finished query.
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryRecord {
+    pub peer_id: PeerId,
+    pub query_id: QueryId,
+    pub kind: QueryKind,
+    pub outcome: QueryOutcome,
+    pub started_at: f64,
+    pub finished_at: f64,
+    pub rpcs_sent: u32,
+    pub hops: u32,
+    pub peers_contacted: u32,
+}
+
+impl QueryRecord {
+    /// Returns the time elapsed between the start and the end of the query.
+    pub fn latency(&self) -> f64 {
+        self.finished_at - self.started_at
+    }
+}
+
+/// Collection of the records of finished queries.
+/// Its debug representation contains only the number of records.
+#[derive(Default, Clone, Serialize)]
+pub struct QueryRecords(pub Vec<QueryRecord>);
+
+impl std::fmt::Debug for QueryRecords {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("QueryRecords")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+/// Histogram of sampled values that provides their percentiles.
+///
+/// The samples are grouped into logarithmic buckets, each spanning values within
+/// `1 / BUCKETS_PER_DOUBLING` of each other, so the memory does not grow with the number
+/// of samples. A percentile is the mean of the samples in its bucket: it is exact if the bucket
+/// holds equal values, e.g. small integers such as hops, and is within 1% of the exact value
+/// otherwise. The count, the mean, the minimum and the maximum are always exact.
+#[derive(Default, Clone)]
+pub struct Histogram {
+    buckets: BTreeMap<i32, Bucket>, // indexed by the scaled logarithm of the values
+    count: usize,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Bucket {
+    count: usize,
+    sum: f64,
+}
+
+/// The number of buckets between a value and its double, i.e. the relative width of a bucket
+/// is about `ln(2) / BUCKETS_PER_DOUBLING`.
+const BUCKETS_PER_DOUBLING: f64 = 128.;
+
+impl Histogram {
+    /// Creates an empty `Histogram`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a sample to the histogram.
+    pub fn add(&mut self, value: f64) {
+        self.add_to_bucket(
+            Self::bucket_index(value),
+            Bucket {
+                count: 1,
+                sum: value,
+            },
+            value,
+            value,
+        );
+    }
+
+    /// Merges the samples of another histogram into this one.
+    pub fn merge(&mut self, other: &Self) {
+        for (&index, &bucket) in other.buckets.iter() {
+            self.add_to_bucket(index, bucket, other.min, other.max);
+        }
+    }
+
+    /// Returns the number of samples.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the mean of the samples, or `None` if there are no samples.
+    pub fn mean(&self) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.sum / self.count as f64)
+    }
+
+    /// Returns the percentile of the samples using the nearest-rank method,
+    /// or `None` if there are no samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `p` - The percentile in the range `[0, 100]`.
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        self.summary_percentiles(&[p]).map(|values| values[0])
+    }
+
+    /// Returns the summary of the samples.
+    pub fn summary(&self) -> HistogramSummary {
+        let percentiles = self.summary_percentiles(&[0., 50., 90., 99., 100.]);
+        HistogramSummary {
+            count: self.count(),
+            mean: self.mean(),
+            min: percentiles.as_ref().map(|p| p[0]),
+            p50: percentiles.as_ref().map(|p| p[1]),
+            p90: percentiles.as_ref().map(|p| p[2]),
+            p99: percentiles.as_ref().map(|p| p[3]),
+            max: percentiles.as_ref().map(|p| p[4]),
+        }
+    }
+
+    /// Returns the index of the bucket of the value.
+    /// The non-positive values share the first bucket.
+    fn bucket_index(value: f64) -> i32 {
+        if value > 0. {
+            (value.log2() * BUCKETS_PER_DOUBLING).floor() as i32
+        } else {
+            i32::MIN
+        }
+    }
+
+    /// Adds the samples to the bucket with the given index.
+    fn add_to_bucket(&mut self, index: i32, samples: Bucket, min: f64, max: f64) {
+        if samples.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            self.min = min;
+            self.max = max;
+        } else {
+            self.min = self.min.min(min);
+            self.max = self.max.max(max);
+        }
+        self.count += samples.count;
+        self.sum += samples.sum;
+        let bucket = self.buckets.entry(index).or_default();
+        bucket.count += samples.count;
+        bucket.sum += samples.sum;
+    }
+
+    /// Calculates several percentiles with a single pass over the buckets.
+    fn summary_percentiles(&self, ps: &[f64]) -> Option<Vec<f64>> {
+        if self.count == 0 {
+            return None;
+        }
+        let n = self.count;
+        Some(
+            ps.iter()
+                .map(|&p| {
+                    let rank = ((p / 100. * n as f64).ceil() as usize).clamp(1, n);
+                    if rank == 1 {
+                        return self.min;
+                    }
+                    if rank == n {
+                        return self.max;
+                    }
+                    let mut seen = 0;
+                    let bucket = self
+                        .buckets
+                        .values()
+                        .find(|bucket| {
+                            seen += bucket.count;
+                            seen >= rank
+                        })
+                        .unwrap();
+                    (bucket.sum / bucket.count as f64).clamp(self.min, self.max)
+                })
+                .collect(),
+        )
+    }
+}
+
+impl std::fmt::Debug for Histogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.summary().fmt(f)
+    }
+}
+
+impl Serialize for Histogram {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.summary().serialize(serializer)
+    }
+}
+
+/// Represents the summary of a histogram.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramSummary {
+    pub count: usize,
+    pub mean: Option<f64>,
+    pub min: Option<f64>,
+    pub p50: Option<f64>,
+    pub p90: Option<f64>,
+    pub p99: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// Histograms of the completed queries of a single kind.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct QueryKindMetrics {
+    pub latency: Histogram,
+    pub rpcs_sent: Histogram,
+    pub hops: Histogram,
+    pub peers_contacted: Histogram,
+}
+
+impl QueryKindMetrics {
+    /// Adds the record of a completed query to the histograms.
+    pub fn add(&mut self, record: &QueryRecord) {
+        self.latency.add(record.latency());
+        self.rpcs_sent.add(record.rpcs_sent as f64);
+        self.hops.add(record.hops as f64);
+        self.peers_contacted.add(record.peers_contacted as f64);
+    }
+
+    /// Merges the histograms of another instance into this one.
+    pub fn merge(&mut self, other: &Self) {
+        self.latency.merge(&other.latency);
+        self.rpcs_sent.merge(&other.rpcs_sent);
+        self.hops.merge(&other.hops);
+        self.peers_contacted.merge(&other.peers_contacted);
+    }
+}
+
+/// Histograms of the completed queries of every kind.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct QueryMetrics {
+    pub find_node: QueryKindMetrics,
+    pub get_value: QueryKindMetrics,
+    pub put_value: QueryKindMetrics,
+    pub retrieve_data: QueryKindMetrics,
+}
+
+impl QueryMetrics {
+    /// Adds the record of a finished query to the histograms of its kind.
+    /// Only completed queries are taken into account.
+    pub fn add(&mut self, record: &QueryRecord) {
+        if record.outcome == QueryOutcome::Completed {
+            self.kind_mut(record.kind).add(record);
+        }
+    }
+
+    /// Merges the histograms of another instance into this one.
+    pub fn merge(&mut self, other: &Self) {
+        self.find_node.merge(&other.find_node);
+        self.get_value.merge(&other.get_value);
+        self.put_value.merge(&other.put_value);
+        self.retrieve_data.merge(&other.retrieve_data);
+    }
+
+    /// Returns the histograms of the given kind of queries.
+    pub fn kind(&self, kind: QueryKind) -> &QueryKindMetrics {
+        match kind {
+            QueryKind::FindNode => &self.find_node,
+            QueryKind::GetValue => &self.get_value,
+            QueryKind::PutValue => &self.put_value,
+            QueryKind::RetrieveData => &self.retrieve_data,
+        }
+    }
+
+    fn kind_mut(&mut self, kind: QueryKind) -> &mut QueryKindMetrics {
+        match kind {
+            QueryKind::FindNode => &mut self.find_node,
+            QueryKind::GetValue => &mut self.get_value,
+            QueryKind::PutValue => &mut self.put_value,
+            QueryKind::RetrieveData => &mut self.retrieve_data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_percentiles() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.percentile(50.), None);
+        for i in (1..=100).rev() {
+            histogram.add(i as f64);
+        }
+        assert_eq!(histogram.percentile(50.), Some(50.));
+        assert_eq!(histogram.percentile(90.), Some(90.));
+        assert_eq!(histogram.percentile(99.), Some(99.));
+        assert_eq!(histogram.percentile(0.), Some(1.));
+        assert_eq!(histogram.percentile(100.), Some(100.));
+        assert_eq!(histogram.mean(), Some(50.5));
+    }
+
+    #[test]
+    fn test_histogram_merge() {
+        let mut a = Histogram::new();
+        a.add(1.);
+        let mut b = Histogram::new();
+        b.add(3.);
+        a.merge(&b);
+        let summary = a.summary();
+        assert_eq!(summary.count, 2);
+        assert_eq!(summary.min, Some(1.));
+        assert_eq!(summary.max, Some(3.));
+    }
+
+    #[test]
+    fn test_histogram_bounded() {
+        let mut histogram = Histogram::new();
+        for i in 1..=100_000 {
+            histogram.add(i as f64 * 0.001);
+        }
+        assert!(histogram.buckets.len() < 2500);
+        assert_eq!(histogram.count(), 100_000);
+        assert!((histogram.mean().unwrap() - 50.0005).abs() < 1e-6);
+        let p90 = histogram.percentile(90.).unwrap();
+        assert!((p90 - 90.).abs() < 0.9);
+        assert_eq!(histogram.percentile(100.), Some(100.));
+        histogram.add(0.);
+        assert_eq!(histogram.percentile(0.), Some(0.));
+    }
+
+    #[test]
+    fn test_progress_peers_contacted() {
+        let mut lookup = QueryProgress::new(0.);
+        lookup.add_requests(&[1, 2, 3]);
+        let mut progress = QueryProgress::new(0.);
+        progress.absorb(&lookup);
+        progress.add_round(&[2, 3, 4]);
+        assert_eq!(progress.rpcs_sent, 6);
+        assert_eq!(progress.peers_contacted, 4);
+        assert_eq!(progress.hops, 1);
+    }
+}
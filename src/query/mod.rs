@@ -1,7 +1,19 @@
+mod events;
+mod metrics;
 mod pool;
 mod stats;
+mod traffic;
 mod variants;
 
+pub use events::{QueryFinished, QueryResult};
+pub use metrics::{
+    Histogram, HistogramSummary, QueryKind, QueryKindMetrics, QueryMetrics, QueryOutcome,
+    QueryProgress, QueryRecord, QueryRecords,
+};
 pub use pool::{QueriesPool, QueryId};
 pub use stats::QueriesStats;
-pub use variants::{FindNodeQuery, GetValueQuery, PutValueQuery, QueryState, QueryTrigger};
+pub use traffic::{busiest_peers, MessageCounter, PeerTraffic, TrafficOrigin, TrafficStats};
+pub use variants::{
+    FindNodeQuery, FindNodeQueryState, GetValueQuery, LookupTermination, PutValueQuery, QueryState,
+    QueryTrigger,
+};
@@ -1,5 +1,5 @@
-use super::{FindNodeQuery, GetValueQuery, PutValueQuery};
-use std::collections::{HashMap, HashSet};
+use super::{FindNodeQuery, GetValueQuery, PutValueQuery, QueryProgress, QueryTrigger};
+use std::collections::HashMap;
 
 /// Represents a peer's pool of queries.
 #[derive(Debug, Default)]
@@ -8,7 +8,7 @@ pub struct QueriesPool {
     find_node_queries: HashMap<QueryId, FindNodeQuery>,
     get_value_queries: HashMap<QueryId, GetValueQuery>,
     put_value_queries: HashMap<QueryId, PutValueQuery>,
-    retrieve_data_queries: HashSet<QueryId>,
+    retrieve_data_queries: HashMap<QueryId, QueryProgress>,
 }
 
 /// Represents a unique identifier for a query.
@@ -21,6 +21,12 @@ impl std::fmt::Display for QueryId {
     }
 }
 
+impl From<u64> for QueryId {
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
 impl QueriesPool {
     /// Creates an empty `QueryPool` instance.
     pub fn new() -> Self {
@@ -34,6 +40,27 @@ impl QueriesPool {
         query_id
     }
 
+    /// Returns the number of queries of all kinds in the pool.
+    pub fn len(&self) -> usize {
+        self.find_node_queries.len()
+            + self.get_value_queries.len()
+            + self.put_value_queries.len()
+            + self.retrieve_data_queries.len()
+    }
+
+    /// Returns `true` if the pool contains a query of any kind with the given ID.
+    pub fn contains(&self, query_id: QueryId) -> bool {
+        self.find_node_queries.contains_key(&query_id)
+            || self.get_value_queries.contains_key(&query_id)
+            || self.put_value_queries.contains_key(&query_id)
+            || self.retrieve_data_queries.contains_key(&query_id)
+    }
+
+    /// Returns `true` if the pool contains no queries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Adds a `FindNodeQuery` to the pool.
     ///
     /// # Arguments
@@ -70,6 +97,25 @@ impl QueriesPool {
         self.find_node_queries.get_mut(&query_id)
     }
 
+    /// Returns the IDs of the `FindNodeQuery`s started by the query with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `parent_id` - The ID of the `GetValueQuery` or `PutValueQuery`.
+    pub fn find_node_queries_triggered_by(&self, parent_id: QueryId) -> Vec<QueryId> {
+        let mut query_ids = self
+            .find_node_queries
+            .iter()
+            .filter(|(_, query)| match query.trigger() {
+                QueryTrigger::GetValue(id) | QueryTrigger::PutValue(id) => id == parent_id,
+                QueryTrigger::Manual | QueryTrigger::Bootstrap => false,
+            })
+            .map(|(&query_id, _)| query_id)
+            .collect::<Vec<_>>();
+        query_ids.sort_unstable_by_key(|query_id| query_id.0);
+        query_ids
+    }
+
     /// Adds a `GetValueQuery` to the pool.
     ///
     /// # Arguments
@@ -134,8 +180,19 @@ impl QueriesPool {
     /// # Arguments
     ///
     /// * `query_id` - The ID of the query.
-    pub fn add_retrieve_data_query(&mut self, query_id: QueryId) {
-        self.retrieve_data_queries.insert(query_id);
+    /// * `progress` - The initial progress of the query.
+    pub fn add_retrieve_data_query(&mut self, query_id: QueryId, progress: QueryProgress) {
+        self.retrieve_data_queries.insert(query_id, progress);
+    }
+
+    /// Returns a mutable reference to the progress of the `RetrieveDataQuery`
+    /// with the specified query ID, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `query_id` - The ID of the query to retrieve.
+    pub fn get_mut_retrieve_data_query(&mut self, query_id: QueryId) -> Option<&mut QueryProgress> {
+        self.retrieve_data_queries.get_mut(&query_id)
     }
 
     /// Removes a `RetrieveDataQuery` from the pool.
@@ -146,8 +203,8 @@ impl QueriesPool {
     ///
     /// # Returns
     ///
-    /// `true` if the query was removed, `false` if the query did not exist.
-    pub fn remove_retrieve_data_query(&mut self, query_id: QueryId) -> bool {
+    /// The progress of the removed query, if it existed.
+    pub fn remove_retrieve_data_query(&mut self, query_id: QueryId) -> Option<QueryProgress> {
         self.retrieve_data_queries.remove(&query_id)
     }
 }
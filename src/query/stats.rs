@@ -1,26 +1,47 @@
-use super::variants::evaluate_closest_peers;
-use crate::{Key, PeerId};
+use super::{
+    variants::evaluate_closest_peers, QueryMetrics, QueryRecord, QueryRecords, TrafficStats,
+};
+use crate::{Environment, Key, PeerId};
 
 /// Struct to store statistics related to queries.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, serde::Serialize)]
 pub struct QueriesStats {
     pub closest_peers_total: u64,
     pub closest_peers_correct: u64,
     pub find_node_queries_started: u32,
     pub find_node_queries_completed: u32,
     pub find_node_queries_failed: u32,
+    pub find_node_queries_cancelled: u32,
+    pub find_node_requests_timed_out: u64,
     pub get_value_queries_started: u32,
     pub get_value_queries_completed: u32,
     pub get_value_queries_failed: u32,
+    pub get_value_queries_cancelled: u32,
     pub put_value_queries_started: u32,
     pub put_value_queries_completed: u32,
     pub put_value_queries_failed: u32,
+    pub put_value_queries_cancelled: u32,
     pub ping_requests_cnt: u32,
     pub ping_responses_cnt: u32,
     pub ping_requests_failed: u32,
     pub retrieve_data_queries_started: u32,
     pub retrieve_data_queries_completed: u32,
     pub retrieve_data_queries_failed: u32,
+    pub retrieve_data_queries_cancelled: u32,
+    pub dht_records_evicted: u64,
+    pub file_blocks_evicted: u64,
+    pub retrieve_data_requests_served: u64,
+    pub retrieve_data_requests_missed: u64,
+    pub retrieved_blocks_cached: u64,
+    pub gc_runs: u32,
+    pub gc_freed_blocks: u64,
+    pub gc_freed_bytes: u64,
+    pub stale_records_suppressed: u64,
+    pub expired_records_removed: u64,
+    pub traffic: TrafficStats,
+    pub metrics: QueryMetrics,
+    #[serde(skip)]
+    pub records: QueryRecords,
 }
 
 impl QueriesStats {
@@ -33,11 +54,57 @@ impl QueriesStats {
     ///
     /// # Arguments
     ///
+    /// * `env` - The environment of the simulation.
     /// * `target_key` - The key used in the query.
     /// * `peers` - The list of peers returned by the query.
-    pub fn evaluate(&mut self, target_key: Key, peers: &[PeerId]) {
+    pub fn evaluate(&mut self, env: &Environment, target_key: Key, peers: &[PeerId]) {
         self.closest_peers_total += peers.len() as u64;
-        self.closest_peers_correct += evaluate_closest_peers(target_key, peers) as u64;
+        self.closest_peers_correct += evaluate_closest_peers(env, target_key, peers) as u64;
+    }
+
+    /// Updates the statistics with the record of a finished query.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The record of the query.
+    /// * `keep_record` - Whether to keep the record itself in addition to the histograms.
+    pub fn add_query_record(&mut self, record: QueryRecord, keep_record: bool) {
+        self.metrics.add(&record);
+        if keep_record {
+            self.records.0.push(record);
+        }
+    }
+
+    /// Returns the total number of started queries of all kinds.
+    pub fn queries_started(&self) -> u64 {
+        self.find_node_queries_started as u64
+            + self.get_value_queries_started as u64
+            + self.put_value_queries_started as u64
+            + self.retrieve_data_queries_started as u64
+    }
+
+    /// Returns the total number of completed queries of all kinds.
+    pub fn queries_completed(&self) -> u64 {
+        self.find_node_queries_completed as u64
+            + self.get_value_queries_completed as u64
+            + self.put_value_queries_completed as u64
+            + self.retrieve_data_queries_completed as u64
+    }
+
+    /// Returns the total number of failed queries of all kinds.
+    pub fn queries_failed(&self) -> u64 {
+        self.find_node_queries_failed as u64
+            + self.get_value_queries_failed as u64
+            + self.put_value_queries_failed as u64
+            + self.retrieve_data_queries_failed as u64
+    }
+
+    /// Returns the total number of cancelled queries of all kinds.
+    pub fn queries_cancelled(&self) -> u64 {
+        self.find_node_queries_cancelled as u64
+            + self.get_value_queries_cancelled as u64
+            + self.put_value_queries_cancelled as u64
+            + self.retrieve_data_queries_cancelled as u64
     }
 
     /// Merges the statistics from another instance of `QueriesStats` into this one.
@@ -47,17 +114,35 @@ impl QueriesStats {
         self.find_node_queries_started += other.find_node_queries_started;
         self.find_node_queries_completed += other.find_node_queries_completed;
         self.find_node_queries_failed += other.find_node_queries_failed;
+        self.find_node_queries_cancelled += other.find_node_queries_cancelled;
+        self.find_node_requests_timed_out += other.find_node_requests_timed_out;
         self.get_value_queries_started += other.get_value_queries_started;
         self.get_value_queries_completed += other.get_value_queries_completed;
         self.get_value_queries_failed += other.get_value_queries_failed;
+        self.get_value_queries_cancelled += other.get_value_queries_cancelled;
         self.put_value_queries_started += other.put_value_queries_started;
         self.put_value_queries_completed += other.put_value_queries_completed;
         self.put_value_queries_failed += other.put_value_queries_failed;
+        self.put_value_queries_cancelled += other.put_value_queries_cancelled;
         self.ping_requests_cnt += other.ping_requests_cnt;
         self.ping_responses_cnt += other.ping_responses_cnt;
         self.ping_requests_failed += other.ping_requests_failed;
         self.retrieve_data_queries_started += other.retrieve_data_queries_started;
         self.retrieve_data_queries_completed += other.retrieve_data_queries_completed;
         self.retrieve_data_queries_failed += other.retrieve_data_queries_failed;
+        self.retrieve_data_queries_cancelled += other.retrieve_data_queries_cancelled;
+        self.dht_records_evicted += other.dht_records_evicted;
+        self.file_blocks_evicted += other.file_blocks_evicted;
+        self.retrieve_data_requests_served += other.retrieve_data_requests_served;
+        self.retrieve_data_requests_missed += other.retrieve_data_requests_missed;
+        self.retrieved_blocks_cached += other.retrieved_blocks_cached;
+        self.gc_runs += other.gc_runs;
+        self.gc_freed_blocks += other.gc_freed_blocks;
+        self.gc_freed_bytes += other.gc_freed_bytes;
+        self.stale_records_suppressed += other.stale_records_suppressed;
+        self.expired_records_removed += other.expired_records_removed;
+        self.traffic.merge(&other.traffic);
+        self.metrics.merge(&other.metrics);
+        self.records.0.extend_from_slice(&other.records.0);
     }
 }
@@ -0,0 +1,166 @@
+use crate::{
+    message::{MessageInfo, MessageKind},
+    PeerId,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Represents the activity that caused a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum TrafficOrigin {
+    /// Requests and responses of the queries started by the user.
+    User,
+    /// Requests and responses of the k-buckets table refreshes.
+    Bootstrap,
+    /// Requests and responses of the provider record republishing.
+    Republish,
+    /// Requests received from other peers and the responses to them.
+    Serving,
+    /// Responses that arrived after their query had finished.
+    Unknown,
+}
+
+/// Counters of the messages and their sizes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MessageCounter {
+    pub sent: u64,
+    pub received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+impl MessageCounter {
+    /// Returns the number of messages both sent and received.
+    pub fn messages(&self) -> u64 {
+        self.sent + self.received
+    }
+
+    /// Merges the counters of another instance into this one.
+    pub fn merge(&mut self, other: &Self) {
+        self.sent += other.sent;
+        self.received += other.received;
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+    }
+}
+
+/// Statistics of the messages sent and received, grouped by their kind and origin.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct TrafficStats {
+    pub total: MessageCounter,
+    pub by_kind: BTreeMap<MessageKind, MessageCounter>,
+    pub by_origin: BTreeMap<TrafficOrigin, MessageCounter>,
+}
+
+impl TrafficStats {
+    /// Accounts a sent message.
+    pub fn add_sent(&mut self, message: &MessageInfo, origin: TrafficOrigin) {
+        for counter in self.counters(message.kind, origin) {
+            counter.sent += 1;
+            counter.bytes_sent += message.size as u64;
+        }
+    }
+
+    /// Accounts a received message.
+    pub fn add_received(&mut self, message: &MessageInfo, origin: TrafficOrigin) {
+        for counter in self.counters(message.kind, origin) {
+            counter.received += 1;
+            counter.bytes_received += message.size as u64;
+        }
+    }
+
+    /// Merges the statistics of another instance into this one.
+    pub fn merge(&mut self, other: &Self) {
+        self.total.merge(&other.total);
+        for (kind, counter) in other.by_kind.iter() {
+            self.by_kind.entry(*kind).or_default().merge(counter);
+        }
+        for (origin, counter) in other.by_origin.iter() {
+            self.by_origin.entry(*origin).or_default().merge(counter);
+        }
+    }
+
+    fn counters(&mut self, kind: MessageKind, origin: TrafficOrigin) -> [&mut MessageCounter; 3] {
+        [
+            &mut self.total,
+            self.by_kind.entry(kind).or_default(),
+            self.by_origin.entry(origin).or_default(),
+        ]
+    }
+}
+
+/// Represents the traffic of a single peer.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerTraffic {
+    pub peer_id: PeerId,
+    #[serde(flatten)]
+    pub counter: MessageCounter,
+}
+
+/// Selects the peers that sent and received the most messages.
+/// Ties are broken by the peer ID.
+///
+/// # Arguments
+///
+/// * `peers` - The traffic of all the peers.
+/// * `count` - The number of the peers to select.
+pub fn busiest_peers(mut peers: Vec<PeerTraffic>, count: usize) -> Vec<PeerTraffic> {
+    peers.sort_unstable_by_key(|p| (std::cmp::Reverse(p.counter.messages()), p.peer_id));
+    peers.truncate(count);
+    peers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(kind: MessageKind, size: usize) -> MessageInfo {
+        MessageInfo {
+            kind,
+            query_id: None,
+            size,
+        }
+    }
+
+    #[test]
+    fn test_traffic_stats() {
+        let mut stats = TrafficStats::default();
+        stats.add_sent(
+            &message(MessageKind::FindNodeRequest, 40),
+            TrafficOrigin::Bootstrap,
+        );
+        stats.add_received(
+            &message(MessageKind::FindNodeResponse, 88),
+            TrafficOrigin::Bootstrap,
+        );
+        let mut other = TrafficStats::default();
+        other.add_sent(
+            &message(MessageKind::FindNodeRequest, 40),
+            TrafficOrigin::User,
+        );
+        stats.merge(&other);
+
+        assert_eq!(stats.total.messages(), 3);
+        assert_eq!(stats.total.bytes_sent, 80);
+        assert_eq!(stats.by_kind[&MessageKind::FindNodeRequest].sent, 2);
+        assert_eq!(
+            stats.by_origin[&TrafficOrigin::Bootstrap].bytes_received,
+            88
+        );
+        assert_eq!(stats.by_origin[&TrafficOrigin::User].sent, 1);
+    }
+
+    #[test]
+    fn test_busiest_peers() {
+        let peer = |peer_id, sent| PeerTraffic {
+            peer_id,
+            counter: MessageCounter {
+                sent,
+                ..Default::default()
+            },
+        };
+        let busiest = busiest_peers(vec![peer(0, 1), peer(1, 5), peer(2, 5), peer(3, 2)], 3);
+        let ids = busiest.iter().map(|p| p.peer_id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+}
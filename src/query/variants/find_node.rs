@@ -1,11 +1,15 @@
-use super::{QueryState, QueryTrigger};
+use super::{LookupTermination, QueryState, QueryTrigger};
 use crate::{
-    message::FindNodeRequest, query::QueryId, Distance, Key, PeerId, ALPHA_VALUE, KEYS_TREE,
-    K_VALUE,
+    message::FindNodeRequest,
+    query::{QueryId, QueryProgress},
+    Distance, Environment, Key, PeerId,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
 };
-use std::collections::HashSet;
 
-type FindNodeQueryState = QueryState<Vec<(PeerId, FindNodeRequest)>, (Key, Vec<PeerId>)>;
+pub type FindNodeQueryState = QueryState<Vec<(PeerId, FindNodeRequest)>, (Key, Vec<PeerId>)>;
 
 /// Represents a query to find the closest peers to a target key.
 ///
@@ -13,12 +17,20 @@ type FindNodeQueryState = QueryState<Vec<(PeerId, FindNodeRequest)>, (Key, Vec<P
 /// and evaluate the query to calculate the correctness of the results.
 #[derive(Debug)]
 pub struct FindNodeQuery {
+    env: Rc<Environment>,
     trigger: QueryTrigger,
     target_key: Key,
-    peers_all: HashSet<PeerId>,   // waiting + responded + next
+    peers_all: HashSet<PeerId>, // every known peer, including the ones too many hops away
     peers_responded: Vec<PeerId>, // sorted by distance to target in descending order
     peers_waiting: Vec<PeerId>,
-    peers_next: Vec<PeerId>, // sorted by distance to target in descending order
+    peers_failed: HashSet<PeerId>,     // did not respond in time
+    peers_next: Vec<PeerId>,           // sorted by distance to target in descending order
+    hops: HashMap<PeerId, u32>,        // number of hops from the initiator to every known peer
+    peers_requested: Vec<PeerId>,      // every peer is requested at most once
+    closest_distance: Distance,        // distance of the closest known peer
+    responses_without_progress: usize, // consecutive responses with no closer peer
+    max_hop: u32,                      // max number of hops to a responded peer
+    started_at: f64,
 }
 
 impl FindNodeQuery {
@@ -26,19 +38,23 @@ impl FindNodeQuery {
     ///
     /// # Arguments
     ///
+    /// * `env` - The environment of the simulation.
     /// * `query_id` - The ID of the query.
     /// * `trigger` - The trigger that initiated the query.
     /// * `target_key` - The key to find the closest peers to.
     /// * `self_id` - The ID of the peer that initiated the query.
+    /// * `curr_time` - The current simulation time.
     ///
     /// # Returns
     ///
     /// A tuple containing the query and the request to send to itself.
     pub fn new(
+        env: Rc<Environment>,
         query_id: QueryId,
         trigger: QueryTrigger,
         target_key: Key,
         self_id: PeerId,
+        curr_time: f64,
     ) -> (FindNodeQuery, FindNodeRequest) {
         let query = FindNodeQuery {
             trigger,
@@ -46,11 +62,19 @@ impl FindNodeQuery {
             peers_all: HashSet::from_iter([self_id]),
             peers_responded: vec![],
             peers_waiting: {
-                let mut v = Vec::with_capacity(*ALPHA_VALUE);
+                let mut v = Vec::with_capacity(env.alpha());
                 v.push(self_id);
                 v
             },
             peers_next: vec![],
+            peers_failed: HashSet::new(),
+            hops: HashMap::from_iter([(self_id, 0)]),
+            peers_requested: vec![],
+            closest_distance: env.peer_key(self_id).distance(&target_key),
+            responses_without_progress: 0,
+            max_hop: 0,
+            started_at: curr_time,
+            env,
         };
         let request = FindNodeRequest {
             query_id,
@@ -64,6 +88,15 @@ impl FindNodeQuery {
         self.trigger.clone()
     }
 
+    /// Returns the progress of the query.
+    /// The initial request to the peer itself is not counted.
+    pub fn progress(&self) -> QueryProgress {
+        let mut progress = QueryProgress::new(self.started_at);
+        progress.add_requests(&self.peers_requested);
+        progress.hops = self.max_hop;
+        progress
+    }
+
     /// Handles a response from a peer.
     ///
     /// # Arguments
@@ -99,8 +132,21 @@ impl FindNodeQuery {
             }
         }
 
+        self.max_hop = self.max_hop.max(self.hops[&src_id]);
+        let hop = self.hops[&src_id] + 1;
+        let mut made_progress = false;
         for &peer_next in closest_peers.iter() {
             if self.peers_all.insert(peer_next) {
+                self.hops.insert(peer_next, hop);
+                if !self.within_max_hops(peer_next) {
+                    // the peer is known but never queried, so it does not hold the query back
+                    continue;
+                }
+                let dist = self.env.peer_key(peer_next).distance(&self.target_key);
+                if dist < self.closest_distance {
+                    self.closest_distance = dist;
+                    made_progress = true;
+                }
                 match self
                     .peers_next
                     .binary_search_by_key(&key_func(&peer_next), &key_func)
@@ -113,12 +159,52 @@ impl FindNodeQuery {
             }
         }
 
+        if made_progress {
+            self.responses_without_progress = 0;
+        } else {
+            self.responses_without_progress += 1;
+        }
+        self.next_state(query_id)
+    }
+
+    /// Handles a timeout of the request sent to a peer.
+    /// The peer is marked as failed and is excluded from the results,
+    /// even if it responds later.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_id` - The ID of the peer that did not respond in time.
+    /// * `query_id` - The ID of the query associated with the request.
+    ///
+    /// # Returns
+    ///
+    /// The new state of the query, or `None` if the peer has already responded.
+    pub fn on_request_timeout(
+        &mut self,
+        peer_id: PeerId,
+        query_id: QueryId,
+    ) -> Option<FindNodeQueryState> {
+        let idx = self.peers_waiting.iter().position(|&id| id == peer_id)?;
+        self.peers_waiting.swap_remove(idx);
+        self.peers_failed.insert(peer_id);
+        Some(self.next_state(query_id))
+    }
+
+    /// Returns the number of peers that did not respond in time.
+    pub fn failed_count(&self) -> usize {
+        self.peers_failed.len()
+    }
+
+    /// Checks if the query is completed and otherwise
+    /// fills the free request slots with the next peers.
+    fn next_state(&mut self, query_id: QueryId) -> FindNodeQueryState {
         if let Some(peers) = self.check_if_completed() {
             return QueryState::Completed((self.target_key.clone(), peers));
         }
         let mut result = vec![];
-        while self.peers_waiting.len() < *ALPHA_VALUE {
+        while self.peers_waiting.len() < self.env.alpha() && self.may_send_request() {
             if let Some(peer_id) = self.pop_next_peer() {
+                self.peers_requested.push(peer_id);
                 let request = FindNodeRequest {
                     query_id,
                     key: self.target_key.clone(),
@@ -137,36 +223,92 @@ impl FindNodeQuery {
     ///
     /// The ID of the next peer, if it exists.
     fn pop_next_peer(&mut self) -> Option<PeerId> {
-        let next_peer = self.peers_next.pop();
-        if let Some(peer_id) = next_peer {
-            self.peers_waiting.push(peer_id);
+        let peer_id = self.peers_next.pop()?;
+        self.peers_waiting.push(peer_id);
+        Some(peer_id)
+    }
+
+    /// Checks if the peer is close enough to the initiator to be queried
+    /// according to the termination policy.
+    fn within_max_hops(&self, peer_id: PeerId) -> bool {
+        match self.env.config().lookup_termination {
+            LookupTermination::MaxHops { max_hops } => self.hops[&peer_id] <= max_hops,
+            _ => true,
+        }
+    }
+
+    /// Checks if one more request may be sent according to the termination policy.
+    fn may_send_request(&self) -> bool {
+        match self.env.config().lookup_termination {
+            LookupTermination::MaxRequests { max_requests } => {
+                self.peers_requested.len() < max_requests
+            }
+            _ => true,
         }
-        next_peer
     }
 
-    /// Checks if the query is completed and returns the list
-    /// of closest peers if so.
+    /// Checks if the query is completed according to the termination policy
+    /// and returns the list of closest peers if so.
     fn check_if_completed(&mut self) -> Option<Vec<PeerId>> {
+        if self.peers_waiting.is_empty() && (self.peers_next.is_empty() || !self.may_send_request())
+        {
+            return Some(self.take_closest_responded());
+        }
+        let completed = match self.env.config().lookup_termination {
+            LookupTermination::KClosestResponded
+            | LookupTermination::MaxHops { .. }
+            | LookupTermination::MaxRequests { .. } => self.k_closest_responded(),
+            LookupTermination::NoCloserPeers => self.responses_without_progress >= self.env.alpha(),
+            LookupTermination::Beta { beta } => self.beta_closest_responded(beta),
+        };
+        if completed {
+            Some(self.take_closest_responded())
+        } else {
+            None
+        }
+    }
+
+    /// Checks if the k closest responded peers are all closer
+    /// than the closest unqueried peer.
+    fn k_closest_responded(&self) -> bool {
+        if self.peers_responded.len() < self.env.k() {
+            return false;
+        }
         let key_func = self.key_func();
-        if self.peers_responded.len() >= *K_VALUE {
-            if let Some(&peer_id) = self.peers_next.last() {
-                let i = self.peers_responded.len() - *K_VALUE;
-                if key_func(&peer_id) < key_func(&self.peers_responded[i]) {
-                    let ans = self.peers_responded.split_off(i);
-                    return Some(ans);
-                }
-            }
-        } else if self.peers_waiting.is_empty() && self.peers_next.is_empty() {
-            return Some(std::mem::take(&mut self.peers_responded));
+        let i = self.peers_responded.len() - self.env.k();
+        match self.peers_next.last() {
+            Some(peer_id) => key_func(peer_id) < key_func(&self.peers_responded[i]),
+            None => false,
         }
-        None
+    }
+
+    /// Checks if the `beta` closest known peers have all responded.
+    fn beta_closest_responded(&self, beta: usize) -> bool {
+        if beta == 0 || self.peers_responded.len() < beta {
+            return false;
+        }
+        let key_func = self.key_func();
+        let threshold = key_func(&self.peers_responded[self.peers_responded.len() - beta]);
+        self.peers_next
+            .last()
+            .iter()
+            .copied()
+            .chain(self.peers_waiting.iter())
+            .all(|peer_id| key_func(peer_id) < threshold)
+    }
+
+    /// Takes up to k closest responded peers.
+    fn take_closest_responded(&mut self) -> Vec<PeerId> {
+        let i = self.peers_responded.len().saturating_sub(self.env.k());
+        self.peers_responded.split_off(i)
     }
 
     /// Returns a key function for sorting peers by distance to the target key
     /// in descending order.
     fn key_func(&self) -> impl Fn(&PeerId) -> Distance {
+        let env = self.env.clone();
         let target_key = self.target_key.clone();
-        move |&peer_id| !Key::from_peer_id(peer_id).distance(&target_key)
+        move |&peer_id| !env.peer_key(peer_id).distance(&target_key)
     }
 }
 
@@ -175,16 +317,189 @@ impl FindNodeQuery {
 ///
 /// # Arguments
 ///
+/// * `env` - The environment of the simulation.
 /// * `target_key` - The key used in the query.
 /// * `result` - The list of peers returned by the query.
 ///
 /// # Returns
 ///
 /// The number of peers that are included in the correct answer.
-pub fn evaluate_closest_peers(target_key: Key, result: &[PeerId]) -> usize {
-    let correct_result = KEYS_TREE.find_closest_peers(&target_key, result.len());
+pub fn evaluate_closest_peers(env: &Environment, target_key: Key, result: &[PeerId]) -> usize {
+    let correct_result = env.find_closest_peers(&target_key, result.len());
     result
         .iter()
         .filter(|&id| correct_result.contains(id))
         .count()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::test_config;
+    use std::collections::VecDeque;
+
+    fn lookup_env(lookup_termination: LookupTermination) -> Rc<Environment> {
+        let mut config = test_config(64);
+        config.k = 4;
+        config.alpha = 2;
+        config.lookup_termination = lookup_termination;
+        Rc::new(Environment::new(config))
+    }
+
+    /// Runs a lookup from peer 0, answering the requests in the order they are sent.
+    fn lookup(
+        env: &Rc<Environment>,
+        target_key: &Key,
+        respond: impl Fn(PeerId) -> Vec<PeerId>,
+    ) -> (Vec<PeerId>, QueryProgress) {
+        let query_id = QueryId::from(0);
+        let (mut query, _) = FindNodeQuery::new(
+            env.clone(),
+            query_id,
+            QueryTrigger::Manual,
+            target_key.clone(),
+            0,
+            0.,
+        );
+        let mut waiting = VecDeque::from([0]);
+        while let Some(peer_id) = waiting.pop_front() {
+            match query.on_response(peer_id, query_id, respond(peer_id)) {
+                QueryState::Completed((_, peers)) => return (peers, query.progress()),
+                QueryState::InProgress(requests) => {
+                    waiting.extend(requests.into_iter().map(|(id, _)| id))
+                }
+            }
+        }
+        panic!("the query is neither completed nor waiting for a response");
+    }
+
+    /// Every peer returns the peers closest to the target key in the whole network.
+    fn closest_peers(env: &Environment, target_key: &Key) -> Vec<PeerId> {
+        let mut peers = env
+            .find_closest_peers(target_key, env.k())
+            .into_iter()
+            .collect::<Vec<_>>();
+        peers.sort();
+        peers
+    }
+
+    /// Every peer `p` knows the peers `4p + 1..=4p + 4`, so the hops form a tree.
+    fn tree(peer_id: PeerId) -> Vec<PeerId> {
+        (peer_id * 4 + 1..=peer_id * 4 + 4)
+            .filter(|&id| id < 64)
+            .collect()
+    }
+
+    #[test]
+    fn test_k_closest_responded() {
+        let env = lookup_env(LookupTermination::KClosestResponded);
+        let target_key = Key::from_sha256(b"target");
+        let closest = closest_peers(&env, &target_key);
+        let (peers, _) = lookup(&env, &target_key, |_| closest.clone());
+        assert_eq!(evaluate_closest_peers(&env, target_key, &peers), env.k());
+    }
+
+    #[test]
+    fn test_no_closer_peers() {
+        let env = lookup_env(LookupTermination::NoCloserPeers);
+        let target_key = Key::from_sha256(b"target");
+        let closest = closest_peers(&env, &target_key);
+        let (peers, progress) = lookup(&env, &target_key, |_| closest.clone());
+        // the closest peers are found by the first response, and the lookup stops
+        // after `alpha` more responses revealing nothing closer, one slot being refilled
+        assert_eq!(progress.rpcs_sent, env.alpha() as u32 + 1);
+        assert_eq!(
+            evaluate_closest_peers(&env, target_key, &peers),
+            env.alpha()
+        );
+    }
+
+    #[test]
+    fn test_beta() {
+        let env = lookup_env(LookupTermination::Beta { beta: 2 });
+        let target_key = Key::from_sha256(b"target");
+        let closest = closest_peers(&env, &target_key);
+        let (peers, progress) = lookup(&env, &target_key, |_| closest.clone());
+        assert!(progress.rpcs_sent >= 2);
+        let two_closest = env.find_closest_peers(&target_key, 2);
+        assert!(two_closest.iter().all(|id| peers.contains(id)));
+    }
+
+    #[test]
+    fn test_max_hops() {
+        let env = lookup_env(LookupTermination::MaxHops { max_hops: 1 });
+        let target_key = Key::from_sha256(b"target");
+        let (peers, progress) = lookup(&env, &target_key, tree);
+        assert_eq!(progress.hops, 1);
+        assert_eq!(progress.rpcs_sent, 4);
+        assert!(peers.iter().all(|&id| id <= 4));
+
+        // the peers returned by the initiator itself are already out of range
+        let env = lookup_env(LookupTermination::MaxHops { max_hops: 0 });
+        let (peers, progress) = lookup(&env, &target_key, tree);
+        assert_eq!(peers, vec![0]);
+        assert_eq!(progress.rpcs_sent, 0);
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let env = lookup_env(LookupTermination::KClosestResponded);
+        let target_key = Key::from_sha256(b"target");
+        let query_id = QueryId::from(0);
+        let (mut query, _) = FindNodeQuery::new(
+            env.clone(),
+            query_id,
+            QueryTrigger::Manual,
+            target_key,
+            0,
+            0.,
+        );
+        let QueryState::InProgress(requests) = query.on_response(0, query_id, tree(0)) else {
+            panic!("the query is completed by the first response");
+        };
+        assert_eq!(requests.len(), env.alpha());
+        let mut waiting = requests
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<VecDeque<_>>();
+        let failed = waiting.pop_front().unwrap();
+
+        // the slot of the timed out request is given to the next peer
+        let Some(QueryState::InProgress(requests)) = query.on_request_timeout(failed, query_id)
+        else {
+            panic!("the timeout is ignored");
+        };
+        assert_eq!(requests.len(), 1);
+        waiting.extend(requests.into_iter().map(|(id, _)| id));
+        assert_eq!(query.failed_count(), 1);
+        assert!(query.on_request_timeout(failed, query_id).is_none());
+
+        // the late response is ignored, and the peer is excluded from the results
+        let QueryState::InProgress(requests) = query.on_response(failed, query_id, tree(failed))
+        else {
+            panic!("the late response completes the query");
+        };
+        assert!(requests.is_empty());
+        while let Some(peer_id) = waiting.pop_front() {
+            match query.on_response(peer_id, query_id, tree(peer_id)) {
+                QueryState::Completed((_, peers)) => {
+                    assert!(!peers.contains(&failed));
+                    return;
+                }
+                QueryState::InProgress(requests) => {
+                    waiting.extend(requests.into_iter().map(|(id, _)| id))
+                }
+            }
+        }
+        panic!("the query is neither completed nor waiting for a response");
+    }
+
+    #[test]
+    fn test_max_requests() {
+        let env = lookup_env(LookupTermination::MaxRequests { max_requests: 3 });
+        let target_key = Key::from_sha256(b"target");
+        let (peers, progress) = lookup(&env, &target_key, tree);
+        assert_eq!(progress.rpcs_sent, 3);
+        assert_eq!(peers.len(), env.k());
+    }
+}
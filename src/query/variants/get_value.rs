@@ -1,11 +1,18 @@
 use super::QueryState;
-use crate::{message::PutValueRequest, storage::Record, Key, PeerId, CONFIG};
+use crate::{
+    message::PutValueRequest,
+    query::{QueryId, QueryProgress},
+    storage::Record,
+    Key, PeerId,
+};
 
 /// Query to get the value associated with a key from the DHT.
 #[derive(Debug)]
 pub struct GetValueQuery {
     key: Key,
     caching: Vec<PeerId>,
+    caching_max_peers: usize,
+    progress: QueryProgress,
 }
 
 impl GetValueQuery {
@@ -14,17 +21,31 @@ impl GetValueQuery {
     /// # Arguments
     ///
     /// * `key` - The key to retrieve the value for.
+    /// * `caching_max_peers` - The maximum number of peers to cache the found record at.
+    /// * `curr_time` - The current simulation time.
     ///
     /// # Returns
     ///
     /// A new `GetValueQuery` instance.
-    pub fn new(key: Key) -> Self {
+    pub fn new(key: Key, caching_max_peers: usize, curr_time: f64) -> Self {
         Self {
             key,
             caching: vec![],
+            caching_max_peers,
+            progress: QueryProgress::new(curr_time),
         }
     }
 
+    /// Returns the progress of the query.
+    pub fn progress(&self) -> &QueryProgress {
+        &self.progress
+    }
+
+    /// Returns a mutable reference to the progress of the query.
+    pub fn progress_mut(&mut self) -> &mut QueryProgress {
+        &mut self.progress
+    }
+
     /// Returns the key to retrieve the value for.
     pub fn key(&self) -> Key {
         self.key.clone()
@@ -34,6 +55,7 @@ impl GetValueQuery {
     ///
     /// # Arguments
     ///
+    /// * `query_id` - The ID of the query.
     /// * `peer` - The peer that sent the response.
     /// * `record` - The record associated with the key, if it was found.
     ///
@@ -43,6 +65,7 @@ impl GetValueQuery {
     /// of peers and requests to send to them.
     pub fn on_response(
         &mut self,
+        query_id: QueryId,
         peer: PeerId,
         record: Option<Record>,
     ) -> QueryState<(), (Record, Vec<(PeerId, PutValueRequest)>)> {
@@ -54,6 +77,7 @@ impl GetValueQuery {
                     (
                         dst,
                         PutValueRequest {
+                            query_id,
                             key: self.key.clone(),
                             record: record.clone(),
                         },
@@ -62,7 +86,7 @@ impl GetValueQuery {
                 .collect();
             QueryState::Completed((record, requests))
         } else {
-            if self.caching.len() < CONFIG.caching_max_peers {
+            if self.caching.len() < self.caching_max_peers {
                 self.caching.push(peer);
             }
             QueryState::InProgress(())
@@ -1,10 +1,12 @@
 mod find_node;
 mod get_value;
 mod put_value;
+mod termination;
 
-pub use find_node::{evaluate_closest_peers, FindNodeQuery};
+pub use find_node::{evaluate_closest_peers, FindNodeQuery, FindNodeQueryState};
 pub use get_value::GetValueQuery;
 pub use put_value::PutValueQuery;
+pub use termination::LookupTermination;
 
 pub enum QueryState<T, Y> {
     InProgress(T),
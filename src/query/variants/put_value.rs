@@ -1,9 +1,10 @@
-use crate::{storage::Record, Key};
+use crate::{query::QueryProgress, storage::Record, Key};
 
 #[derive(Debug)]
 pub struct PutValueQuery {
     key: Key,
     record: Record,
+    progress: QueryProgress,
 }
 
 impl PutValueQuery {
@@ -12,17 +13,29 @@ impl PutValueQuery {
     /// # Arguments
     ///
     /// * `record` - The record to store.
+    /// * `curr_time` - The current simulation time.
     ///
     /// # Returns
     ///
     /// A new `PutValueQuery` instance.
-    pub fn new(record: Record) -> PutValueQuery {
+    pub fn new(record: Record, curr_time: f64) -> PutValueQuery {
         PutValueQuery {
             key: record.key(),
             record,
+            progress: QueryProgress::new(curr_time),
         }
     }
 
+    /// Returns the progress of the query.
+    pub fn progress(&self) -> &QueryProgress {
+        &self.progress
+    }
+
+    /// Returns a mutable reference to the progress of the query.
+    pub fn progress_mut(&mut self) -> &mut QueryProgress {
+        &mut self.progress
+    }
+
     /// Returns the key of the record to store.
     pub fn key(&self) -> Key {
         self.key.clone()
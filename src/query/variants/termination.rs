@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// Represents the rule that decides when a `FindNodeQuery` is completed.
+///
+/// Regardless of the rule, the query is completed when there are no more
+/// peers to query, and the closest peers that have responded are returned.
+///
+/// In the configuration file, the rule is a table with the `kind` key,
+/// e.g. `{ kind = "beta", beta = 3 }`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum LookupTermination {
+    /// Stops when the k closest responded peers are all closer
+    /// than the closest unqueried peer.
+    #[default]
+    KClosestResponded,
+    /// Classic Kademlia rule: stops when a round of `alpha` responses
+    /// has not revealed any peer closer than the closest known one.
+    NoCloserPeers,
+    /// libp2p-style rule: stops when the `beta` closest known peers have all responded.
+    Beta { beta: usize },
+    /// Queries only the peers that are at most `max_hops` hops away
+    /// from the initiator. The `KClosestResponded` rule also applies.
+    MaxHops { max_hops: u32 },
+    /// Sends at most `max_requests` requests to the other peers.
+    /// The `KClosestResponded` rule also applies.
+    MaxRequests { max_requests: usize },
+}
@@ -0,0 +1,199 @@
+use crate::{
+    profile::ProfileStats,
+    query::{PeerTraffic, QueriesStats, QueryRecord},
+    SimulationConfig,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Represents the report of a simulation run.
+#[derive(Serialize)]
+pub struct Report<'a> {
+    /// The configuration of the run.
+    pub config: &'a SimulationConfig,
+    /// The simulation time at which the report was made.
+    pub time: f64,
+    /// The statistics merged from all the peers.
+    pub stats: &'a QueriesStats,
+    /// The peers that sent and received the most messages.
+    pub busiest_peers: &'a [PeerTraffic],
+    /// The statistics grouped by the profiles of the peers, empty if there are no profiles.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub profiles: &'a [ProfileStats],
+    /// The records of the finished queries.
+    pub queries: &'a [QueryRecord],
+}
+
+impl<'a> Report<'a> {
+    /// Creates a new `Report` instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration of the run.
+    /// * `time` - The current simulation time.
+    /// * `stats` - The merged statistics.
+    /// * `busiest_peers` - The peers that sent and received the most messages.
+    pub fn new(
+        config: &'a SimulationConfig,
+        time: f64,
+        stats: &'a QueriesStats,
+        busiest_peers: &'a [PeerTraffic],
+    ) -> Self {
+        Self {
+            config,
+            time,
+            stats,
+            busiest_peers,
+            profiles: &[],
+            queries: &stats.records.0,
+        }
+    }
+
+    /// Writes the report to the JSON file.
+    pub fn write_json(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+
+    /// Writes the records of the queries to the CSV file, and the configuration
+    /// with the statistics and the busiest peers to the CSV file with the `_summary` suffix next to it.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "peer_id,query_id,kind,outcome,started_at,finished_at,latency,rpcs_sent,hops,peers_contacted"
+        )?;
+        for record in self.queries {
+            writeln!(
+                writer,
+                "{},{},{:?},{:?},{},{},{},{},{},{}",
+                record.peer_id,
+                record.query_id,
+                record.kind,
+                record.outcome,
+                record.started_at,
+                record.finished_at,
+                record.latency(),
+                record.rpcs_sent,
+                record.hops,
+                record.peers_contacted,
+            )?;
+        }
+        writer.flush()?;
+
+        let mut writer = BufWriter::new(File::create(summary_path(path))?);
+        writeln!(writer, "name,value")?;
+        let mut rows = vec![("time".to_owned(), Value::from(self.time))];
+        flatten("config", &serde_json::to_value(self.config)?, &mut rows);
+        flatten("stats", &serde_json::to_value(self.stats)?, &mut rows);
+        flatten(
+            "busiest_peers",
+            &serde_json::to_value(self.busiest_peers)?,
+            &mut rows,
+        );
+        for profile in self.profiles {
+            flatten(
+                &format!("profiles.{}", profile.name),
+                &serde_json::to_value(&profile.stats)?,
+                &mut rows,
+            );
+        }
+        for (name, value) in rows {
+            match value {
+                Value::String(s) => writeln!(writer, "{},{}", name, escape_csv(&s))?,
+                value => writeln!(writer, "{},{}", name, value)?,
+            }
+        }
+        writer.flush()
+    }
+}
+
+/// Returns the path of the summary CSV file for the given CSV report path.
+fn summary_path(path: &Path) -> PathBuf {
+    suffixed_path(path, "summary")
+}
+
+/// Returns the path next to the given one with the suffix added to the file name,
+/// e.g. `report_summary.csv` for `report.csv` and `summary`.
+pub fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, suffix, ext.to_string_lossy()),
+        None => format!("{}_{}", stem, suffix),
+    };
+    path.with_file_name(name)
+}
+
+/// Flattens the nested JSON value into rows of dot-separated names and scalar values.
+///
+/// # Arguments
+///
+/// * `prefix` - The name of the value.
+/// * `value` - The value to flatten.
+/// * `rows` - The rows to append the result to.
+pub fn flatten(prefix: &str, value: &Value, rows: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(map) => {
+            for (name, value) in map {
+                flatten(&format!("{}.{}", prefix, name), value, rows);
+            }
+        }
+        Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                flatten(&format!("{}.{}", prefix, i), value, rows);
+            }
+        }
+        value => rows.push((prefix.to_owned(), value.clone())),
+    }
+}
+
+/// Quotes the string for CSV if needed.
+fn escape_csv(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten() {
+        let value = serde_json::json!({"a": {"b": 1, "c": [2, "x"]}, "d": null});
+        let mut rows = vec![];
+        flatten("root", &value, &mut rows);
+        let names = rows
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["root.a.b", "root.a.c.0", "root.a.c.1", "root.d"]
+        );
+    }
+
+    #[test]
+    fn test_summary_path() {
+        assert_eq!(
+            summary_path(Path::new("out/report.csv")),
+            PathBuf::from("out/report_summary.csv")
+        );
+        assert_eq!(
+            summary_path(Path::new("report")),
+            PathBuf::from("report_summary")
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::peer::Peer;
+use dslab_core::{cast, Event, EventHandler, Simulation, SimulationContext};
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    rc::Rc,
+};
+
+/// Represents a snapshot of the network-wide metrics taken during a run.
+/// The counters are accumulated over the window since the previous sample.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSample {
+    /// The simulation time of the sample.
+    pub time: f64,
+    /// The number of online peers.
+    pub online_peers: u32,
+    /// The total number of records in the DHT storages.
+    pub dht_records: u64,
+    /// The total number of blocks in the file storages.
+    pub file_blocks: u64,
+    /// The average number of peers in the k-buckets tables.
+    pub avg_routing_table_size: f64,
+    /// The number of queries of all kinds that are still running.
+    pub queries_in_flight: u64,
+    /// The number of queries of all kinds started in the window.
+    pub queries_started: u64,
+    /// The number of queries of all kinds completed in the window.
+    pub queries_completed: u64,
+    /// The number of queries of all kinds failed in the window.
+    pub queries_failed: u64,
+    /// The share of completed queries among the finished ones in the window,
+    /// or `None` if no query finished.
+    pub success_rate: Option<f64>,
+    /// The number of messages sent in the window.
+    pub messages_sent: u64,
+    /// The number of messages received in the window.
+    pub messages_received: u64,
+    /// The number of messages sent per second in the window.
+    pub messages_per_second: f64,
+}
+
+/// Cumulative counters of the previous sample used to compute the window values.
+#[derive(Debug, Default, Clone, Copy)]
+struct Totals {
+    queries_started: u64,
+    queries_completed: u64,
+    queries_failed: u64,
+    messages_sent: u64,
+    messages_received: u64,
+}
+
+/// Periodically takes snapshots of the network-wide metrics.
+pub struct MetricsSampler {
+    ctx: SimulationContext,
+    peers: Vec<Rc<RefCell<Peer>>>,
+    interval: f64,
+    last_time: f64,
+    last_totals: Totals,
+    samples: Vec<MetricsSample>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct MetricsSampleTimer {}
+
+impl MetricsSampler {
+    /// Registers a new `MetricsSampler` within the given simulation.
+    ///
+    /// # Arguments
+    ///
+    /// * `sim` - A mutable reference to the simulation.
+    /// * `peers` - The peers to take the metrics of.
+    /// * `interval` - The interval between the samples in seconds.
+    ///
+    /// # Returns
+    ///
+    /// The registered sampler.
+    pub fn register(
+        sim: &mut Simulation,
+        peers: Vec<Rc<RefCell<Peer>>>,
+        interval: f64,
+    ) -> Rc<RefCell<Self>> {
+        let name = "metrics_sampler";
+        let ctx = sim.create_context(name);
+        ctx.emit_self(MetricsSampleTimer {}, interval);
+        let last_time = ctx.time();
+        let mut sampler = Self {
+            ctx,
+            peers,
+            interval,
+            last_time,
+            last_totals: Totals::default(),
+            samples: vec![],
+        };
+        sampler.last_totals = sampler.totals();
+        let sampler = Rc::new(RefCell::new(sampler));
+        sim.add_handler(name, sampler.clone());
+        sampler
+    }
+
+    /// Returns the samples taken so far.
+    pub fn samples(&self) -> &[MetricsSample] {
+        &self.samples
+    }
+
+    /// Takes a sample of the metrics at the current time.
+    pub fn take_sample(&mut self) -> MetricsSample {
+        let mut sample = MetricsSample {
+            time: self.ctx.time(),
+            online_peers: 0,
+            dht_records: 0,
+            file_blocks: 0,
+            avg_routing_table_size: 0.,
+            queries_in_flight: 0,
+            queries_started: 0,
+            queries_completed: 0,
+            queries_failed: 0,
+            success_rate: None,
+            messages_sent: 0,
+            messages_received: 0,
+            messages_per_second: 0.,
+        };
+        let mut routing_table_size = 0;
+        for peer in self.peers.iter() {
+            let peer = peer.borrow();
+            sample.online_peers += peer.is_online() as u32;
+            sample.dht_records += peer.dht_records_count() as u64;
+            sample.file_blocks += peer.file_blocks_count() as u64;
+            sample.queries_in_flight += peer.queries_in_flight() as u64;
+            routing_table_size += peer.routing_table_size();
+        }
+        if !self.peers.is_empty() {
+            sample.avg_routing_table_size = routing_table_size as f64 / self.peers.len() as f64;
+        }
+
+        // The statistics are reset when they are extracted from the peers,
+        // so the counters may decrease between the samples.
+        let totals = self.totals();
+        let last = self.last_totals;
+        sample.queries_started = totals.queries_started.saturating_sub(last.queries_started);
+        sample.queries_completed = totals
+            .queries_completed
+            .saturating_sub(last.queries_completed);
+        sample.queries_failed = totals.queries_failed.saturating_sub(last.queries_failed);
+        let finished = sample.queries_completed + sample.queries_failed;
+        if finished > 0 {
+            sample.success_rate = Some(sample.queries_completed as f64 / finished as f64);
+        }
+        sample.messages_sent = totals.messages_sent.saturating_sub(last.messages_sent);
+        sample.messages_received = totals
+            .messages_received
+            .saturating_sub(last.messages_received);
+        let elapsed = sample.time - self.last_time;
+        if elapsed > 0. {
+            sample.messages_per_second = sample.messages_sent as f64 / elapsed;
+        }
+
+        self.last_time = sample.time;
+        self.last_totals = totals;
+        self.samples.push(sample.clone());
+        sample
+    }
+
+    /// Writes the samples to the CSV file.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "time,online_peers,dht_records,file_blocks,avg_routing_table_size,queries_in_flight,\
+             queries_started,queries_completed,queries_failed,success_rate,\
+             messages_sent,messages_received,messages_per_second"
+        )?;
+        for s in self.samples.iter() {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                s.time,
+                s.online_peers,
+                s.dht_records,
+                s.file_blocks,
+                s.avg_routing_table_size,
+                s.queries_in_flight,
+                s.queries_started,
+                s.queries_completed,
+                s.queries_failed,
+                s.success_rate.map(|r| r.to_string()).unwrap_or_default(),
+                s.messages_sent,
+                s.messages_received,
+                s.messages_per_second,
+            )?;
+        }
+        writer.flush()
+    }
+
+    /// Sums the cumulative counters of all the peers.
+    fn totals(&self) -> Totals {
+        let mut totals = Totals::default();
+        for peer in self.peers.iter() {
+            let peer = peer.borrow();
+            let stats = peer.peek_stats();
+            totals.queries_started += stats.queries_started();
+            totals.queries_completed += stats.queries_completed();
+            totals.queries_failed += stats.queries_failed();
+            totals.messages_sent += stats.traffic.total.sent;
+            totals.messages_received += stats.traffic.total.received;
+        }
+        totals
+    }
+}
+
+impl EventHandler for MetricsSampler {
+    fn on(&mut self, event: Event) {
+        cast!(match event.data {
+            MetricsSampleTimer {} => {
+                self.take_sample();
+                self.ctx.emit_self(MetricsSampleTimer {}, self.interval);
+            }
+        })
+    }
+}
@@ -0,0 +1,412 @@
+use crate::{
+    config::{ConfigError, FieldError},
+    query::QueriesStats,
+    PeerId, SimulationConfig,
+};
+use serde::Deserialize;
+use std::path::Path;
+
+pub use crate::network::Popularity;
+
+/// Represents a scenario of a simulation: a timeline of actions executed by `App::run_scenario`.
+///
+/// The scenario is a TOML file, e.g.
+///
+/// ```toml
+/// duration = 3600.0
+///
+/// [[steps]]
+/// at = 0.0
+/// action = "publish"
+/// count = 1000
+///
+/// [[steps]]
+/// at = 10.0
+/// action = "retrieve"
+/// count = 10000
+/// interval = 0.1
+/// popularity = { kind = "zipf", exponent = 1.0 }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioSpec {
+    /// The time the simulation runs for since the start of the scenario.
+    /// If it is not specified, the simulation runs until there are no events left,
+    /// which requires the periodic tasks to be disabled, see `SimulationConfig::periodic_tasks`,
+    /// and the user load to be stopped by the end of the scenario.
+    pub duration: Option<f64>,
+    /// The steps of the scenario. The steps scheduled for the same time
+    /// are executed in the order they are listed.
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+/// Represents an action scheduled at some time.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Step {
+    /// The time of the action since the start of the scenario.
+    pub at: f64,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+/// Represents an action of a scenario.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Action {
+    /// Publishes `count` blocks, one every `interval` seconds, from random peers of `peers`.
+    /// The blocks are taken from `content` in turn, or are generated:
+    /// random strings of `size` bytes if it is specified, `data-<n>` otherwise.
+    Publish {
+        #[serde(default = "default_count")]
+        count: usize,
+        #[serde(default)]
+        interval: f64,
+        #[serde(default)]
+        peers: PeerSelection,
+        content: Option<Vec<String>>,
+        size: Option<usize>,
+    },
+    /// Retrieves `count` blocks, one every `interval` seconds, from random peers of `peers`.
+    /// The blocks are chosen from `content` if it is specified, and from the blocks published
+    /// by the scenario so far otherwise, with the given popularity distribution.
+    Retrieve {
+        #[serde(default = "default_count")]
+        count: usize,
+        #[serde(default)]
+        interval: f64,
+        #[serde(default)]
+        peers: PeerSelection,
+        content: Option<Vec<String>>,
+        #[serde(default)]
+        popularity: Popularity,
+    },
+    /// Starts the user load generation configured in the `[user_load]` table.
+    StartUserLoad,
+    /// Stops the user load generation.
+    StopUserLoad,
+    /// Splits the network into isolated groups of peers.
+    /// The peers not listed in any group form one more group.
+    Partition { groups: Vec<Vec<PeerId>> },
+    /// Removes the partition of the network.
+    Heal,
+    /// Takes the peers offline, and brings them back online in `duration` if it is specified.
+    GoOffline {
+        peers: PeerSelection,
+        duration: Option<f64>,
+    },
+    /// Brings the peers online.
+    GoOnline { peers: PeerSelection },
+    /// Saves the statistics of all the peers collected so far under the given name.
+    Snapshot { name: String },
+    /// Clears the storages of the peers.
+    ClearStorage {
+        #[serde(default = "PeerSelection::all")]
+        peers: PeerSelection,
+    },
+}
+
+fn default_count() -> usize {
+    1
+}
+
+/// Represents a selection of peers, either a list of peer indices or
+/// `"all"`, `"random"` (a single random peer), `{ count = n }` or `{ fraction = f }`
+/// (a random subset of the given size).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PeerSelection {
+    Ids(Vec<PeerId>),
+    Named(NamedSelection),
+    Count { count: usize },
+    Fraction { fraction: f64 },
+}
+
+/// Represents the named selections of peers.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NamedSelection {
+    All,
+    Random,
+}
+
+impl PeerSelection {
+    fn all() -> Self {
+        Self::Named(NamedSelection::All)
+    }
+}
+
+impl Default for PeerSelection {
+    fn default() -> Self {
+        Self::Named(NamedSelection::Random)
+    }
+}
+
+/// Represents the statistics of all the peers saved by the `snapshot` action.
+#[derive(Debug, Clone)]
+pub struct StatsSnapshot {
+    /// The name of the snapshot.
+    pub name: String,
+    /// The simulation time at which the snapshot was taken.
+    pub time: f64,
+    /// The statistics merged from all the peers.
+    pub stats: QueriesStats,
+}
+
+impl ScenarioSpec {
+    /// Parses the scenario from a TOML file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let data = std::fs::read_to_string(path)?;
+        Self::from_toml_str(&data)
+    }
+
+    /// Parses the scenario from a TOML string.
+    pub fn from_toml_str(data: &str) -> Result<Self, ConfigError> {
+        toml::from_str(data).map_err(|err| ConfigError::Parse(err.to_string()))
+    }
+
+    /// Checks that the scenario can be run with the given configuration.
+    /// All the problems found are reported at once.
+    pub fn validate(&self, config: &SimulationConfig) -> Result<(), ConfigError> {
+        let mut errors = vec![];
+        let mut error = |field: String, reason: &str| {
+            errors.push(FieldError {
+                field,
+                reason: reason.to_string(),
+            })
+        };
+        if let Some(duration) = self.duration {
+            if duration < 0. {
+                error("duration".to_string(), "must be non-negative");
+            }
+        }
+        let mut publishes = false;
+        let mut user_load = config.enable_user_load_generation;
+        let mut steps = self.steps.iter().enumerate().collect::<Vec<_>>();
+        steps.sort_by(|(_, a), (_, b)| a.at.total_cmp(&b.at));
+        for (i, step) in steps {
+            match step.action {
+                Action::StartUserLoad => user_load = true,
+                Action::StopUserLoad => user_load = false,
+                _ => {}
+            }
+            let field = |name: &str| format!("steps.{}.{}", i, name);
+            if step.at < 0. {
+                error(field("at"), "must be non-negative");
+            }
+            let peers = match step.action {
+                Action::Publish { ref peers, .. }
+                | Action::Retrieve { ref peers, .. }
+                | Action::GoOffline { ref peers, .. }
+                | Action::GoOnline { ref peers }
+                | Action::ClearStorage { ref peers } => Some(peers),
+                _ => None,
+            };
+            if let Some((name, reason)) =
+                peers.and_then(|peers| check_peers(peers, config.num_peers))
+            {
+                error(field(name), reason);
+            }
+            match step.action {
+                Action::Publish {
+                    interval,
+                    ref content,
+                    ..
+                } => {
+                    if content.as_ref().is_some_and(|content| content.is_empty()) {
+                        error(field("content"), "must not be empty");
+                    }
+                    if interval < 0. {
+                        error(field("interval"), "must be non-negative");
+                    }
+                    publishes = true;
+                }
+                Action::Retrieve {
+                    interval,
+                    ref content,
+                    popularity,
+                    ..
+                } => {
+                    match content {
+                        Some(content) if content.is_empty() => {
+                            error(field("content"), "must not be empty")
+                        }
+                        None if !publishes => error(
+                            field("content"),
+                            "is required if nothing is published before the step",
+                        ),
+                        _ => {}
+                    }
+                    if interval < 0. {
+                        error(field("interval"), "must be non-negative");
+                    }
+                    if let Popularity::Zipf { exponent } = popularity {
+                        if exponent <= 0. {
+                            error(field("popularity.exponent"), "must be positive");
+                        }
+                    }
+                }
+                Action::StartUserLoad
+                    if config.user_load_block_size.is_none()
+                        || config.user_load_blocks_pool_size.is_none()
+                        || config.user_load_events_interval.is_none() =>
+                {
+                    error(
+                        field("action"),
+                        "requires the user load to be configured in the [user_load] table",
+                    );
+                }
+                Action::Partition { ref groups }
+                    if groups.iter().flatten().any(|&id| id >= config.num_peers) =>
+                {
+                    error(field("groups"), "must contain peers less than num_peers");
+                }
+                Action::GoOffline {
+                    duration: Some(duration),
+                    ..
+                } if duration < 0. => {
+                    error(field("duration"), "must be non-negative");
+                }
+                _ => {}
+            }
+        }
+        if self.duration.is_none() {
+            let mut tasks = config.periodic_tasks();
+            tasks.retain(|&task| task != "user_load.enabled");
+            if user_load {
+                tasks.push("user load");
+            }
+            if !tasks.is_empty() {
+                error(
+                    "duration".to_string(),
+                    &format!(
+                        "is required, since the periodic tasks never stop: {}",
+                        tasks.join(", ")
+                    ),
+                );
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+}
+
+/// Checks the selection of peers.
+///
+/// # Returns
+///
+/// The path to the invalid field relative to the step and the reason, if the selection is invalid.
+fn check_peers(peers: &PeerSelection, num_peers: u32) -> Option<(&'static str, &'static str)> {
+    match *peers {
+        PeerSelection::Ids(ref ids) if ids.is_empty() => Some(("peers", "must not be empty")),
+        PeerSelection::Ids(ref ids) if ids.iter().any(|&id| id >= num_peers) => {
+            Some(("peers", "must be less than num_peers"))
+        }
+        PeerSelection::Count { count } if count > num_peers as usize => {
+            Some(("peers.count", "must not be greater than num_peers"))
+        }
+        PeerSelection::Fraction { fraction } if !(0. ..=1.).contains(&fraction) => {
+            Some(("peers.fraction", "must be between 0 and 1"))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scenario_files() {
+        let config = SimulationConfig::from_default_config_file().unwrap();
+        for entry in std::fs::read_dir("scenarios").unwrap() {
+            let path = entry.unwrap().path();
+            let scenario = ScenarioSpec::from_file(&path).unwrap();
+            scenario.validate(&config).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = SimulationConfig::from_default_config_file().unwrap();
+        let scenario = ScenarioSpec::from_toml_str(
+            r#"
+            [[steps]]
+            at = 5.0
+            action = "publish"
+            peers = [0, 10000]
+
+            [[steps]]
+            at = 1.0
+            action = "retrieve"
+            popularity = { kind = "zipf", exponent = 0.0 }
+
+            [[steps]]
+            at = 2.0
+            action = "go_offline"
+            peers = { fraction = 0.1 }
+            "#,
+        )
+        .unwrap();
+        let Err(ConfigError::Invalid(errors)) = scenario.validate(&config) else {
+            panic!("expected the scenario to be invalid");
+        };
+        let fields = errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                "steps.1.content",
+                "steps.1.popularity.exponent",
+                "steps.0.peers"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duration_required() {
+        let mut config = SimulationConfig::from_default_config_file().unwrap();
+        let start = r#"
+            [[steps]]
+            at = 0.0
+            action = "start_user_load"
+            "#;
+        let stop = r#"
+            [[steps]]
+            at = 10.0
+            action = "stop_user_load"
+            "#;
+        let invalid_fields =
+            |scenario: &str, config: &SimulationConfig| match ScenarioSpec::from_toml_str(scenario)
+                .unwrap()
+                .validate(config)
+            {
+                Ok(()) => vec![],
+                Err(ConfigError::Invalid(errors)) => {
+                    errors.into_iter().map(|e| e.field).collect::<Vec<_>>()
+                }
+                Err(err) => panic!("unexpected error: {}", err),
+            };
+        assert_eq!(invalid_fields(start, &config), vec!["duration"]);
+        let stopped = format!("{}{}", start, stop);
+        assert!(invalid_fields(&stopped, &config).is_empty());
+        config.gc_interval = Some(60.);
+        assert_eq!(invalid_fields(&stopped, &config), vec!["duration"]);
+        let limited = format!("duration = 20.0\n{}", stopped);
+        assert!(invalid_fields(&limited, &config).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_action() {
+        let result = ScenarioSpec::from_toml_str(
+            r#"
+            [[steps]]
+            at = 0.0
+            action = "explode"
+            "#,
+        );
+        assert!(matches!(result, Err(ConfigError::Parse(_))));
+    }
+}
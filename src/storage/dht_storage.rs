@@ -0,0 +1,225 @@
+use super::{EvictingMap, EvictionPolicy, Record, RecordData, StorageCapacity};
+use crate::{Key, PeerId};
+use std::collections::HashMap;
+
+/// Represents the local storage for the DHT.
+#[derive(Debug, Default)]
+pub struct LocalDHTStorage {
+    records: EvictingMap<Record>,
+    // the expiration times of the expired records removed from the storage,
+    // until they are stored again or forgotten, see `remove_expired`
+    expired_keys: HashMap<Key, f64>,
+}
+
+impl LocalDHTStorage {
+    /// Creates a new `LocalDHTStorage` instance with unlimited capacity.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new `LocalDHTStorage` instance with limited capacity.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The capacity limits of the storage.
+    /// * `policy` - The policy used to evict records when the storage is full.
+    /// * `seed` - The seed used by the random eviction policy.
+    pub fn with_capacity(capacity: StorageCapacity, policy: EvictionPolicy, seed: u64) -> Self {
+        Self {
+            records: EvictingMap::new(capacity, policy, seed),
+            expired_keys: HashMap::new(),
+        }
+    }
+
+    /// Retrieves a record from the storage.
+    /// Expired records are never returned, even if they have not been removed yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the record.
+    /// * `curr_time` - The current simulation time.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing a reference to the record if found and not expired,
+    /// or `None` otherwise.
+    pub fn get(&self, key: &Key, curr_time: f64) -> Option<&Record> {
+        self.records
+            .peek(key)
+            .filter(|record| record.expires_at > curr_time)
+            .and_then(|_| self.records.get(key))
+    }
+
+    /// Retrieves an unexpired record from the storage without marking it as used,
+    /// so the eviction order is not affected.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the record.
+    /// * `curr_time` - The current simulation time.
+    pub fn peek(&self, key: &Key, curr_time: f64) -> Option<&Record> {
+        self.records
+            .peek(key)
+            .filter(|record| record.expires_at > curr_time)
+    }
+
+    /// Checks if the record associated with the given key has expired,
+    /// whether it is still held by the storage or has already been removed as expired.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the record.
+    /// * `curr_time` - The current simulation time.
+    pub fn has_expired(&self, key: &Key, curr_time: f64) -> bool {
+        match self.records.peek(key) {
+            Some(record) => record.expires_at <= curr_time,
+            None => self.expired_keys.contains_key(key),
+        }
+    }
+
+    /// Inserts a record into the storage.
+    /// If the storage already contains a live record for the key, the records are merged.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the record.
+    /// * `record` - The record to be inserted.
+    /// * `curr_time` - The current simulation time.
+    ///
+    /// # Returns
+    ///
+    /// The keys of the records evicted to free space for the new one.
+    pub fn put(&mut self, key: Key, record: Record, curr_time: f64) -> Vec<Key> {
+        let record = match self.records.peek(&key) {
+            Some(stored) if stored.expires_at > curr_time => stored.merged(&record, curr_time),
+            _ => record,
+        };
+        let size = record.size();
+        let expires_at = record.expires_at;
+        self.expired_keys.remove(&key);
+        self.records.insert(key, record, size, expires_at, false)
+    }
+
+    /// Removes a record from the storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the record.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the record was removed, `false` otherwise.
+    pub fn remove(&mut self, key: &Key) -> bool {
+        self.expired_keys.remove(key);
+        self.records.remove(key).is_some()
+    }
+
+    /// Removes a provider from the record associated with the given key,
+    /// and the record itself if no other providers remain.
+    /// The record is updated in place, so its position in the eviction order is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the record.
+    /// * `provider` - The ID of the provider to remove.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the provider was listed in the record, `false` otherwise.
+    pub fn remove_provider(&mut self, key: &Key, provider: PeerId) -> bool {
+        let Some(stored) = self.records.peek(key) else {
+            return false;
+        };
+        let RecordData::ProviderRecord { providers, .. } = &stored.data;
+        if providers.iter().all(|p| p.id != provider) {
+            return false;
+        }
+        match stored.without_provider(provider) {
+            Some(record) => {
+                let size = record.size();
+                let expires_at = record.expires_at;
+                self.records.update(key, record, size, expires_at);
+            }
+            None => {
+                self.records.remove(key);
+            }
+        }
+        true
+    }
+
+    /// Removes expired records from the storage.
+    /// Their keys are remembered, so that the reads of them are recognized as stale,
+    /// and forgotten once the records have been expired for longer than `retention`.
+    ///
+    /// # Arguments
+    ///
+    /// * `curr_time` - The current simulation time.
+    /// * `retention` - How long the keys of the removed records are remembered.
+    ///
+    /// # Returns
+    ///
+    /// The number of removed records.
+    pub fn remove_expired(&mut self, curr_time: f64, retention: f64) -> usize {
+        let mut removed = 0;
+        let expired_keys = &mut self.expired_keys;
+        expired_keys.retain(|_, expires_at| *expires_at + retention > curr_time);
+        self.records.retain(|key, record| {
+            let live = record.expires_at > curr_time;
+            if !live {
+                expired_keys.insert(key.clone(), record.expires_at);
+                removed += 1;
+            }
+            live
+        });
+        removed
+    }
+
+    /// Clears the storage, removing all records.
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.expired_keys.clear();
+    }
+
+    /// Returns the number of records in the storage.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns `true` if the storage contains no records.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Returns the total size of the records in bytes.
+    pub fn total_bytes(&self) -> usize {
+        self.records.total_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expired_keys() {
+        let mut storage = LocalDHTStorage::new();
+        let key = |i: u32| Key::from_sha256(&i.to_le_bytes());
+        storage.put(key(0), Record::new_provider_record(1, key(0), 10.), 0.);
+        storage.put(key(1), Record::new_provider_record(1, key(1), 20.), 0.);
+        assert_eq!(storage.remove_expired(15., 100.), 1);
+        assert!(storage.has_expired(&key(0), 15.));
+        assert!(!storage.has_expired(&key(1), 15.));
+
+        // a key stored again is no longer stale
+        storage.put(key(0), Record::new_provider_record(1, key(0), 50.), 15.);
+        assert!(!storage.has_expired(&key(0), 15.));
+
+        assert_eq!(storage.remove_expired(60., 100.), 2);
+        assert!(storage.has_expired(&key(1), 60.));
+        assert_eq!(storage.remove_expired(130., 100.), 0);
+        assert!(!storage.has_expired(&key(1), 130.));
+        assert!(storage.has_expired(&key(0), 130.));
+        assert_eq!(storage.remove_expired(150., 100.), 0);
+        assert!(storage.expired_keys.is_empty());
+    }
+}
@@ -0,0 +1,429 @@
+use crate::Key;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{cell::Cell, collections::HashMap};
+
+/// Represents the policy used to choose an entry to evict when the storage is full.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// Evicts the least recently used entry.
+    #[default]
+    Lru,
+    /// Evicts the least frequently used entry.
+    /// Ties are broken by the least recent use.
+    Lfu,
+    /// Evicts the entry that expires first.
+    OldestExpiryFirst,
+    /// Evicts a random entry.
+    Random,
+}
+
+/// Represents the capacity limits of a storage.
+/// Limits that are not specified are not enforced.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StorageCapacity {
+    /// The maximum number of entries in the storage.
+    pub max_records: Option<usize>,
+    /// The maximum total size of the entries in bytes.
+    pub max_bytes: Option<usize>,
+}
+
+impl StorageCapacity {
+    /// Creates a capacity without any limits.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    size: usize,
+    expires_at: f64,
+    pinned: bool,
+    inserted_seq: u64,
+    priority: u64,
+    last_access: Cell<u64>,
+    access_count: Cell<u64>,
+}
+
+/// A map with limited capacity that evicts entries according to the given policy.
+///
+/// Entry accesses are tracked with a logical clock, so all the policies
+/// are deterministic for a fixed seed.
+#[derive(Debug)]
+pub struct EvictingMap<V> {
+    entries: HashMap<Key, Entry<V>>,
+    capacity: StorageCapacity,
+    policy: EvictionPolicy,
+    rng: StdRng,
+    clock: Cell<u64>,
+    total_bytes: usize,
+    evictions: u64,
+}
+
+impl<V> EvictingMap<V> {
+    /// Creates a new empty `EvictingMap`.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The capacity limits of the map.
+    /// * `policy` - The policy used to choose an entry to evict.
+    /// * `seed` - The seed used by the random eviction policy.
+    pub fn new(capacity: StorageCapacity, policy: EvictionPolicy, seed: u64) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity,
+            policy,
+            rng: StdRng::seed_from_u64(seed),
+            clock: Cell::new(0),
+            total_bytes: 0,
+            evictions: 0,
+        }
+    }
+
+    /// Retrieves a value from the map and marks it as used.
+    pub fn get(&self, key: &Key) -> Option<&V> {
+        self.entries.get(key).map(|entry| {
+            entry.last_access.set(self.tick());
+            entry.access_count.set(entry.access_count.get() + 1);
+            &entry.value
+        })
+    }
+
+    /// Retrieves a value from the map without marking it as used.
+    pub fn peek(&self, key: &Key) -> Option<&V> {
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Inserts a value into the map, evicting other entries if the capacity is exceeded.
+    /// Pinned entries are never evicted.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the value.
+    /// * `value` - The value to be inserted.
+    /// * `size` - The size of the value in bytes.
+    /// * `expires_at` - The expiration time of the value.
+    /// * `pinned` - Whether the entry is pinned.
+    ///
+    /// # Returns
+    ///
+    /// The keys of the evicted entries. If an unpinned value cannot fit into the map,
+    /// it is not inserted and its key is returned as evicted. A pinned value is always
+    /// inserted, even if the capacity is exceeded.
+    pub fn insert(
+        &mut self,
+        key: Key,
+        value: V,
+        size: usize,
+        expires_at: f64,
+        pinned: bool,
+    ) -> Vec<Key> {
+        self.remove(&key);
+        if !pinned && self.capacity.max_bytes.is_some_and(|max| size > max) {
+            self.evictions += 1;
+            return vec![key];
+        }
+        let mut evicted = vec![];
+        while self.exceeds_capacity(1, size) {
+            match self.victim() {
+                Some(victim) => {
+                    self.remove(&victim);
+                    self.evictions += 1;
+                    evicted.push(victim);
+                }
+                None if pinned => break,
+                None => {
+                    self.evictions += 1;
+                    evicted.push(key);
+                    return evicted;
+                }
+            }
+        }
+        let seq = self.tick();
+        let entry = Entry {
+            value,
+            size,
+            expires_at,
+            pinned,
+            inserted_seq: seq,
+            priority: self.rng.gen(),
+            last_access: Cell::new(seq),
+            access_count: Cell::new(0),
+        };
+        self.total_bytes += size;
+        self.entries.insert(key, entry);
+        evicted
+    }
+
+    /// Replaces the value of an existing entry without marking it as used,
+    /// so its pinning and its position in the eviction order are kept.
+    /// The new value must not be larger than the replaced one, so nothing is evicted.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the value.
+    /// * `value` - The new value.
+    /// * `size` - The size of the new value in bytes.
+    /// * `expires_at` - The expiration time of the new value.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the entry exists, `false` otherwise.
+    pub fn update(&mut self, key: &Key, value: V, size: usize, expires_at: f64) -> bool {
+        let Some(entry) = self.entries.get_mut(key) else {
+            return false;
+        };
+        debug_assert!(size <= entry.size, "the updated value must not grow");
+        self.total_bytes = self.total_bytes - entry.size + size;
+        entry.value = value;
+        entry.size = size;
+        entry.expires_at = expires_at;
+        true
+    }
+
+    /// Removes a value from the map.
+    ///
+    /// # Returns
+    ///
+    /// The removed value, if it existed.
+    pub fn remove(&mut self, key: &Key) -> Option<V> {
+        self.entries.remove(key).map(|entry| {
+            self.total_bytes -= entry.size;
+            entry.value
+        })
+    }
+
+    /// Retains only the values specified by the predicate.
+    pub fn retain(&mut self, mut f: impl FnMut(&Key, &V) -> bool) {
+        let mut freed = 0;
+        self.entries.retain(|key, entry| {
+            let keep = f(key, &entry.value);
+            if !keep {
+                freed += entry.size;
+            }
+            keep
+        });
+        self.total_bytes -= freed;
+    }
+
+    /// Sets whether the entry with the given key is pinned.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the entry exists, `false` otherwise.
+    pub fn set_pinned(&mut self, key: &Key, pinned: bool) -> bool {
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.pinned = pinned;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns `true` if the entry with the given key exists and is pinned.
+    pub fn is_pinned(&self, key: &Key) -> bool {
+        self.entries.get(key).is_some_and(|entry| entry.pinned)
+    }
+
+    /// Removes all unpinned entries from the map.
+    ///
+    /// # Returns
+    ///
+    /// The keys of the removed entries and their total size in bytes.
+    pub fn remove_unpinned(&mut self) -> (Vec<Key>, usize) {
+        let mut keys = vec![];
+        let mut freed = 0;
+        self.entries.retain(|key, entry| {
+            if !entry.pinned {
+                keys.push(key.clone());
+                freed += entry.size;
+            }
+            entry.pinned
+        });
+        self.total_bytes -= freed;
+        (keys, freed)
+    }
+
+    /// Removes all values from the map.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.total_bytes = 0;
+    }
+
+    /// Returns an iterator over the keys and values of the map.
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &V)> {
+        self.entries.iter().map(|(key, entry)| (key, &entry.value))
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the total size of the entries in bytes.
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    /// Returns the number of entries evicted due to the capacity limits.
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// Checks if adding `count` entries of `size` bytes would exceed the capacity.
+    fn exceeds_capacity(&self, count: usize, size: usize) -> bool {
+        self.capacity
+            .max_records
+            .is_some_and(|max| self.entries.len() + count > max)
+            || self
+                .capacity
+                .max_bytes
+                .is_some_and(|max| self.total_bytes + size > max)
+    }
+
+    /// Chooses the entry to evict according to the policy.
+    fn victim(&self) -> Option<Key> {
+        let entries = self.entries.iter().filter(|(_, e)| !e.pinned);
+        let victim = match self.policy {
+            EvictionPolicy::Lru => {
+                entries.min_by_key(|(_, e)| (e.last_access.get(), e.inserted_seq))
+            }
+            EvictionPolicy::Lfu => entries
+                .min_by_key(|(_, e)| (e.access_count.get(), e.last_access.get(), e.inserted_seq)),
+            EvictionPolicy::OldestExpiryFirst => entries.min_by(|(_, a), (_, b)| {
+                a.expires_at
+                    .total_cmp(&b.expires_at)
+                    .then(a.inserted_seq.cmp(&b.inserted_seq))
+            }),
+            EvictionPolicy::Random => entries.min_by_key(|(_, e)| (e.priority, e.inserted_seq)),
+        };
+        victim.map(|(key, _)| key.clone())
+    }
+
+    /// Advances the logical clock and returns its previous value.
+    fn tick(&self) -> u64 {
+        let time = self.clock.get();
+        self.clock.set(time + 1);
+        time
+    }
+}
+
+impl<V> Default for EvictingMap<V> {
+    fn default() -> Self {
+        Self::new(StorageCapacity::unlimited(), EvictionPolicy::default(), 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: u32) -> Key {
+        Key::from_sha256(&i.to_le_bytes())
+    }
+
+    fn map(policy: EvictionPolicy) -> EvictingMap<u32> {
+        let capacity = StorageCapacity {
+            max_records: Some(3),
+            max_bytes: Some(100),
+        };
+        EvictingMap::new(capacity, policy, 0)
+    }
+
+    #[test]
+    fn test_lru() {
+        let mut map = map(EvictionPolicy::Lru);
+        for i in 0..3 {
+            assert!(map.insert(key(i), i, 10, 0., false).is_empty());
+        }
+        map.get(&key(0));
+        assert_eq!(map.insert(key(3), 3, 10, 0., false), vec![key(1)]);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.evictions(), 1);
+    }
+
+    #[test]
+    fn test_update() {
+        let mut map = map(EvictionPolicy::Lru);
+        for i in 0..3 {
+            map.insert(key(i), i, 10, 0., false);
+        }
+        map.get(&key(0));
+        assert!(map.update(&key(1), 10, 5, 1.));
+        assert!(!map.update(&key(3), 3, 5, 1.));
+        assert_eq!(map.peek(&key(1)), Some(&10));
+        assert_eq!(map.total_bytes(), 25);
+        assert_eq!(map.insert(key(3), 3, 10, 0., false), vec![key(1)]);
+    }
+
+    #[test]
+    fn test_lfu() {
+        let mut map = map(EvictionPolicy::Lfu);
+        for i in 0..3 {
+            map.insert(key(i), i, 10, 0., false);
+        }
+        map.get(&key(0));
+        map.get(&key(0));
+        map.get(&key(1));
+        map.get(&key(2));
+        map.get(&key(1));
+        assert_eq!(map.insert(key(3), 3, 10, 0., false), vec![key(2)]);
+    }
+
+    #[test]
+    fn test_oldest_expiry_first() {
+        let mut map = map(EvictionPolicy::OldestExpiryFirst);
+        map.insert(key(0), 0, 10, 5., false);
+        map.insert(key(1), 1, 10, 1., false);
+        map.insert(key(2), 2, 10, 3., false);
+        assert_eq!(map.insert(key(3), 3, 10, 4., false), vec![key(1)]);
+    }
+
+    #[test]
+    fn test_random() {
+        let mut map = map(EvictionPolicy::Random);
+        for i in 0..10 {
+            map.insert(key(i), i, 10, 0., false);
+        }
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.evictions(), 7);
+    }
+
+    #[test]
+    fn test_pinned() {
+        let mut map = map(EvictionPolicy::Lru);
+        map.insert(key(0), 0, 10, 0., true);
+        map.insert(key(1), 1, 10, 0., true);
+        map.insert(key(2), 2, 10, 0., false);
+        assert_eq!(map.insert(key(3), 3, 10, 0., false), vec![key(2)]);
+        assert!(map.set_pinned(&key(3), true));
+        assert_eq!(map.insert(key(4), 4, 10, 0., false), vec![key(4)]);
+        assert!(map.insert(key(5), 5, 10, 0., true).is_empty());
+        assert_eq!(map.len(), 4);
+        assert!(map.set_pinned(&key(0), false));
+        assert_eq!(map.remove_unpinned(), (vec![key(0)], 10));
+        assert_eq!(map.total_bytes(), 30);
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        let mut map = map(EvictionPolicy::Lru);
+        map.insert(key(0), 0, 40, 0., false);
+        map.insert(key(1), 1, 40, 0., false);
+        assert_eq!(map.insert(key(2), 2, 40, 0., false), vec![key(0)]);
+        assert_eq!(map.total_bytes(), 80);
+        assert_eq!(map.insert(key(3), 3, 101, 0., false), vec![key(3)]);
+        assert_eq!(map.total_bytes(), 80);
+        map.remove(&key(1));
+        assert_eq!(map.total_bytes(), 40);
+    }
+}
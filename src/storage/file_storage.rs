@@ -0,0 +1,138 @@
+use super::{EvictingMap, EvictionPolicy, StorageCapacity};
+use crate::Key;
+
+/// Represents the local file storage.
+#[derive(Debug, Default)]
+pub struct LocalFileStorage {
+    data: EvictingMap<String>,
+}
+
+impl LocalFileStorage {
+    /// Creates a new `LocalFileStorage` instance with unlimited capacity.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new `LocalFileStorage` instance with limited capacity.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The capacity limits of the storage.
+    /// * `policy` - The policy used to evict data when the storage is full.
+    /// * `seed` - The seed used by the random eviction policy.
+    pub fn with_capacity(capacity: StorageCapacity, policy: EvictionPolicy, seed: u64) -> Self {
+        Self {
+            data: EvictingMap::new(capacity, policy, seed),
+        }
+    }
+
+    /// Retrieves data from the storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the data.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing a reference to the data if found, or `None` if not found.
+    pub fn get(&self, key: &Key) -> Option<&String> {
+        self.data.get(key)
+    }
+
+    /// Checks if the storage holds the data without marking it as used,
+    /// so the eviction order is not affected.
+    pub fn contains(&self, key: &Key) -> bool {
+        self.data.peek(key).is_some()
+    }
+
+    /// Inserts data into the storage.
+    /// Data never expires, so it is only removed explicitly, evicted or garbage collected.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the data.
+    /// * `data` - The data to be inserted.
+    /// * `pinned` - Whether the data is pinned. Pinned data is never evicted
+    ///   or garbage collected.
+    ///
+    /// # Returns
+    ///
+    /// The keys of the data evicted to free space for the new one.
+    pub fn put(&mut self, key: Key, data: String, pinned: bool) -> Vec<Key> {
+        let size = data.len();
+        self.data.insert(key, data, size, f64::INFINITY, pinned)
+    }
+
+    /// Pins the data, protecting it from eviction and garbage collection.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the data exists, `false` otherwise.
+    pub fn pin(&mut self, key: &Key) -> bool {
+        self.data.set_pinned(key, true)
+    }
+
+    /// Unpins the data, so it can be evicted or garbage collected.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the data exists, `false` otherwise.
+    pub fn unpin(&mut self, key: &Key) -> bool {
+        self.data.set_pinned(key, false)
+    }
+
+    /// Returns `true` if the data exists and is pinned.
+    pub fn is_pinned(&self, key: &Key) -> bool {
+        self.data.is_pinned(key)
+    }
+
+    /// Removes all unpinned data if the total size of the storage exceeds the watermark.
+    ///
+    /// # Arguments
+    ///
+    /// * `watermark` - The size in bytes above which the garbage is collected.
+    ///   If `None`, the garbage is always collected.
+    ///
+    /// # Returns
+    ///
+    /// The keys of the removed data and its total size in bytes.
+    pub fn collect_garbage(&mut self, watermark: Option<usize>) -> (Vec<Key>, usize) {
+        if watermark.is_some_and(|watermark| self.data.total_bytes() <= watermark) {
+            return (vec![], 0);
+        }
+        self.data.remove_unpinned()
+    }
+
+    /// Removes data from the storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key associated with the data.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the data was removed, `false` otherwise.
+    pub fn remove(&mut self, key: &Key) -> bool {
+        self.data.remove(key).is_some()
+    }
+
+    /// Clears the storage, removing all data.
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Returns the number of blocks in the storage.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if the storage contains no data.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the total size of the data in bytes.
+    pub fn total_bytes(&self) -> usize {
+        self.data.total_bytes()
+    }
+}
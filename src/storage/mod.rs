@@ -0,0 +1,9 @@
+mod dht_storage;
+mod eviction;
+mod file_storage;
+mod record;
+
+pub use dht_storage::LocalDHTStorage;
+pub use eviction::{EvictingMap, EvictionPolicy, StorageCapacity};
+pub use file_storage::LocalFileStorage;
+pub use record::{Provider, Record, RecordData};
@@ -0,0 +1,181 @@
+use crate::{Key, PeerId};
+use serde::Serialize;
+
+/// Represents a record in the storage.
+#[derive(Debug, Clone, Serialize)]
+pub struct Record {
+    /// The data associated with the record.
+    pub data: RecordData,
+    /// The expiration time of the record.
+    pub expires_at: f64,
+}
+
+/// Represents the data associated with a record.
+#[derive(Debug, Clone, Serialize)]
+pub enum RecordData {
+    /// Provider record containing a key and a list of providers.
+    ProviderRecord { key: Key, providers: Vec<Provider> },
+}
+
+/// Represents a provider listed in a provider record.
+/// Every provider expires separately, so that a fresh announcement of one provider
+/// does not extend the lifetime of the others.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Provider {
+    /// The ID of the peer providing the data.
+    pub id: PeerId,
+    /// The time the provider expires at unless it announces the data again.
+    pub expires_at: f64,
+}
+
+impl Record {
+    /// Creates a new provider record.
+    ///
+    /// # Arguments
+    ///
+    /// * `self_id` - The ID of the current peer.
+    /// * `key` - The key associated with the record.
+    /// * `expires_at` - The simulation time when the record expires.
+    ///
+    /// # Returns
+    ///
+    /// A new `Record` instance.
+    pub fn new_provider_record(self_id: PeerId, key: Key, expires_at: f64) -> Self {
+        Self {
+            data: RecordData::ProviderRecord {
+                key,
+                providers: vec![Provider {
+                    id: self_id,
+                    expires_at,
+                }],
+            },
+            expires_at,
+        }
+    }
+
+    /// Returns the approximate size of the record in bytes.
+    ///
+    /// It is used to account the record against the byte capacity of the storage.
+    pub fn size(&self) -> usize {
+        match &self.data {
+            RecordData::ProviderRecord { providers, .. } => {
+                std::mem::size_of::<Key>()
+                    + providers.len() * std::mem::size_of::<PeerId>()
+                    + std::mem::size_of::<f64>()
+            }
+        }
+    }
+
+    /// Returns the key associated with the record.
+    pub fn key(&self) -> Key {
+        match &self.data {
+            RecordData::ProviderRecord { key, .. } => key.clone(),
+        }
+    }
+
+    /// Returns a copy of the record merged with another record for the same key.
+    ///
+    /// The providers of both records are united, keeping the later expiration time
+    /// of every provider, and the expired providers are dropped.
+    /// The record expires when its last provider does.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The record to merge with.
+    /// * `curr_time` - The current simulation time.
+    pub fn merged(&self, other: &Record, curr_time: f64) -> Self {
+        match (&self.data, &other.data) {
+            (
+                RecordData::ProviderRecord { key, providers },
+                RecordData::ProviderRecord {
+                    providers: other_providers,
+                    ..
+                },
+            ) => {
+                let mut providers = providers.clone();
+                for provider in other_providers {
+                    match providers.iter_mut().find(|p| p.id == provider.id) {
+                        Some(p) => p.expires_at = p.expires_at.max(provider.expires_at),
+                        None => providers.push(*provider),
+                    }
+                }
+                providers.retain(|p| p.expires_at > curr_time);
+                let expires_at = providers
+                    .iter()
+                    .map(|p| p.expires_at)
+                    .fold(f64::NEG_INFINITY, f64::max);
+                Self {
+                    data: RecordData::ProviderRecord {
+                        key: key.clone(),
+                        providers,
+                    },
+                    expires_at,
+                }
+            }
+        }
+    }
+
+    /// Returns a copy of the record without the given provider.
+    /// The record expires when its last remaining provider does.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the provider to drop.
+    ///
+    /// # Returns
+    ///
+    /// The new record, or `None` if no providers remain.
+    pub fn without_provider(&self, id: PeerId) -> Option<Self> {
+        match &self.data {
+            RecordData::ProviderRecord { key, providers } => {
+                let providers = providers
+                    .iter()
+                    .filter(|p| p.id != id)
+                    .copied()
+                    .collect::<Vec<_>>();
+                let expires_at = providers.iter().map(|p| p.expires_at).reduce(f64::max)?;
+                Some(Self {
+                    data: RecordData::ProviderRecord {
+                        key: key.clone(),
+                        providers,
+                    },
+                    expires_at,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merged() {
+        let key = Key::from_sha256(b"key");
+        let stale = Record::new_provider_record(1, key.clone(), 10.)
+            .merged(&Record::new_provider_record(2, key.clone(), 20.), 0.);
+        assert_eq!(stale.expires_at, 20.);
+
+        let merged = stale.merged(&Record::new_provider_record(3, key.clone(), 30.), 15.);
+        let RecordData::ProviderRecord { providers, .. } = &merged.data;
+        assert_eq!(
+            providers,
+            &vec![
+                Provider {
+                    id: 2,
+                    expires_at: 20.
+                },
+                Provider {
+                    id: 3,
+                    expires_at: 30.
+                }
+            ]
+        );
+        assert_eq!(merged.expires_at, 30.);
+
+        let remaining = merged.without_provider(3).unwrap();
+        assert_eq!(remaining.expires_at, 20.);
+        assert!(remaining.without_provider(2).is_none());
+    }
+}
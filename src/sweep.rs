@@ -0,0 +1,573 @@
+use crate::{
+    app::App,
+    config::{ConfigError, FieldError},
+    query::QueriesStats,
+    report::flatten,
+    SimulationConfig,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+};
+
+/// Represents the specification of a parameter sweep.
+///
+/// The specification is a TOML file, e.g.
+///
+/// ```toml
+/// repetitions = 5
+/// metrics = ["retrieve_data_queries_completed", "metrics.retrieve_data.latency"]
+///
+/// [parameters]
+/// "dht.k" = [10, 20]
+/// "dht.alpha" = { start = 1, end = 3 }
+/// "network.delay" = [{ kind = "constant", value = 0.05 }, { kind = "uniform", min = 0.01, max = 0.1 }]
+/// ```
+#[derive(Debug, Clone)]
+pub struct SweepSpec {
+    /// The swept fields of the configuration with their values in TOML syntax,
+    /// ordered by the field names.
+    pub parameters: Vec<(String, Vec<String>)>,
+    /// The seeds every combination of the parameters is run with.
+    /// If it is empty, the seed of the base configuration is used.
+    pub seeds: Vec<u64>,
+    /// The prefixes of the names of the statistics to aggregate.
+    /// All the statistics are aggregated if it is not specified.
+    pub metrics: Option<Vec<String>>,
+}
+
+/// Represents the structure to parse the sweep specification into.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SweepSpecTOML {
+    #[serde(default)]
+    parameters: BTreeMap<String, ParameterValuesTOML>,
+    seeds: Option<Vec<u64>>,
+    repetitions: Option<u64>,
+    metrics: Option<Vec<String>>,
+}
+
+/// Represents the values of a swept parameter: either a list or an inclusive range.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ParameterValuesTOML {
+    List(Vec<toml::Value>),
+    Range {
+        start: toml::Value,
+        end: toml::Value,
+        step: Option<toml::Value>,
+    },
+}
+
+impl SweepSpec {
+    /// Parses the sweep specification from a TOML file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the specification file.
+    /// * `base_seed` - The seed the repetitions are counted from.
+    pub fn from_file(path: impl AsRef<Path>, base_seed: u64) -> Result<Self, ConfigError> {
+        let data = std::fs::read_to_string(path)?;
+        Self::from_toml_str(&data, base_seed)
+    }
+
+    /// Parses the sweep specification from a TOML string.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The contents of the specification file.
+    /// * `base_seed` - The seed the repetitions are counted from: `repetitions = n`
+    ///   is the same as `seeds = [base_seed, ..., base_seed + n - 1]`.
+    pub fn from_toml_str(data: &str, base_seed: u64) -> Result<Self, ConfigError> {
+        let toml: SweepSpecTOML =
+            toml::from_str(data).map_err(|err| ConfigError::Parse(err.to_string()))?;
+        let mut errors = vec![];
+        let mut error = |field: &str, reason: String| {
+            errors.push(FieldError {
+                field: field.to_string(),
+                reason,
+            })
+        };
+
+        let seeds = match (toml.seeds, toml.repetitions) {
+            (Some(_), Some(_)) => {
+                error("repetitions", "conflicts with seeds".to_string());
+                vec![]
+            }
+            (Some(seeds), None) => seeds,
+            (None, Some(0)) => {
+                error("repetitions", "must be positive".to_string());
+                vec![]
+            }
+            (None, Some(repetitions)) => (0..repetitions).map(|i| base_seed + i).collect(),
+            (None, None) => vec![],
+        };
+
+        let mut parameters = vec![];
+        for (name, values) in toml.parameters {
+            let field = format!("parameters.{}", name);
+            match expand_values(values) {
+                Ok(values) if values.is_empty() => error(&field, "has no values".to_string()),
+                Ok(values) => parameters.push((name, values)),
+                Err(reason) => error(&field, reason),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+        Ok(Self {
+            parameters,
+            seeds,
+            metrics: toml.metrics,
+        })
+    }
+
+    /// Returns the Cartesian product of the values of the parameters.
+    /// Every combination is a list of overrides of the configuration.
+    pub fn combinations(&self) -> Vec<Vec<(String, String)>> {
+        let mut combinations = vec![vec![]];
+        for (name, values) in self.parameters.iter() {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push((name.clone(), value.clone()));
+                        combination
+                    })
+                })
+                .collect();
+        }
+        combinations
+    }
+}
+
+/// Expands the values of a parameter into a list of values in TOML syntax.
+fn expand_values(values: ParameterValuesTOML) -> Result<Vec<String>, String> {
+    match values {
+        ParameterValuesTOML::List(values) => Ok(values.iter().map(|v| v.to_string()).collect()),
+        ParameterValuesTOML::Range { start, end, step } => {
+            let step = step.unwrap_or(toml::Value::Integer(1));
+            match (start, end, step) {
+                (
+                    toml::Value::Integer(start),
+                    toml::Value::Integer(end),
+                    toml::Value::Integer(step),
+                ) => {
+                    if step <= 0 {
+                        return Err("step must be positive".to_string());
+                    }
+                    Ok((start..=end)
+                        .step_by(step as usize)
+                        .map(|v| v.to_string())
+                        .collect())
+                }
+                (start, end, step) => {
+                    let (Some(start), Some(end), Some(step)) =
+                        (as_float(&start), as_float(&end), as_float(&step))
+                    else {
+                        return Err("start, end and step of a range must be numbers".to_string());
+                    };
+                    if step <= 0. {
+                        return Err("step must be positive".to_string());
+                    }
+                    // the tolerance keeps the end of the range despite the rounding errors
+                    let count = ((end - start) / step + 1e-9).floor();
+                    if count < 0. {
+                        return Ok(vec![]);
+                    }
+                    Ok((0..=count as u64)
+                        .map(|i| toml::Value::Float(start + i as f64 * step).to_string())
+                        .collect())
+                }
+            }
+        }
+    }
+}
+
+/// Converts the TOML number into `f64`.
+fn as_float(value: &toml::Value) -> Option<f64> {
+    match *value {
+        toml::Value::Integer(v) => Some(v as f64),
+        toml::Value::Float(v) => Some(v),
+        _ => None,
+    }
+}
+
+/// Represents a single run of the sweep.
+#[derive(Debug)]
+pub struct SweepRun {
+    /// The index of the combination of the parameters.
+    pub combination: usize,
+    /// The seed of the run.
+    pub seed: u64,
+    /// The configuration of the run.
+    pub config: SimulationConfig,
+}
+
+/// Represents a parameter sweep: the scenario is run for every combination
+/// of the parameters with every seed, and the results are aggregated per combination.
+pub struct Sweep {
+    spec: SweepSpec,
+    combinations: Vec<Vec<(String, String)>>,
+    runs: Vec<SweepRun>,
+}
+
+impl Sweep {
+    /// Creates a new `Sweep` instance.
+    /// The configurations of all the runs are built and validated in advance.
+    ///
+    /// # Arguments
+    ///
+    /// * `spec` - The specification of the sweep.
+    /// * `config_data` - The contents of the base configuration file.
+    /// * `overrides` - The overrides applied to the base configuration before the swept ones.
+    pub fn new(
+        spec: SweepSpec,
+        config_data: &str,
+        overrides: &[(String, String)],
+    ) -> Result<Self, ConfigError> {
+        let combinations = spec.combinations();
+        let seeds = if spec.seeds.is_empty() {
+            vec![SimulationConfig::from_toml_str_with_overrides(config_data, overrides)?.seed]
+        } else {
+            spec.seeds.clone()
+        };
+        let mut runs = vec![];
+        for (i, combination) in combinations.iter().enumerate() {
+            for &seed in seeds.iter() {
+                let mut run_overrides = overrides.to_vec();
+                run_overrides.extend_from_slice(combination);
+                run_overrides.push(("seed".to_string(), seed.to_string()));
+                let config =
+                    SimulationConfig::from_toml_str_with_overrides(config_data, &run_overrides)?;
+                runs.push(SweepRun {
+                    combination: i,
+                    seed,
+                    config,
+                });
+            }
+        }
+        Ok(Self {
+            spec,
+            combinations,
+            runs,
+        })
+    }
+
+    /// Returns the runs of the sweep.
+    pub fn runs(&self) -> &[SweepRun] {
+        &self.runs
+    }
+
+    /// Places the output files of every run into a separate subdirectory of the given directory.
+    pub fn set_output_dir(&mut self, dir: impl AsRef<Path>) -> std::io::Result<()> {
+        for (i, run) in self.runs.iter_mut().enumerate() {
+            let run_dir = dir.as_ref().join(format!("run-{:04}", i));
+            std::fs::create_dir_all(&run_dir)?;
+            run.config.set_output_dir(run_dir);
+        }
+        Ok(())
+    }
+
+    /// Runs the sweep.
+    ///
+    /// # Arguments
+    ///
+    /// * `threads` - The number of runs executed in parallel.
+    /// * `scenario` - The scenario that is run in every simulation; it returns the statistics.
+    ///
+    /// # Returns
+    ///
+    /// The statistics aggregated per combination of the parameters,
+    /// or the error of the first run whose application could not be created.
+    pub fn run<F>(self, threads: usize, scenario: F) -> Result<SweepReport, ConfigError>
+    where
+        F: Fn(&mut App) -> QueriesStats + Sync,
+    {
+        let total = self.runs.len();
+        let queue = Mutex::new(self.runs.into_iter().enumerate());
+        let (sender, receiver) = mpsc::channel();
+        std::thread::scope(|scope| {
+            for _ in 0..threads.clamp(1, total.max(1)) {
+                let sender = sender.clone();
+                let (queue, scenario) = (&queue, &scenario);
+                scope.spawn(move || loop {
+                    let Some((i, run)) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    let result = App::new(run.config).map(|mut app| {
+                        let stats = scenario(&mut app);
+                        (run.combination, run.seed, stats_values(&stats))
+                    });
+                    sender.send((i, result)).unwrap();
+                });
+            }
+        });
+        drop(sender);
+
+        // the results are sorted to make the report independent of the scheduling of the threads
+        let mut results = receiver.into_iter().collect::<Vec<_>>();
+        results.sort_by_key(|&(i, _)| i);
+        let mut samples = vec![BTreeMap::<String, Vec<f64>>::new(); self.combinations.len()];
+        let mut runs = vec![vec![]; self.combinations.len()];
+        for (_, result) in results {
+            let (combination, seed, values) = result?;
+            runs[combination].push(seed);
+            for (name, value) in values {
+                let selected = self.spec.metrics.as_ref().is_none_or(|metrics| {
+                    metrics
+                        .iter()
+                        .any(|prefix| name.starts_with(prefix.as_str()))
+                });
+                if selected {
+                    samples[combination].entry(name).or_default().push(value);
+                }
+            }
+        }
+
+        Ok(SweepReport {
+            parameters: self
+                .spec
+                .parameters
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect(),
+            points: self
+                .combinations
+                .into_iter()
+                .zip(runs)
+                .zip(samples)
+                .map(|((combination, seeds), samples)| SweepPoint {
+                    values: combination.into_iter().map(|(_, value)| value).collect(),
+                    seeds,
+                    metrics: samples
+                        .into_iter()
+                        .map(|(name, values)| MetricSummary::from_samples(name, &values))
+                        .collect(),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Returns the numeric statistics of a run by their dot-separated names.
+fn stats_values(stats: &QueriesStats) -> Vec<(String, f64)> {
+    let mut rows = vec![];
+    flatten("", &serde_json::to_value(stats).unwrap(), &mut rows);
+    rows.into_iter()
+        .filter_map(|(name, value)| match value {
+            Value::Number(number) => Some((name[1..].to_string(), number.as_f64()?)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Represents the results of a parameter sweep.
+#[derive(Debug)]
+pub struct SweepReport {
+    /// The names of the swept parameters.
+    pub parameters: Vec<String>,
+    /// The aggregated results of every combination of the parameters.
+    pub points: Vec<SweepPoint>,
+}
+
+/// Represents the aggregated results of a single combination of the parameters.
+#[derive(Debug)]
+pub struct SweepPoint {
+    /// The values of the parameters in TOML syntax, in the order of `SweepReport::parameters`.
+    pub values: Vec<String>,
+    /// The seeds of the runs.
+    pub seeds: Vec<u64>,
+    /// The summaries of the statistics.
+    pub metrics: Vec<MetricSummary>,
+}
+
+/// Represents the summary of a statistic over the runs of a combination of the parameters.
+#[derive(Debug, Clone)]
+pub struct MetricSummary {
+    /// The dot-separated name of the statistic, e.g. `metrics.find_node.latency.p50`.
+    pub name: String,
+    /// The number of runs the statistic is defined in.
+    pub runs: usize,
+    /// The mean value over the runs.
+    pub mean: f64,
+    /// The sample standard deviation, or 0 if there is a single run.
+    pub std_dev: f64,
+    /// The half-width of the 95% confidence interval of the mean,
+    /// based on the Student's t-distribution.
+    pub ci95: f64,
+}
+
+impl MetricSummary {
+    /// Summarizes the values of a statistic.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the statistic.
+    /// * `values` - The values of the statistic in the runs; there must be at least one.
+    pub fn from_samples(name: String, values: &[f64]) -> Self {
+        let n = values.len();
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let (std_dev, ci95) = if n > 1 {
+            let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+            let std_dev = variance.sqrt();
+            (std_dev, student_t_975(n - 1) * std_dev / (n as f64).sqrt())
+        } else {
+            (0., 0.)
+        };
+        Self {
+            name,
+            runs: n,
+            mean,
+            std_dev,
+            ci95,
+        }
+    }
+}
+
+/// Returns the 97.5% quantile of the Student's t-distribution with the given degrees of freedom.
+fn student_t_975(df: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+    match df {
+        0 => f64::NAN,
+        1..=30 => TABLE[df - 1],
+        _ => 1.96,
+    }
+}
+
+impl SweepReport {
+    /// Writes the report to the CSV file with a row per combination of the parameters and statistic.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let mut header = self.parameters.clone();
+        header.extend(
+            ["metric", "runs", "mean", "std_dev", "ci95_low", "ci95_high"].map(str::to_string),
+        );
+        writeln!(
+            writer,
+            "{}",
+            header
+                .iter()
+                .map(|s| escape_csv(s))
+                .collect::<Vec<_>>()
+                .join(",")
+        )?;
+        for point in self.points.iter() {
+            let values = point
+                .values
+                .iter()
+                .map(|value| escape_csv(value))
+                .collect::<Vec<_>>();
+            for metric in point.metrics.iter() {
+                let mut row = values.clone();
+                row.push(metric.name.clone());
+                row.push(metric.runs.to_string());
+                row.push(metric.mean.to_string());
+                row.push(metric.std_dev.to_string());
+                row.push((metric.mean - metric.ci95).to_string());
+                row.push((metric.mean + metric.ci95).to_string());
+                writeln!(writer, "{}", row.join(","))?;
+            }
+        }
+        writer.flush()
+    }
+
+    /// Returns the default path of the report in the given directory.
+    pub fn default_path(dir: impl AsRef<Path>) -> PathBuf {
+        dir.as_ref().join("sweep.csv")
+    }
+}
+
+/// Quotes the string for CSV if needed.
+fn escape_csv(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combinations() {
+        let spec = SweepSpec::from_toml_str(
+            r#"
+            repetitions = 3
+            [parameters]
+            "dht.k" = [10, 20]
+            "dht.alpha" = { start = 1, end = 3 }
+            delay_mean = { start = 0.1, end = 0.3, step = 0.1 }
+            "#,
+            7,
+        )
+        .unwrap();
+        assert_eq!(spec.seeds, vec![7, 8, 9]);
+        assert_eq!(spec.parameters[0].0, "delay_mean");
+        assert_eq!(spec.parameters[0].1.len(), 3);
+        assert_eq!(spec.parameters[1].1, vec!["1", "2", "3"]);
+        let combinations = spec.combinations();
+        assert_eq!(combinations.len(), 18);
+        assert_eq!(
+            combinations[1],
+            vec![
+                ("delay_mean".to_string(), "0.1".to_string()),
+                ("dht.alpha".to_string(), "1".to_string()),
+                ("dht.k".to_string(), "20".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_confidence_interval() {
+        let summary = MetricSummary::from_samples("x".to_string(), &[1., 2., 3.]);
+        assert_eq!(summary.mean, 2.);
+        assert_eq!(summary.std_dev, 1.);
+        assert!((summary.ci95 - 4.303 / 3f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parallel_runs() {
+        let spec = SweepSpec::from_toml_str(
+            r#"
+            seeds = [1, 2]
+            metrics = ["find_node_queries"]
+            [parameters]
+            num_peers = [10, 20]
+            "#,
+            0,
+        )
+        .unwrap();
+        let overrides = [("log_level_filter".to_string(), "'off'".to_string())];
+        let sweep = Sweep::new(spec, include_str!("../config.toml"), &overrides).unwrap();
+        assert_eq!(sweep.runs().len(), 4);
+        let report = sweep
+            .run(3, |app| {
+                app.peers()[0].borrow_mut().publish_data("data".to_string());
+                app.summarize_stats()
+            })
+            .unwrap();
+        assert_eq!(report.parameters, vec!["num_peers"]);
+        assert_eq!(report.points.len(), 2);
+        assert_eq!(report.points[1].values, vec!["20"]);
+        assert_eq!(report.points[1].seeds, vec![1, 2]);
+        let metrics = &report.points[1].metrics;
+        assert!(!metrics.is_empty());
+        assert!(metrics
+            .iter()
+            .all(|m| m.name.starts_with("find_node_queries") && m.runs == 2));
+    }
+}
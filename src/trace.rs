@@ -0,0 +1,219 @@
+use crate::{
+    message::MessageInfo,
+    query::{QueryId, QueryKind},
+    PeerId,
+};
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    rc::Rc,
+};
+
+/// Represents a transition of a query to another state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryTransition {
+    Started,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Represents a single event of the trace.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TraceEvent {
+    /// A message was sent by `src` to `dst`.
+    Sent {
+        time: f64,
+        src: PeerId,
+        dst: PeerId,
+        #[serde(flatten)]
+        message: MessageInfo,
+    },
+    /// A message sent by `src` was received by `dst`.
+    Received {
+        time: f64,
+        src: PeerId,
+        dst: PeerId,
+        #[serde(flatten)]
+        message: MessageInfo,
+    },
+    /// A query of the peer changed its state.
+    Query {
+        time: f64,
+        peer_id: PeerId,
+        query_id: QueryId,
+        kind: QueryKind,
+        transition: QueryTransition,
+    },
+}
+
+/// Restricts the trace to a subset of the queries and peers.
+/// An event is recorded only if it passes both of the filters.
+#[derive(Debug, Default, Clone)]
+pub struct TraceFilter {
+    /// The traced queries as the pairs of the peer that started the query and its ID,
+    /// since every peer assigns the query IDs independently, or `None` to trace all the queries.
+    /// Messages that do not belong to any query are not traced if it is set.
+    pub queries: Option<HashSet<(PeerId, QueryId)>>,
+    /// The IDs of the traced peers, or `None` to trace all the peers.
+    /// A message is traced if either its source or destination is traced.
+    pub peers: Option<HashSet<PeerId>>,
+}
+
+impl TraceFilter {
+    /// Checks if the event passes the filter.
+    pub fn matches(&self, event: &TraceEvent) -> bool {
+        let (query, peer_matches) = match event {
+            TraceEvent::Sent {
+                src, dst, message, ..
+            }
+            | TraceEvent::Received {
+                src, dst, message, ..
+            } => {
+                // the requests are sent by the peer that started the query,
+                // and the responses are sent back to it
+                let initiator = if message.kind.is_request() {
+                    *src
+                } else {
+                    *dst
+                };
+                (
+                    message.query_id.map(|id| (initiator, id)),
+                    self.matches_peer(*src) || self.matches_peer(*dst),
+                )
+            }
+            TraceEvent::Query {
+                peer_id, query_id, ..
+            } => (Some((*peer_id, *query_id)), self.matches_peer(*peer_id)),
+        };
+        let query_matches = match &self.queries {
+            Some(queries) => query.is_some_and(|query| queries.contains(&query)),
+            None => true,
+        };
+        query_matches && peer_matches
+    }
+
+    fn matches_peer(&self, peer_id: PeerId) -> bool {
+        self.peers
+            .as_ref()
+            .is_none_or(|peers| peers.contains(&peer_id))
+    }
+}
+
+struct TraceRecorder {
+    writer: Box<dyn Write>,
+    filter: TraceFilter,
+    events_recorded: u64,
+}
+
+/// Records the trace of the messages and query state transitions as JSON Lines,
+/// one event per line. The tracer is shared by all the peers of the simulation.
+#[derive(Clone)]
+pub struct Tracer {
+    recorder: Rc<RefCell<TraceRecorder>>,
+}
+
+impl Tracer {
+    /// Creates a new `Tracer` writing the trace to the given writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The destination of the trace.
+    /// * `filter` - The filter of the recorded events.
+    pub fn new(writer: impl Write + 'static, filter: TraceFilter) -> Self {
+        Self {
+            recorder: Rc::new(RefCell::new(TraceRecorder {
+                writer: Box::new(writer),
+                filter,
+                events_recorded: 0,
+            })),
+        }
+    }
+
+    /// Creates a new `Tracer` writing the trace to the file at the given path.
+    pub fn to_file(path: impl AsRef<Path>, filter: TraceFilter) -> std::io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?), filter))
+    }
+
+    /// Records the event if it passes the filter.
+    /// Write errors are logged and do not interrupt the simulation.
+    pub fn record(&self, event: TraceEvent) {
+        let mut recorder = self.recorder.borrow_mut();
+        if !recorder.filter.matches(&event) {
+            return;
+        }
+        let result = serde_json::to_writer(&mut recorder.writer, &event)
+            .map_err(std::io::Error::from)
+            .and_then(|_| recorder.writer.write_all(b"\n"));
+        match result {
+            Ok(()) => recorder.events_recorded += 1,
+            Err(err) => log::error!("Failed to write trace event: {}", err),
+        }
+    }
+
+    /// Returns the number of events written to the trace.
+    pub fn events_recorded(&self) -> u64 {
+        self.recorder.borrow().events_recorded
+    }
+
+    /// Flushes the buffered events to the destination.
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.recorder.borrow_mut().writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::MessageKind;
+
+    fn message(kind: MessageKind, src: PeerId, dst: PeerId, query_id: Option<u64>) -> TraceEvent {
+        TraceEvent::Sent {
+            time: 0.,
+            src,
+            dst,
+            message: MessageInfo {
+                kind,
+                query_id: query_id.map(QueryId::from),
+                size: 40,
+            },
+        }
+    }
+
+    fn sent(src: PeerId, dst: PeerId, query_id: Option<u64>) -> TraceEvent {
+        message(MessageKind::FindNodeRequest, src, dst, query_id)
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = TraceFilter {
+            queries: Some(HashSet::from([(7, QueryId::from(1))])),
+            peers: Some(HashSet::from([7])),
+        };
+        assert!(filter.matches(&sent(7, 2, Some(1))));
+        assert!(filter.matches(&message(MessageKind::FindNodeResponse, 2, 7, Some(1))));
+        // the query with the same ID started by another peer
+        assert!(!filter.matches(&sent(2, 7, Some(1))));
+        assert!(!filter.matches(&message(MessageKind::FindNodeResponse, 7, 2, Some(1))));
+        assert!(!filter.matches(&sent(2, 3, Some(1))));
+        assert!(!filter.matches(&sent(7, 2, Some(2))));
+        assert!(!filter.matches(&sent(7, 2, None)));
+        assert!(TraceFilter::default().matches(&sent(2, 3, None)));
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let event = sent(1, 2, Some(3));
+        let line = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            line,
+            r#"{"event":"sent","time":0.0,"src":1,"dst":2,"kind":"FindNodeRequest","query_id":3,"size":40}"#
+        );
+    }
+}